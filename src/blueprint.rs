@@ -0,0 +1,132 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{bail, ensure, Context};
+use cgmath::Vector3;
+
+use crate::save::chunk_format;
+use crate::voxel_things::{region::Clipboard, voxel::Voxel};
+
+const MAGIC: &[u8; 4] = b"VXBP";
+const VERSION: u16 = 1;
+pub const EXTENSION: &str = "blueprint";
+pub const DEFAULT_DIRECTORY: &str = "blueprints";
+
+/// A directory of saved clipboards, one `<name>.blueprint` file each.
+///
+/// The files start with `VXBP`, a little endian u16 version and the size as three u32s. The
+/// palette follows, saved the same way as in chunks: a u8 count, then each block's name prefixed
+/// by its u8 length. Last comes one u8 palette index per voxel in the order of
+/// `Clipboard::voxels`. Blocks are stored by name so reordering `BlockType` doesn't break
+/// existing files.
+#[derive(Debug, Clone)]
+pub struct BlueprintLibrary {
+    pub directory: PathBuf,
+}
+
+impl Default for BlueprintLibrary {
+    fn default() -> Self {
+        Self::new(DEFAULT_DIRECTORY)
+    }
+}
+
+impl BlueprintLibrary {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self { directory: directory.into() }
+    }
+
+    fn path(&self, name: &str) -> anyhow::Result<PathBuf> {
+        ensure!(!name.is_empty() && name.chars().all(|character| character.is_ascii_alphanumeric() || character == '-' || character == '_'), "blueprint names can only use letters, digits, '-' and '_', got '{}'", name);
+        Ok(self.directory.join(name).with_extension(EXTENSION))
+    }
+
+    pub fn save(&self, name: &str, clipboard: &Clipboard) -> anyhow::Result<PathBuf> {
+        let path = self.path(name)?;
+        fs::create_dir_all(&self.directory).with_context(|| format!("failed to create {}", self.directory.display()))?;
+        fs::write(&path, encode(clipboard)).with_context(|| format!("failed to write {}", path.display()))?;
+        Ok(path)
+    }
+
+    pub fn load(&self, name: &str) -> anyhow::Result<Clipboard> {
+        let path = self.path(name)?;
+        let bytes = fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+        decode(&bytes).with_context(|| format!("{} is not a valid blueprint", path.display()))
+    }
+
+    pub fn delete(&self, name: &str) -> anyhow::Result<()> {
+        let path = self.path(name)?;
+        fs::remove_file(&path).with_context(|| format!("failed to delete {}", path.display()))
+    }
+
+    /// Names of the saved blueprints in alphabetical order, empty if the directory doesn't exist yet.
+    pub fn list(&self) -> anyhow::Result<Vec<String>> {
+        if !self.directory.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&self.directory).with_context(|| format!("failed to list {}", self.directory.display()))? {
+            let path = entry?.path();
+            if path.extension().and_then(|extension| extension.to_str()) == Some(EXTENSION) {
+                if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+}
+
+pub fn encode(clipboard: &Clipboard) -> Vec<u8> {
+    let (palette, indices) = chunk_format::encode_palette(clipboard.voxels());
+
+    let mut bytes = Vec::with_capacity(palette.len() + indices.len() + 18);
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    for axis in 0..3 {
+        bytes.extend_from_slice(&(clipboard.size()[axis] as u32).to_le_bytes());
+    }
+    bytes.extend_from_slice(&palette);
+    bytes.extend_from_slice(&indices);
+    bytes
+}
+
+pub fn decode(bytes: &[u8]) -> anyhow::Result<Clipboard> {
+    let mut reader = Reader { bytes };
+    ensure!(reader.take(4)? == MAGIC, "missing blueprint header");
+    let version = u16::from_le_bytes(reader.array()?);
+    ensure!(version == VERSION, "unsupported blueprint version {}", version);
+
+    let mut size = Vector3::new(0, 0, 0);
+    for axis in 0..3 {
+        size[axis] = i32::try_from(u32::from_le_bytes(reader.array()?)).context("blueprint is too big")?;
+    }
+
+    let (palette, indices) = chunk_format::decode_palette(reader.bytes)?;
+    let voxels = indices
+        .iter()
+        .map(|index| palette.get(*index as usize).map(|block_type| Voxel::new(*block_type)).with_context(|| format!("palette index {} out of range", index)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    match Clipboard::from_voxels(size, voxels) {
+        Some(clipboard) => Ok(clipboard),
+        None => bail!("voxel count doesn't match the size {}x{}x{}", size.x, size.y, size.z),
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> anyhow::Result<&'a [u8]> {
+        ensure!(self.bytes.len() >= count, "blueprint ends early");
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+        Ok(self.take(N)?.try_into()?)
+    }
+}
@@ -0,0 +1,144 @@
+use std::fmt;
+
+use cgmath::{InnerSpace, Vector2, Vector3};
+
+use crate::voxel_things::{
+    hash,
+    region::Region,
+    voxel::{BlockType, Voxel},
+    world::{EditSummary, World},
+};
+
+pub const MIN_RADIUS: f32 = 0.5;
+pub const MAX_RADIUS: f32 = 16.0;
+/// Separates the brush dither from other users of `hash::hash_position`.
+const DITHER_SALT: u32 = 0xb205;
+/// Smoothing fills a voxel when more than this many of its 26 neighbors are solid and erodes it
+/// when fewer are.
+const SMOOTH_MAJORITY: usize = 13;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrushShape {
+    Sphere,
+    Cube,
+    /// Upright, as tall as it is wide.
+    Cylinder,
+}
+
+impl BrushShape {
+    pub const ALL: [BrushShape; 3] = [BrushShape::Sphere, BrushShape::Cube, BrushShape::Cylinder];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrushMode {
+    /// Fills empty voxels with the block.
+    Add,
+    /// Empties solid voxels.
+    Remove,
+    /// Changes the block of solid voxels without changing the shape.
+    Paint,
+    /// Erodes voxels most of whose neighbors are empty and fills the ones most of whose neighbors
+    /// are solid.
+    Smooth,
+}
+
+impl BrushMode {
+    pub const ALL: [BrushMode; 4] = [BrushMode::Add, BrushMode::Remove, BrushMode::Paint, BrushMode::Smooth];
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Brush {
+    pub shape: BrushShape,
+    pub mode: BrushMode,
+    /// In voxels, from the center voxel to the edge of the shape.
+    pub radius: f32,
+    /// Part of the radius, from the edge inwards, over which the brush fades out. Faded voxels are
+    /// dithered, so a soft brush leaves a rough edge.
+    pub falloff: f32,
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Self {
+            shape: BrushShape::Sphere,
+            mode: BrushMode::Add,
+            radius: 3.0,
+            falloff: 0.0,
+        }
+    }
+}
+
+impl fmt::Display for Brush {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} {:?} brush, radius {:.1}, falloff {:.0}%", self.mode, self.shape, self.radius, self.falloff * 100.0)
+    }
+}
+
+impl Brush {
+    /// How strongly the brush acts at `offset` from its center, 0 outside of the shape.
+    pub fn strength(&self, offset: Vector3<i32>) -> f32 {
+        let offset = offset.cast::<f32>().unwrap() / self.radius.max(MIN_RADIUS);
+        let distance = match self.shape {
+            BrushShape::Sphere => offset.magnitude(),
+            BrushShape::Cube => offset.x.abs().max(offset.y.abs()).max(offset.z.abs()),
+            BrushShape::Cylinder => Vector2::new(offset.x, offset.z).magnitude().max(offset.y.abs()),
+        };
+
+        if distance > 1.0 {
+            0.0
+        } else if self.falloff <= 0.0 {
+            1.0
+        } else {
+            ((1.0 - distance) / self.falloff).min(1.0)
+        }
+    }
+
+    /// Every position the brush touches around `center`, with the falloff dithered in.
+    pub fn positions(&self, center: Vector3<i32>) -> impl Iterator<Item = Vector3<i32>> + '_ {
+        let reach = self.radius.max(MIN_RADIUS).ceil() as i32;
+        let reach = Vector3::new(reach, reach, reach);
+        Region::new(center - reach, center + reach).positions().filter(move |position| {
+            let strength = self.strength(position - center);
+            strength > 0.0 && strength >= hash::hash_position_unit(*position, DITHER_SALT, 0)
+        })
+    }
+
+    /// Applies one dab of the brush around `center`, using `block` for added and painted voxels.
+    pub fn apply(&self, world: &mut World, center: Vector3<i32>, block: BlockType) -> EditSummary {
+        let voxel = Voxel::new(block);
+        let edits = match self.mode {
+            BrushMode::Add => self.positions(center).filter(|position| world.get_voxel(*position).is_empty()).map(|position| (position, voxel)).collect(),
+            BrushMode::Remove => self.positions(center).filter(|position| !world.get_voxel(*position).is_empty()).map(|position| (position, Voxel::new_empty())).collect(),
+            BrushMode::Paint => self.positions(center).filter(|position| !world.get_voxel(*position).is_empty()).map(|position| (position, voxel)).collect(),
+            BrushMode::Smooth => self.positions(center).filter_map(|position| smoothed(world, position).map(|voxel| (position, voxel))).collect::<Vec<_>>(),
+        };
+        world.set_voxels(edits)
+    }
+}
+
+/// What smoothing turns the voxel at `position` into, if it changes. All neighbors are read before
+/// anything is written, so the result doesn't depend on the order voxels are visited in.
+fn smoothed(world: &World, position: Vector3<i32>) -> Option<Voxel> {
+    // Indexed by `BlockType as usize`, which matches the order of `BlockType::ALL`.
+    let mut counts = [0; BlockType::ALL.len()];
+    for x in -1..=1 {
+        for y in -1..=1 {
+            for z in -1..=1 {
+                if (x, y, z) != (0, 0, 0) {
+                    counts[world.get_voxel(position + Vector3::new(x, y, z)).block_type as usize] += 1;
+                }
+            }
+        }
+    }
+    let solid = counts[BlockType::Empty as usize + 1..].iter().sum::<usize>();
+
+    let voxel = world.get_voxel(position);
+    if !voxel.is_empty() && solid < SMOOTH_MAJORITY {
+        Some(Voxel::new_empty())
+    } else if voxel.is_empty() && solid > SMOOTH_MAJORITY {
+        let most_common = (1..counts.len()).max_by_key(|index| counts[*index])?;
+        Some(Voxel::new(BlockType::ALL[most_common]))
+    } else {
+        None
+    }
+}
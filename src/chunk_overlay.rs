@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use cgmath::{EuclideanSpace, Point3, Vector3};
+use winit::event::{ElementState, VirtualKeyCode};
+
+use crate::debug_draw::{self, DebugDraw};
+use crate::text::TextRenderer;
+use crate::voxel_things::{chunk::{ChunkStats, CHUNK_SIZE}, world::World};
+
+const GRID_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 0.6];
+const MARGIN: f32 = 8.0;
+const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.45];
+
+/// Chunk borders around the camera and the mesher's numbers for each chunk, toggled with F3.
+pub struct ChunkOverlay {
+    pub enabled: bool,
+    /// How many chunks away from the camera's chunk borders are drawn.
+    pub radius: i32,
+    stats: HashMap<Vector3<i32>, ChunkStats>,
+}
+
+impl Default for ChunkOverlay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChunkOverlay {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            radius: 1,
+            stats: HashMap::new(),
+        }
+    }
+
+    pub fn process_keyboard(&mut self, key: VirtualKeyCode, state: ElementState) -> bool {
+        if key != VirtualKeyCode::F3 || state != ElementState::Pressed {
+            return false;
+        }
+
+        self.enabled = !self.enabled;
+        true
+    }
+
+    /// Keeps the latest stats of a chunk, called whenever it is meshed.
+    pub fn record(&mut self, position: Vector3<i32>, stats: ChunkStats) {
+        self.stats.insert(position, stats);
+    }
+
+    pub fn stats(&self, position: Vector3<i32>) -> Option<&ChunkStats> {
+        self.stats.get(&position)
+    }
+
+    pub fn all_stats(&self) -> &HashMap<Vector3<i32>, ChunkStats> {
+        &self.stats
+    }
+
+    /// Draws the borders of the loaded chunks near the camera.
+    pub fn update(&mut self, world: &World, camera_position: Point3<f32>, debug_draw: &mut DebugDraw) {
+        if !self.enabled {
+            return;
+        }
+
+        let camera_chunk = camera_chunk(camera_position);
+        for x in -self.radius..=self.radius {
+            for y in -self.radius..=self.radius {
+                for z in -self.radius..=self.radius {
+                    let position = camera_chunk + Vector3::new(x, y, z);
+                    let Some(chunk) = world.chunks.get(&position) else {
+                        continue;
+                    };
+
+                    let min = Point3::from_vec(chunk.world_offset());
+                    let max = min + Vector3::new(1.0, 1.0, 1.0) * CHUNK_SIZE as f32;
+                    let color = if position == camera_chunk { debug_draw::YELLOW } else { GRID_COLOR };
+                    debug_draw.aabb(min, max, color);
+                }
+            }
+        }
+    }
+
+    /// The stats of the chunk the camera is in, in the top right corner.
+    pub fn draw(&self, text: &mut TextRenderer, camera_position: Point3<f32>, screen_width: u32) {
+        if !self.enabled {
+            return;
+        }
+
+        let camera_chunk = camera_chunk(camera_position);
+        let lines = match self.stats.get(&camera_chunk) {
+            Some(stats) => format_stats(camera_chunk, stats),
+            None => format!("chunk [{}, {}, {}]: not meshed", camera_chunk.x, camera_chunk.y, camera_chunk.z),
+        };
+
+        let [width, height] = text.measure(&lines);
+        let left = screen_width as f32 - width - MARGIN;
+        text.queue_rect([left - MARGIN / 2.0, MARGIN / 2.0], [left + width + MARGIN / 2.0, height + MARGIN * 1.5], BACKGROUND_COLOR);
+        text.queue(&lines, [left, MARGIN], TEXT_COLOR);
+    }
+}
+
+fn camera_chunk(camera_position: Point3<f32>) -> Vector3<i32> {
+    let camera_voxel = Vector3::new(camera_position.x.floor() as i32, camera_position.y.floor() as i32, camera_position.z.floor() as i32);
+    World::split_position(camera_voxel).0
+}
+
+pub fn format_stats(position: Vector3<i32>, stats: &ChunkStats) -> String {
+    format!(
+        "chunk [{}, {}, {}]\n{} vertices, {} indices\n{} translucent vertices, {} translucent indices\nbuilt in {:.2?}, {:?} storage",
+        position.x,
+        position.y,
+        position.z,
+        stats.vertex_count,
+        stats.index_count,
+        stats.translucent_vertex_count,
+        stats.translucent_index_count,
+        stats.build_time,
+        stats.storage,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel_things::chunk::{self, Chunk, ChunkMesh};
+    use crate::voxel_things::voxel::{BlockType, Voxel};
+
+    #[test]
+    fn records_the_counts_of_a_meshed_chunk() {
+        let position = Vector3::new(0, 0, 0);
+        let mut world = World::new(0);
+        world.chunks.insert(position, Chunk::new(position));
+        // Two stones side by side hide the faces between them, leaving ten.
+        world.set_voxel(Vector3::new(4, 4, 4), Voxel::new(BlockType::Stone));
+        world.set_voxel(Vector3::new(5, 4, 4), Voxel::new(BlockType::Stone));
+
+        let (mut mesh, mut translucent_mesh) = (ChunkMesh::new(), ChunkMesh::new());
+        let mut overlay = ChunkOverlay::new();
+        overlay.record(position, chunk::mesh_chunk(&world, &world.chunks[&position], &mut mesh, &mut translucent_mesh));
+
+        let stats = overlay.stats(position).expect("the chunk was recorded");
+        assert_eq!(stats.vertex_count, 10 * 4);
+        assert_eq!(stats.index_count, 10 * 6);
+        assert_eq!((stats.translucent_vertex_count, stats.translucent_index_count), (0, 0));
+        assert_eq!((mesh.vertices.len(), mesh.indices.len()), (stats.vertex_count, stats.index_count));
+        assert!(overlay.stats(Vector3::new(1, 0, 0)).is_none());
+    }
+}
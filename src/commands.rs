@@ -0,0 +1,714 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use cgmath::{EuclideanSpace, Point3, Vector3};
+
+use crate::blueprint::BlueprintLibrary;
+use crate::camera::Camera;
+use crate::editor::{Editor, SelectionShape, Tool};
+use crate::mesh_export::{self, MeshFormat};
+use crate::save::{chunk_format::Compression, Autosave, WorldMetadata, WorldSave};
+use crate::vox::{self, VoxFile};
+use crate::voxelizer::{self, VoxelizeOptions};
+use crate::voxel_things::{
+    region::{self, Axis, Clipboard, Region},
+    chunk::CHUNK_SIZE,
+    voxel,
+    world::World,
+};
+
+/// Handled by the registry itself since it lists the other commands.
+const HELP: &str = "help";
+/// Most voxels a single region edit may touch, so a typo doesn't freeze the engine.
+const MAX_EDIT_VOLUME: i64 = 1 << 20;
+
+/// The parts of the engine commands can act on.
+pub struct CommandContext<'a> {
+    pub world: &'a mut World,
+    pub camera: &'a mut Camera,
+    pub editor: &'a mut Editor,
+    /// Hours after midnight, see `light::sun_position`.
+    pub time_of_day: &'a mut f32,
+    /// What `copy` stored for `paste`.
+    pub clipboard: &'a mut Option<Clipboard>,
+    pub blueprints: &'a BlueprintLibrary,
+    /// Where `save` writes the world and `load` reads it from.
+    pub world_save: &'a mut WorldSave,
+    pub autosave: &'a mut Autosave,
+    /// `screenshot` stores the requested path here and the renderer saves the next frame to it,
+    /// `None` when running without a renderer.
+    pub screenshot: Option<&'a mut Option<PathBuf>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgumentKind {
+    Integer,
+    Float,
+    /// A block name like "stone".
+    Block,
+    /// One of a fixed set of words.
+    Keyword(&'static [&'static str]),
+    /// Any single word, e.g. a file name.
+    Text,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Argument {
+    pub name: &'static str,
+    pub kind: ArgumentKind,
+    /// Optional arguments can only be followed by other optional arguments.
+    pub optional: bool,
+}
+
+impl Argument {
+    pub const fn required(name: &'static str, kind: ArgumentKind) -> Self {
+        Self {
+            name,
+            kind,
+            optional: false,
+        }
+    }
+
+    pub const fn optional(name: &'static str, kind: ArgumentKind) -> Self {
+        Self {
+            name,
+            kind,
+            optional: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Integer(i32),
+    Float(f32),
+    Block(voxel::BlockType),
+    Word(String),
+}
+
+/// Arguments parsed according to a command's `Argument` list, missing optional ones are left out.
+#[derive(Debug, Clone, Default)]
+pub struct Arguments {
+    values: Vec<Value>,
+}
+
+impl Arguments {
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Value> {
+        self.values.get(index)
+    }
+
+    /// Integer at `index`, panics if the command declared a different kind there.
+    pub fn integer(&self, index: usize) -> i32 {
+        match self.values[index] {
+            Value::Integer(value) => value,
+            ref value => panic!("argument {} is {:?}, not an integer", index, value),
+        }
+    }
+
+    pub fn float(&self, index: usize) -> f32 {
+        match self.values[index] {
+            Value::Float(value) => value,
+            Value::Integer(value) => value as f32,
+            ref value => panic!("argument {} is {:?}, not a float", index, value),
+        }
+    }
+
+    pub fn block(&self, index: usize) -> voxel::BlockType {
+        match self.values[index] {
+            Value::Block(value) => value,
+            ref value => panic!("argument {} is {:?}, not a block", index, value),
+        }
+    }
+
+    pub fn word(&self, index: usize) -> &str {
+        match &self.values[index] {
+            Value::Word(value) => value,
+            value => panic!("argument {} is {:?}, not a word", index, value),
+        }
+    }
+}
+
+pub type CommandHandler = Box<dyn Fn(&mut CommandContext, &Arguments) -> anyhow::Result<String>>;
+
+pub struct Command {
+    pub name: String,
+    pub description: String,
+    pub arguments: Vec<Argument>,
+    handler: CommandHandler,
+}
+
+impl Command {
+    /// e.g. `fill <x1> <y1> <z1> <x2> <y2> <z2> <block>`, optional arguments are in square brackets.
+    pub fn usage(&self) -> String {
+        let mut usage = self.name.clone();
+        for argument in &self.arguments {
+            let name = match argument.kind {
+                ArgumentKind::Keyword(words) => words.join("|"),
+                _ => argument.name.to_string(),
+            };
+            if argument.optional {
+                usage += &format!(" [{}]", name);
+            } else {
+                usage += &format!(" <{}>", name);
+            }
+        }
+        usage
+    }
+
+    fn parse(&self, words: &[&str]) -> anyhow::Result<Arguments> {
+        let required = self.arguments.iter().filter(|argument| !argument.optional).count();
+        if words.len() < required || words.len() > self.arguments.len() {
+            bail!("usage: {}", self.usage());
+        }
+
+        let values = words
+            .iter()
+            .zip(&self.arguments)
+            .map(|(word, argument)| parse_value(word, argument).with_context(|| format!("usage: {}", self.usage())))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Arguments { values })
+    }
+}
+
+fn parse_value(word: &str, argument: &Argument) -> anyhow::Result<Value> {
+    Ok(match argument.kind {
+        ArgumentKind::Integer => Value::Integer(word.parse().with_context(|| format!("{} must be a whole number, got '{}'", argument.name, word))?),
+        ArgumentKind::Float => Value::Float(word.parse().with_context(|| format!("{} must be a number, got '{}'", argument.name, word))?),
+        ArgumentKind::Block => Value::Block(voxel::BlockType::from_name(word).with_context(|| format!("unknown block '{}'", word))?),
+        ArgumentKind::Keyword(words) => {
+            if !words.contains(&word) {
+                bail!("{} must be one of {}, got '{}'", argument.name, words.join(", "), word);
+            }
+            Value::Word(word.to_string())
+        }
+        ArgumentKind::Text => Value::Word(word.to_string()),
+    })
+}
+
+/// Words `kind` can be completed to.
+fn candidates(kind: ArgumentKind) -> Vec<&'static str> {
+    match kind {
+        ArgumentKind::Block => voxel::BlockType::ALL.iter().map(|block_type| block_type.definition().name).collect(),
+        ArgumentKind::Keyword(words) => words.to_vec(),
+        ArgumentKind::Integer | ArgumentKind::Float | ArgumentKind::Text => Vec::new(),
+    }
+}
+
+/// Named commands typed into the console or read from stdin.
+#[derive(Default)]
+pub struct CommandRegistry {
+    commands: BTreeMap<String, Command>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry with `tp`, `seed`, `time`, `regen`, `save`, `load`, `autosave`, `compression`, `screenshot` and the region edits `fill`,
+    /// `hollow`, `walls`, `replace`, `copy`, `paste`, `rotate`, `mirror`, `undo`, `redo`, `select` and
+    /// `blueprint`. `help` is always available.
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        register_builtins(&mut registry);
+        registry
+    }
+
+    /// Adds a command, replacing any existing one with the same name.
+    pub fn register(&mut self, name: &str, description: &str, arguments: Vec<Argument>, handler: impl Fn(&mut CommandContext, &Arguments) -> anyhow::Result<String> + 'static) {
+        let command = Command {
+            name: name.to_string(),
+            description: description.to_string(),
+            arguments,
+            handler: Box::new(handler),
+        };
+        self.commands.insert(command.name.clone(), command);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Command> {
+        self.commands.get(name)
+    }
+
+    pub fn commands(&self) -> impl Iterator<Item = &Command> {
+        self.commands.values()
+    }
+
+    /// Parses and runs one line, returning what the command reported.
+    pub fn execute(&self, context: &mut CommandContext, line: &str) -> anyhow::Result<String> {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let Some((name, words)) = words.split_first() else {
+            return Ok(String::new());
+        };
+
+        if *name == HELP {
+            return match words {
+                [] => self.help(None),
+                [command] => self.help(Some(command)),
+                _ => bail!("usage: help [command]"),
+            };
+        }
+
+        let command = self.commands.get(*name).with_context(|| format!("unknown command '{}', try 'help'", name))?;
+        let arguments = command.parse(words)?;
+
+        // Everything one command changes is undone together.
+        context.world.history.begin();
+        let result = (command.handler)(context, &arguments);
+        context.world.history.end();
+        result
+    }
+
+    /// Completions of the last word in `line`, each one is the whole line with that word replaced.
+    pub fn complete(&self, line: &str) -> Vec<String> {
+        let mut words = line.split_whitespace().collect::<Vec<_>>();
+        if line.is_empty() || line.ends_with(char::is_whitespace) {
+            words.push("");
+        }
+        let Some((partial, previous)) = words.split_last() else {
+            return Vec::new();
+        };
+
+        let options = match previous.split_first() {
+            None => self.commands.keys().map(String::as_str).chain([HELP]).collect(),
+            Some((&HELP, [])) => self.commands.keys().map(String::as_str).collect(),
+            Some((name, arguments)) => match self.commands.get(*name).and_then(|command| command.arguments.get(arguments.len())) {
+                Some(argument) => candidates(argument.kind),
+                None => Vec::new(),
+            },
+        };
+
+        let prefix = previous.iter().map(|word| format!("{} ", word)).collect::<String>();
+        options
+            .into_iter()
+            .filter(|option| option.starts_with(partial))
+            .map(|option| format!("{}{}", prefix, option))
+            .collect()
+    }
+
+    fn help(&self, name: Option<&str>) -> anyhow::Result<String> {
+        if let Some(name) = name {
+            let command = self.commands.get(name).with_context(|| format!("unknown command '{}'", name))?;
+            return Ok(format!("{}\n  {}", command.usage(), command.description));
+        }
+
+        let mut lines = self.commands.values().map(|command| format!("{} - {}", command.usage(), command.description)).collect::<Vec<_>>();
+        lines.push(format!("{} [command] - Lists the commands or describes one", HELP));
+        Ok(lines.join("\n"))
+    }
+}
+
+fn register_builtins(registry: &mut CommandRegistry) {
+    use ArgumentKind::*;
+
+    registry.register("tp", "Moves the camera to a position", vec![Argument::required("x", Float), Argument::required("y", Float), Argument::required("z", Float)], |context, arguments| {
+        context.camera.position = Point3::new(arguments.float(0), arguments.float(1), arguments.float(2));
+        Ok(format!("teleported to {} {} {}", arguments.float(0), arguments.float(1), arguments.float(2)))
+    });
+
+    registry.register("seed", "Shows the world seed, or sets it and generates the world again", vec![Argument::optional("seed", Integer)], |context, arguments| {
+        if arguments.is_empty() {
+            return Ok(format!("seed: {}", context.world.seed));
+        }
+
+        context.world.seed = arguments.integer(0) as u32;
+        context.world.regenerate();
+        Ok(format!("seed set to {}", context.world.seed))
+    });
+
+    registry.register("fill", "Fills the box between two corners with a block", region_arguments(&[Argument::required("block", Block)]), |context, arguments| {
+        let region = region_argument(arguments)?;
+        Ok(region::fill(context.world, region, voxel::Voxel::new(arguments.block(6))).to_string())
+    });
+
+    registry.register("hollow", "Builds a hollow box between two corners, emptying its inside", region_arguments(&[Argument::required("block", Block)]), |context, arguments| {
+        let region = region_argument(arguments)?;
+        Ok(region::hollow(context.world, region, voxel::Voxel::new(arguments.block(6))).to_string())
+    });
+
+    registry.register("walls", "Builds the four sides of the box between two corners", region_arguments(&[Argument::required("block", Block)]), |context, arguments| {
+        let region = region_argument(arguments)?;
+        Ok(region::walls(context.world, region, voxel::Voxel::new(arguments.block(6))).to_string())
+    });
+
+    registry.register("replace", "Replaces one block with another inside the box between two corners", region_arguments(&[Argument::required("from", Block), Argument::required("to", Block)]), |context, arguments| {
+        let region = region_argument(arguments)?;
+        Ok(region::replace(context.world, region, arguments.block(6), voxel::Voxel::new(arguments.block(7))).to_string())
+    });
+
+    let corners = region_arguments(&[]).into_iter().map(|argument| Argument::optional(argument.name, argument.kind)).collect();
+    registry.register("copy", "Copies the box between two corners, or the selection, to the clipboard", corners, |context, arguments| {
+        let clipboard = match arguments.len() {
+            0 => context.editor.selection.as_ref().context("nothing is selected")?.copy(context.world),
+            6 => Clipboard::copy(context.world, region_argument(arguments)?),
+            _ => bail!("copy needs both corners or none"),
+        };
+        let size = clipboard.size();
+        *context.clipboard = Some(clipboard);
+        Ok(format!("copied {}x{}x{} blocks", size.x, size.y, size.z))
+    });
+
+    registry.register(
+        "paste",
+        "Pastes the clipboard with its lowest corner at a position, or at the camera. 'merge' keeps blocks where the clipboard is empty",
+        vec![Argument::optional("x", Integer), Argument::optional("y", Integer), Argument::optional("z", Integer), Argument::optional("mode", Keyword(&["merge", "replace"]))],
+        |context, arguments| {
+            let clipboard = context.clipboard.as_ref().context("the clipboard is empty, use 'copy' first")?;
+            let origin = match arguments.len() {
+                0 => context.camera.position.map(|value| value.floor() as i32).to_vec(),
+                3 | 4 => Vector3::new(arguments.integer(0), arguments.integer(1), arguments.integer(2)),
+                _ => bail!("paste needs all three coordinates or none"),
+            };
+            let include_empty = arguments.len() == 4 && arguments.word(3) == "replace";
+            Ok(clipboard.paste(context.world, origin, include_empty).to_string())
+        },
+    );
+
+    registry.register("undo", "Reverts the last edits", vec![Argument::optional("count", Integer)], |context, arguments| {
+        let count = if arguments.is_empty() { 1 } else { arguments.integer(0).max(1) };
+        let mut changed = 0;
+        let mut undone = 0;
+        while undone < count {
+            let Some(summary) = context.world.undo() else {
+                break;
+            };
+            changed += summary.changed;
+            undone += 1;
+        }
+        if undone == 0 {
+            bail!("nothing to undo");
+        }
+        Ok(format!("undid {} edits, changing {} blocks", undone, changed))
+    });
+
+    registry.register("redo", "Applies undone edits again", vec![Argument::optional("count", Integer)], |context, arguments| {
+        let count = if arguments.is_empty() { 1 } else { arguments.integer(0).max(1) };
+        let mut changed = 0;
+        let mut redone = 0;
+        while redone < count {
+            let Some(summary) = context.world.redo() else {
+                break;
+            };
+            changed += summary.changed;
+            redone += 1;
+        }
+        if redone == 0 {
+            bail!("nothing to redo");
+        }
+        Ok(format!("redid {} edits, changing {} blocks", redone, changed))
+    });
+
+    registry.register(
+        "select",
+        "Picks selections with the mouse, or saves and restores them by name",
+        vec![Argument::required("action", Keyword(&["box", "polygon", "clear", "save", "load", "delete", "list"])), Argument::optional("name", Text)],
+        |context, arguments| {
+            let editor = &mut *context.editor;
+            let name = || if arguments.len() == 2 { Ok(arguments.word(1)) } else { bail!("usage: select save|load|delete <name>") };
+            match arguments.word(0) {
+                shape @ ("box" | "polygon") => {
+                    editor.enabled = true;
+                    editor.tool = Tool::Select;
+                    editor.selection_shape = if shape == "box" { SelectionShape::Box } else { SelectionShape::Polygon };
+                    Ok(format!("click to pick a {} selection", shape))
+                }
+                "clear" => {
+                    editor.selection = None;
+                    Ok("selection cleared".to_string())
+                }
+                "save" => {
+                    let selection = editor.selection.clone().context("nothing is selected")?;
+                    editor.named_selections.insert(name()?.to_string(), selection);
+                    Ok(format!("saved the selection as {}", name()?))
+                }
+                "load" => {
+                    let selection = editor.named_selections.get(name()?).with_context(|| format!("no selection named '{}'", arguments.word(1)))?;
+                    editor.selection = Some(selection.clone());
+                    Ok(format!("selected {}", name()?))
+                }
+                "delete" => {
+                    editor.named_selections.remove(name()?).with_context(|| format!("no selection named '{}'", arguments.word(1)))?;
+                    Ok(format!("deleted selection {}", name()?))
+                }
+                _ if editor.named_selections.is_empty() => Ok("no saved selections".to_string()),
+                _ => Ok(editor.named_selections.keys().cloned().collect::<Vec<_>>().join("\n")),
+            }
+        },
+    );
+
+    registry.register(
+        "blueprint",
+        "Saves the selection as a blueprint file, or loads one to place it",
+        vec![Argument::required("action", Keyword(&["save", "load", "delete", "list"])), Argument::optional("name", Text)],
+        |context, arguments| {
+            let name = || if arguments.len() == 2 { Ok(arguments.word(1)) } else { bail!("usage: blueprint save|load|delete <name>") };
+            match arguments.word(0) {
+                "save" => {
+                    // Without a selection the clipboard is saved, e.g. after rotating it.
+                    let clipboard = match (&context.editor.selection, &*context.clipboard) {
+                        (Some(selection), _) => selection.copy(context.world),
+                        (None, Some(clipboard)) => clipboard.clone(),
+                        (None, None) => bail!("nothing is selected"),
+                    };
+                    let path = context.blueprints.save(name()?, &clipboard)?;
+                    Ok(format!("saved {}", path.display()))
+                }
+                "load" => {
+                    let clipboard = context.blueprints.load(name()?)?;
+                    let size = clipboard.size();
+                    *context.clipboard = Some(clipboard);
+                    context.editor.enabled = true;
+                    context.editor.tool = Tool::Place;
+                    Ok(format!("loaded {} ({}x{}x{}), click to place it", name()?, size.x, size.y, size.z))
+                }
+                "delete" => {
+                    context.blueprints.delete(name()?)?;
+                    Ok(format!("deleted blueprint {}", name()?))
+                }
+                _ => {
+                    let names = context.blueprints.list()?;
+                    if names.is_empty() {
+                        return Ok(format!("no blueprints in {}", context.blueprints.directory.display()));
+                    }
+                    Ok(names.join("\n"))
+                }
+            }
+        },
+    );
+
+    let mut vox_arguments = vec![Argument::required("action", Keyword(&["import", "export"])), Argument::required("path", Text)];
+    vox_arguments.extend(region_arguments(&[]).into_iter().map(|argument| Argument::optional(argument.name, argument.kind)));
+    registry.register(
+        "vox",
+        "Imports a MagicaVoxel file to place it, or exports the box between two corners, the selection or the clipboard",
+        vox_arguments,
+        |context, arguments| {
+            let mut path = PathBuf::from(arguments.word(1));
+            if path.extension().is_none() {
+                path.set_extension(vox::EXTENSION);
+            }
+            if arguments.word(0) == "import" {
+                let file = VoxFile::read(&path)?;
+                let clipboard = file.to_clipboard()?;
+                let size = clipboard.size();
+                *context.clipboard = Some(clipboard);
+                context.editor.enabled = true;
+                context.editor.tool = Tool::Place;
+                return Ok(format!("imported {} models from {} ({}x{}x{}), click to place them", file.instances.len(), path.display(), size.x, size.y, size.z));
+            }
+
+            let clipboard = match (arguments.len(), &context.editor.selection, &*context.clipboard) {
+                (8, _, _) => Clipboard::copy(context.world, region_argument(&Arguments { values: arguments.values[2..].to_vec() })?),
+                (2, Some(selection), _) => selection.copy(context.world),
+                (2, None, Some(clipboard)) => clipboard.clone(),
+                (2, None, None) => bail!("nothing is selected"),
+                _ => bail!("vox export needs both corners or none"),
+            };
+            let file = VoxFile::from_clipboard(&clipboard)?;
+            file.write(&path)?;
+            Ok(format!("exported {} models to {}", file.models.len(), path.display()))
+        },
+    );
+
+    registry.register(
+        "voxelize",
+        "Turns an OBJ from res/models into blocks, pasted at a position or held to place it. 'solid' also fills the inside",
+        vec![
+            Argument::required("model", Text),
+            Argument::required("resolution", Integer),
+            Argument::optional("mode", Keyword(&["hollow", "solid"])),
+            Argument::optional("x", Integer),
+            Argument::optional("y", Integer),
+            Argument::optional("z", Integer),
+        ],
+        |context, arguments| {
+            let path = voxelizer::models_directory().join(arguments.word(0)).with_extension(voxelizer::EXTENSION);
+            let options = VoxelizeOptions {
+                resolution: arguments.integer(1),
+                fill_interior: arguments.len() > 2 && arguments.word(2) == "solid",
+            };
+            let clipboard = voxelizer::voxelize(&path, options)?;
+            let size = clipboard.size();
+            match arguments.len() {
+                2 | 3 => {
+                    *context.clipboard = Some(clipboard);
+                    context.editor.enabled = true;
+                    context.editor.tool = Tool::Place;
+                    Ok(format!("voxelized {} ({}x{}x{}), click to place it", arguments.word(0), size.x, size.y, size.z))
+                }
+                6 => {
+                    let origin = Vector3::new(arguments.integer(3), arguments.integer(4), arguments.integer(5));
+                    let summary = clipboard.paste(context.world, origin, false);
+                    *context.clipboard = Some(clipboard);
+                    Ok(format!("voxelized {} ({}x{}x{}), {}", arguments.word(0), size.x, size.y, size.z, summary))
+                }
+                _ => bail!("voxelize needs all three coordinates or none"),
+            }
+        },
+    );
+
+    let mut export_arguments = vec![Argument::required("format", Keyword(&["obj", "glb"])), Argument::required("path", Text), Argument::optional("faces", Keyword(&["all", "greedy"]))];
+    export_arguments.extend(region_arguments(&[]).into_iter().map(|argument| Argument::optional(argument.name, argument.kind)));
+    registry.register(
+        "export",
+        "Writes the meshes of the chunks the box between two corners touches, or of every loaded chunk, as OBJ or binary glTF. 'greedy' merges faces",
+        export_arguments,
+        |context, arguments| {
+            let format = MeshFormat::from_name(arguments.word(0)).context("unknown format")?;
+            let mut path = PathBuf::from(arguments.word(1));
+            if path.extension().is_none() {
+                path.set_extension(format.name());
+            }
+            let greedy = arguments.len() > 2 && arguments.word(2) == "greedy";
+            let region = match arguments.len() {
+                2 | 3 => {
+                    if context.world.chunks.is_empty() {
+                        bail!("no chunks are loaded");
+                    }
+                    let chunks = context.world.chunks.keys();
+                    let min = chunks.clone().fold(Vector3::new(i32::MAX, i32::MAX, i32::MAX), |min, position| Vector3::new(min.x.min(position.x), min.y.min(position.y), min.z.min(position.z)));
+                    let max = chunks.fold(Vector3::new(i32::MIN, i32::MIN, i32::MIN), |max, position| Vector3::new(max.x.max(position.x), max.y.max(position.y), max.z.max(position.z)));
+                    Region::new(min * CHUNK_SIZE as i32, max * CHUNK_SIZE as i32)
+                }
+                9 => Region::new(Vector3::new(arguments.integer(3), arguments.integer(4), arguments.integer(5)), Vector3::new(arguments.integer(6), arguments.integer(7), arguments.integer(8))),
+                _ => bail!("export needs both corners or none"),
+            };
+
+            let chunks = mesh_export::mesh_region(context.world, region, greedy);
+            let summary = format.write(&path, &chunks)?;
+            Ok(format!("{} to {}", summary, path.display()))
+        },
+    );
+
+    registry.register("rotate", "Rotates the clipboard clockwise around the vertical axis in quarter turns", vec![Argument::optional("turns", Integer)], |context, arguments| {
+        let clipboard = context.clipboard.as_mut().context("the clipboard is empty, use 'copy' first")?;
+        let turns = if arguments.is_empty() { 1 } else { arguments.integer(0) };
+        *clipboard = clipboard.rotated(turns);
+        let size = clipboard.size();
+        Ok(format!("clipboard is now {}x{}x{}", size.x, size.y, size.z))
+    });
+
+    registry.register("mirror", "Flips the clipboard along an axis", vec![Argument::required("axis", Keyword(&["x", "y", "z"]))], |context, arguments| {
+        let clipboard = context.clipboard.as_mut().context("the clipboard is empty, use 'copy' first")?;
+        let axis = match arguments.word(0) {
+            "x" => Axis::X,
+            "y" => Axis::Y,
+            _ => Axis::Z,
+        };
+        *clipboard = clipboard.mirrored(axis);
+        Ok(format!("mirrored the clipboard along {}", arguments.word(0)))
+    });
+
+    registry.register("time", "Shows the time of day, or sets it in hours after midnight", vec![Argument::optional("set", Keyword(&["set"])), Argument::optional("hours", Float)], |context, arguments| {
+        match arguments.len() {
+            0 => Ok(format!("time: {:.1}", context.time_of_day)),
+            2 => {
+                *context.time_of_day = arguments.float(1).rem_euclid(24.0);
+                Ok(format!("time set to {:.1}", context.time_of_day))
+            }
+            _ => bail!("usage: time [set <hours>]"),
+        }
+    });
+
+    registry.register("autosave", "Shows how often the world saves itself, or sets it in seconds", vec![Argument::optional("seconds|off", Text)], |context, arguments| {
+        if !arguments.is_empty() {
+            context.autosave.interval = match arguments.word(0) {
+                "off" => None,
+                seconds => {
+                    let seconds = seconds.parse::<f32>().ok().filter(|seconds| *seconds > 0.0).with_context(|| format!("expected a positive number of seconds or 'off', got '{}'", seconds))?;
+                    Some(Duration::from_secs_f32(seconds))
+                }
+            };
+            context.autosave.reset();
+        }
+
+        Ok(match context.autosave.interval {
+            Some(interval) => format!("autosaving every {} seconds", interval.as_secs_f32()),
+            None => "autosave is off".to_string(),
+        })
+    });
+
+    registry.register("compression", "Shows or sets how saved chunks are compressed, chunks that are already saved keep theirs", vec![Argument::optional("compression", Keyword(&["none", "deflate", "lz4"]))], |context, arguments| {
+        if !arguments.is_empty() {
+            context.world_save.compression = Compression::from_name(arguments.word(0)).context("unknown compression")?;
+        }
+        Ok(format!("compression: {}", context.world_save.compression.name()))
+    });
+
+    registry.register("regen", "Generates the loaded chunks again, throwing away edits", Vec::new(), |context, _| {
+        context.world.regenerate();
+        Ok(format!("regenerated {} chunks", context.world.chunks.len()))
+    });
+
+    registry.register("save", "Saves the chunks edited since the last save, the camera and the time of day", Vec::new(), |context, _| {
+        if context.world_save.load_failed {
+            // Every chunk replaces what is left of the save that failed to load.
+            let positions = context.world.chunks.keys().copied().collect::<Vec<_>>();
+            context.world.modified_chunks.extend(positions);
+        }
+        let metadata = WorldMetadata::new(context.world, context.camera, *context.time_of_day, context.world_save.compression);
+        let summary = context.world_save.save(context.world, &metadata)?;
+        context.world_save.load_failed = false;
+        context.autosave.reset();
+        Ok(format!("{} to {}", summary, context.world_save.directory.display()))
+    });
+
+    registry.register("load", "Loads the saved world, throwing away unsaved edits", Vec::new(), |context, _| {
+        let loaded = context.world_save.load(context.world.seed, context.world.chunks.keys().copied())?;
+        let metadata = loaded.metadata.with_context(|| format!("nothing is saved in {}", context.world_save.directory.display()))?;
+        let mut world = loaded.world;
+        world.dirty_chunks.extend(world.chunks.keys().copied());
+        *context.world = world;
+        *context.camera = metadata.camera();
+        *context.time_of_day = metadata.time_of_day;
+        context.world_save.compression = metadata.compression;
+        context.world_save.load_failed = false;
+
+        let mut lines = loaded.warnings;
+        lines.push(format!("loaded {}", context.world_save.directory.display()));
+        Ok(lines.join("\n"))
+    });
+
+    registry.register("screenshot", "Saves the next frame as a PNG", vec![Argument::optional("path", Text)], |context, arguments| {
+        let Some(screenshot) = context.screenshot.as_deref_mut() else {
+            bail!("screenshots need a renderer");
+        };
+
+        let path = match arguments.get(0) {
+            Some(_) => PathBuf::from(arguments.word(0)),
+            None => {
+                let seconds = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or_default();
+                PathBuf::from(format!("screenshot-{}.png", seconds))
+            }
+        };
+        let message = format!("saving screenshot to {}", path.display());
+        *screenshot = Some(path);
+        Ok(message)
+    });
+}
+
+/// The two corners region edits start with, followed by `rest`.
+fn region_arguments(rest: &[Argument]) -> Vec<Argument> {
+    let mut arguments = ["x1", "y1", "z1", "x2", "y2", "z2"].map(|name| Argument::required(name, ArgumentKind::Integer)).to_vec();
+    arguments.extend_from_slice(rest);
+    arguments
+}
+
+fn region_argument(arguments: &Arguments) -> anyhow::Result<Region> {
+    let first = Vector3::new(arguments.integer(0), arguments.integer(1), arguments.integer(2));
+    let second = Vector3::new(arguments.integer(3), arguments.integer(4), arguments.integer(5));
+    let region = Region::new(first, second);
+    if region.volume() > MAX_EDIT_VOLUME {
+        bail!("region edits are limited to {} blocks", MAX_EDIT_VOLUME);
+    }
+    Ok(region)
+}
@@ -0,0 +1,164 @@
+use std::collections::VecDeque;
+
+use winit::event::{ElementState, VirtualKeyCode, WindowEvent};
+
+use crate::commands::CommandRegistry;
+use crate::text::TextRenderer;
+
+/// Lines of output kept around for scrolling back.
+const MAX_OUTPUT_LINES: usize = 200;
+/// Part of the window height the open console covers.
+const HEIGHT_FRACTION: f32 = 0.4;
+const MARGIN: f32 = 8.0;
+const BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.75];
+const OUTPUT_COLOR: [f32; 4] = [0.85, 0.85, 0.85, 1.0];
+const INPUT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const TOGGLE_KEY: VirtualKeyCode = VirtualKeyCode::Grave;
+const TOGGLE_CHARACTER: char = '`';
+
+/// Drop-down console toggled with the grave key. Keyboard input goes to it while it is open.
+#[derive(Default)]
+pub struct Console {
+    pub open: bool,
+    input: String,
+    output: VecDeque<String>,
+    history: Vec<String>,
+    /// Position in `history` while browsing it with the arrow keys.
+    history_index: Option<usize>,
+    submitted: Vec<String>,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends command output, multi line strings become multiple lines.
+    pub fn print(&mut self, text: &str) {
+        for line in text.lines() {
+            if self.output.len() == MAX_OUTPUT_LINES {
+                self.output.pop_front();
+            }
+            self.output.push_back(line.to_string());
+        }
+    }
+
+    /// Lines entered since the last call, in the order they were submitted.
+    pub fn take_submitted(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.submitted)
+    }
+
+    pub fn process_event(&mut self, event: &WindowEvent, commands: &CommandRegistry) -> bool {
+        match event {
+            WindowEvent::KeyboardInput { input, .. } => {
+                let Some(key) = input.virtual_keycode else {
+                    return self.open;
+                };
+                if input.state != ElementState::Pressed {
+                    return self.open;
+                }
+
+                if key == TOGGLE_KEY {
+                    self.open = !self.open;
+                    return true;
+                }
+                if !self.open {
+                    return false;
+                }
+
+                match key {
+                    VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => self.submit(),
+                    VirtualKeyCode::Back => {
+                        self.input.pop();
+                    }
+                    VirtualKeyCode::Escape => self.open = false,
+                    VirtualKeyCode::Tab => self.complete(commands),
+                    VirtualKeyCode::Up => self.browse_history(true),
+                    VirtualKeyCode::Down => self.browse_history(false),
+                    _ => {}
+                }
+                true
+            }
+            WindowEvent::ReceivedCharacter(character) if self.open => {
+                if !character.is_control() && *character != TOGGLE_CHARACTER {
+                    self.input.push(*character);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn submit(&mut self) {
+        let line = std::mem::take(&mut self.input);
+        self.history_index = None;
+        if line.trim().is_empty() {
+            return;
+        }
+
+        self.print(&format!("> {}", line));
+        if self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+        self.submitted.push(line);
+    }
+
+    /// Completes the input if there is a single candidate, otherwise completes the part all
+    /// candidates share and lists them.
+    fn complete(&mut self, commands: &CommandRegistry) {
+        let candidates = commands.complete(&self.input);
+        match candidates.as_slice() {
+            [] => {}
+            [candidate] => self.input = format!("{} ", candidate),
+            [first, rest @ ..] => {
+                // Byte length of the shared prefix, only ever advanced by whole characters.
+                let common = rest.iter().fold(first.len(), |length, candidate| {
+                    first.char_indices().zip(candidate.chars()).take_while(|((_, a), b)| a == b).map(|((index, a), _)| index + a.len_utf8()).last().unwrap_or(0).min(length)
+                });
+                self.input = first[..common].to_string();
+
+                let words = candidates.iter().filter_map(|candidate| candidate.split_whitespace().last()).collect::<Vec<_>>();
+                self.print(&words.join("  "));
+            }
+        }
+    }
+
+    fn browse_history(&mut self, older: bool) {
+        if self.history.is_empty() {
+            return;
+        }
+
+        let index = match (self.history_index, older) {
+            (None, true) => Some(self.history.len() - 1),
+            (None, false) => None,
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) if index + 1 < self.history.len() => Some(index + 1),
+            (Some(_), false) => None,
+        };
+        self.history_index = index;
+        self.input = index.map(|index| self.history[index].clone()).unwrap_or_default();
+    }
+
+    pub fn draw(&self, text: &mut TextRenderer, width: u32, height: u32) {
+        if !self.open {
+            return;
+        }
+
+        let bottom = (height as f32 * HEIGHT_FRACTION).round();
+        text.queue_rect([0.0, 0.0], [width as f32, bottom], BACKGROUND_COLOR);
+
+        let line_height = text.line_height();
+        let input_top = bottom - MARGIN - line_height;
+        text.queue(&format!("> {}_", self.input), [MARGIN, input_top], INPUT_COLOR);
+
+        // Newest output sits right above the input line, older lines scroll off the top.
+        let mut top = input_top - line_height;
+        for line in self.output.iter().rev() {
+            if top < 0.0 {
+                break;
+            }
+            text.queue(line, [MARGIN, top], OUTPUT_COLOR);
+            top -= line_height;
+        }
+    }
+}
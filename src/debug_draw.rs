@@ -0,0 +1,235 @@
+use cgmath::{InnerSpace, Matrix4, Point3, SquareMatrix, Vector3, Vector4};
+
+use crate::line::{self, LineVertex};
+use crate::render_utilities;
+use crate::texture;
+use crate::voxel_things::vertex_desc::VertexDesc;
+
+pub const RED: [f32; 4] = [1.0, 0.2, 0.2, 1.0];
+pub const GREEN: [f32; 4] = [0.2, 1.0, 0.2, 1.0];
+pub const BLUE: [f32; 4] = [0.2, 0.4, 1.0, 1.0];
+pub const YELLOW: [f32; 4] = [1.0, 0.9, 0.2, 1.0];
+pub const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+const SPHERE_SEGMENTS: usize = 32;
+const INITIAL_CAPACITY: usize = 1024;
+
+/// Line vertices recorded for one frame, uploaded and cleared by `DebugDraw::render`.
+struct LineBatch {
+    vertices: Vec<LineVertex>,
+    buffer: wgpu::Buffer,
+    capacity: usize,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl LineBatch {
+    fn new(device: &wgpu::Device, pipeline: wgpu::RenderPipeline, label: &str) -> Self {
+        Self {
+            vertices: Vec::new(),
+            buffer: create_vertex_buffer(device, INITIAL_CAPACITY, label),
+            capacity: INITIAL_CAPACITY,
+            pipeline,
+        }
+    }
+
+    /// Copies this frame's vertices into the vertex buffer, growing it when they don't fit.
+    fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, label: &str) {
+        if self.vertices.len() > self.capacity {
+            self.capacity = self.vertices.len().next_power_of_two();
+            self.buffer = create_vertex_buffer(device, self.capacity, label);
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&self.vertices));
+    }
+
+    fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if self.vertices.is_empty() {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, self.buffer.slice(..));
+        render_pass.draw(0..self.vertices.len() as u32, 0..1);
+    }
+}
+
+fn create_vertex_buffer(device: &wgpu::Device, capacity: usize, label: &str) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: (std::mem::size_of::<LineVertex>() * capacity) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// Immediate mode line drawing for visualizing things like bounds, rays and lights.
+///
+/// Shapes are collected during the frame and drawn in their own pass after the voxel pass, then
+/// forgotten, so anything that should stay visible has to be submitted every frame.
+pub struct DebugDraw {
+    depth_tested: LineBatch,
+    on_top: LineBatch,
+    draw_on_top: bool,
+}
+
+impl DebugDraw {
+    pub fn new(device: &wgpu::Device, color_format: wgpu::TextureFormat, camera_bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+        let pipeline_layout = render_utilities::create_pipeline_layout(device, "debug_draw_pipeline", &[camera_bind_group_layout]);
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("line.wgsl"));
+        let depth_tested_pipeline = render_utilities::create_line_pipeline(device, &pipeline_layout, color_format, Some(texture::Texture::DEPTH_FORMAT), &[LineVertex::desc()], shader, "debug draw pipeline");
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("line.wgsl"));
+        let options = render_utilities::PipelineOptions {
+            blend: wgpu::BlendState::ALPHA_BLENDING,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            topology: wgpu::PrimitiveTopology::LineList,
+            cull_mode: None,
+            ..render_utilities::PipelineOptions::new("debug draw on top pipeline")
+        };
+        let on_top_pipeline = render_utilities::create_render_pipeline_with_options(device, &pipeline_layout, color_format, Some(texture::Texture::DEPTH_FORMAT), &[LineVertex::desc()], shader, options);
+
+        Self {
+            depth_tested: LineBatch::new(device, depth_tested_pipeline, "Debug Draw Vertex Buffer"),
+            on_top: LineBatch::new(device, on_top_pipeline, "Debug Draw On Top Vertex Buffer"),
+            draw_on_top: false,
+        }
+    }
+
+    /// Shapes submitted after this are drawn over the terrain instead of being hidden by it.
+    pub fn set_on_top(&mut self, on_top: bool) {
+        self.draw_on_top = on_top;
+    }
+
+    fn vertices(&mut self) -> &mut Vec<LineVertex> {
+        if self.draw_on_top {
+            &mut self.on_top.vertices
+        } else {
+            &mut self.depth_tested.vertices
+        }
+    }
+
+    pub fn line(&mut self, from: Point3<f32>, to: Point3<f32>, color: [f32; 4]) {
+        let vertices = self.vertices();
+        vertices.push(LineVertex::new(from.to_homogeneous().truncate(), color));
+        vertices.push(LineVertex::new(to.to_homogeneous().truncate(), color));
+    }
+
+    pub fn ray(&mut self, origin: Point3<f32>, direction: Vector3<f32>, length: f32, color: [f32; 4]) {
+        self.line(origin, origin + direction.normalize() * length, color);
+    }
+
+    pub fn aabb(&mut self, min: Point3<f32>, max: Point3<f32>, color: [f32; 4]) {
+        let edges = line::box_edges(min.to_homogeneous().truncate(), max.to_homogeneous().truncate(), color);
+        self.vertices().extend_from_slice(&edges);
+    }
+
+    /// Three circles around the axes through `center`.
+    pub fn sphere(&mut self, center: Point3<f32>, radius: f32, color: [f32; 4]) {
+        let point = |angle: f32, axis: usize| {
+            let (sin, cos) = angle.sin_cos();
+            let offset = match axis {
+                0 => Vector3::new(0.0, cos, sin),
+                1 => Vector3::new(cos, 0.0, sin),
+                _ => Vector3::new(cos, sin, 0.0),
+            };
+            center + offset * radius
+        };
+
+        for axis in 0..3 {
+            for segment in 0..SPHERE_SEGMENTS {
+                let start = segment as f32 / SPHERE_SEGMENTS as f32 * std::f32::consts::TAU;
+                let end = (segment + 1) as f32 / SPHERE_SEGMENTS as f32 * std::f32::consts::TAU;
+                self.line(point(start, axis), point(end, axis), color);
+            }
+        }
+    }
+
+    /// Upright cylinder with circles at the top and bottom and four lines joining them.
+    pub fn cylinder(&mut self, center: Point3<f32>, radius: f32, half_height: f32, color: [f32; 4]) {
+        let point = |angle: f32, y: f32| {
+            let (sin, cos) = angle.sin_cos();
+            center + Vector3::new(cos * radius, y, sin * radius)
+        };
+
+        for segment in 0..SPHERE_SEGMENTS {
+            let start = segment as f32 / SPHERE_SEGMENTS as f32 * std::f32::consts::TAU;
+            let end = (segment + 1) as f32 / SPHERE_SEGMENTS as f32 * std::f32::consts::TAU;
+            for y in [-half_height, half_height] {
+                self.line(point(start, y), point(end, y), color);
+            }
+            if segment % (SPHERE_SEGMENTS / 4) == 0 {
+                self.line(point(start, -half_height), point(start, half_height), color);
+            }
+        }
+    }
+
+    /// X, Y and Z axes starting at `origin` in red, green and blue.
+    pub fn axes(&mut self, origin: Point3<f32>, size: f32) {
+        self.line(origin, origin + Vector3::unit_x() * size, RED);
+        self.line(origin, origin + Vector3::unit_y() * size, GREEN);
+        self.line(origin, origin + Vector3::unit_z() * size, BLUE);
+    }
+
+    /// Outline of the volume visible through `view_proj`, like a camera or the sun's shadow map.
+    pub fn frustum(&mut self, view_proj: Matrix4<f32>, color: [f32; 4]) {
+        let Some(inv_view_proj) = view_proj.invert() else {
+            return;
+        };
+
+        // wgpu clip space depth goes from 0 at the near plane to 1 at the far plane.
+        let corner = |x: f32, y: f32, z: f32| {
+            let world = inv_view_proj * Vector4::new(x, y, z, 1.0);
+            Point3::from_homogeneous(world)
+        };
+        let near = [corner(-1.0, -1.0, 0.0), corner(1.0, -1.0, 0.0), corner(1.0, 1.0, 0.0), corner(-1.0, 1.0, 0.0)];
+        let far = [corner(-1.0, -1.0, 1.0), corner(1.0, -1.0, 1.0), corner(1.0, 1.0, 1.0), corner(-1.0, 1.0, 1.0)];
+
+        for i in 0..4 {
+            let next = (i + 1) % 4;
+            self.line(near[i], near[next], color);
+            self.line(far[i], far[next], color);
+            self.line(near[i], far[i], color);
+        }
+    }
+
+    /// Draws everything submitted this frame over `view` and clears it for the next one.
+    pub fn render(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView, depth_view: &wgpu::TextureView, camera_bind_group: &wgpu::BindGroup) {
+        if self.depth_tested.vertices.is_empty() && self.on_top.vertices.is_empty() {
+            return;
+        }
+
+        self.depth_tested.upload(device, queue, "Debug Draw Vertex Buffer");
+        self.on_top.upload(device, queue, "Debug Draw On Top Vertex Buffer");
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Debug Draw Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+
+            render_pass.set_bind_group(0, camera_bind_group, &[]);
+            self.depth_tested.draw(&mut render_pass);
+            self.on_top.draw(&mut render_pass);
+        }
+
+        self.depth_tested.vertices.clear();
+        self.on_top.vertices.clear();
+        self.draw_on_top = false;
+    }
+}
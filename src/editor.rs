@@ -0,0 +1,403 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use cgmath::{Point3, Vector3};
+use winit::dpi::PhysicalPosition;
+use winit::event::{ElementState, MouseScrollDelta, VirtualKeyCode};
+
+use crate::brush::{self, Brush, BrushMode, BrushShape};
+use crate::camera::Camera;
+use crate::debug_draw::{self, DebugDraw};
+use crate::voxel_things::{raycast::RaycastHit, region::Region, selection::Selection, voxel, world::World};
+
+/// Blocks selectable with the number keys, in key order.
+pub const HOTBAR: [voxel::BlockType; 6] = [
+    voxel::BlockType::Grass,
+    voxel::BlockType::Dirt,
+    voxel::BlockType::Stone,
+    voxel::BlockType::Water,
+    voxel::BlockType::Glass,
+    voxel::BlockType::Leaves,
+];
+
+/// How often a held brush is applied again.
+const BRUSH_INTERVAL: Duration = Duration::from_millis(100);
+/// Radius change per line of scrolling.
+const RADIUS_STEP: f32 = 0.5;
+/// Falloff change per line of scrolling.
+const FALLOFF_STEP: f32 = 0.1;
+/// Pixels of touchpad scrolling that count as one line.
+const PIXELS_PER_LINE: f32 = 40.0;
+const SELECTION_COLOR: [f32; 4] = [0.3, 0.9, 1.0, 1.0];
+const PICKED_COLOR: [f32; 4] = [1.0, 0.6, 0.1, 1.0];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    /// Breaks and places single blocks. T switches between the tools.
+    Block,
+    /// Sculpts with `Editor::brush` while the left button is held. C cycles the shape, V the mode
+    /// and the scroll wheel sets the radius, or the falloff while control is held.
+    Brush,
+    /// Picks `Editor::selection` with left clicks. C switches between boxes, picked by two opposite
+    /// corners, and polygons, closed with a right click.
+    Select,
+    /// Pastes the clipboard where its preview is shown with left clicks, right click goes back to
+    /// the block tool. Entered by loading a blueprint.
+    Place,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionShape {
+    Box,
+    Polygon,
+}
+
+pub struct Editor {
+    pub enabled: bool,
+    pub tool: Tool,
+    pub selected_block: voxel::BlockType,
+    pub brush: Brush,
+    /// How far away from the camera blocks can be targeted.
+    pub reach: f32,
+    /// Brushes reach further, terrain is usually sculpted from a distance.
+    pub brush_reach: f32,
+    /// Time since the brush was last applied, while the left button is held.
+    stroke: Option<Duration>,
+    /// Control is held, scrolling changes the falloff instead of the radius.
+    control_held: bool,
+    pub selection_shape: SelectionShape,
+    /// Voxels clicked for the selection that is being picked.
+    picked: Vec<Vector3<i32>>,
+    pub selection: Option<Selection>,
+    /// Selections stored with `select save` to come back to later.
+    pub named_selections: BTreeMap<String, Selection>,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            tool: Tool::Block,
+            selected_block: HOTBAR[0],
+            brush: Brush::default(),
+            reach: 8.0,
+            brush_reach: 48.0,
+            stroke: None,
+            control_held: false,
+            selection_shape: SelectionShape::Box,
+            picked: Vec::new(),
+            selection: None,
+            named_selections: BTreeMap::new(),
+        }
+    }
+
+    pub fn process_keyboard(&mut self, key: VirtualKeyCode, state: ElementState) -> bool {
+        if key == VirtualKeyCode::LControl || key == VirtualKeyCode::RControl {
+            self.control_held = state == ElementState::Pressed;
+            return false;
+        }
+        if state != ElementState::Pressed {
+            return false;
+        }
+
+        let slot = match key {
+            VirtualKeyCode::B => {
+                self.enabled = !self.enabled;
+                return true;
+            }
+            VirtualKeyCode::Key1 => 0,
+            VirtualKeyCode::Key2 => 1,
+            VirtualKeyCode::Key3 => 2,
+            VirtualKeyCode::Key4 => 3,
+            VirtualKeyCode::Key5 => 4,
+            VirtualKeyCode::Key6 => 5,
+            VirtualKeyCode::T if self.enabled => {
+                self.tool = match self.tool {
+                    Tool::Block => Tool::Brush,
+                    Tool::Brush => Tool::Select,
+                    Tool::Select | Tool::Place => Tool::Block,
+                };
+                self.picked.clear();
+                return true;
+            }
+            VirtualKeyCode::C if self.enabled && self.tool == Tool::Select => {
+                self.selection_shape = match self.selection_shape {
+                    SelectionShape::Box => SelectionShape::Polygon,
+                    SelectionShape::Polygon => SelectionShape::Box,
+                };
+                self.picked.clear();
+                return true;
+            }
+            VirtualKeyCode::C if self.enabled && self.tool == Tool::Brush => {
+                self.brush.shape = next(&BrushShape::ALL, self.brush.shape);
+                return true;
+            }
+            VirtualKeyCode::V if self.enabled && self.tool == Tool::Brush => {
+                self.brush.mode = next(&BrushMode::ALL, self.brush.mode);
+                return true;
+            }
+            _ => return false,
+        };
+
+        if !self.enabled {
+            return false;
+        }
+        self.selected_block = HOTBAR[slot];
+        true
+    }
+
+    /// With the brush tool, scrolling changes the brush radius, or its falloff while control is
+    /// held. Otherwise the scroll wheel is left to the camera.
+    pub fn process_scroll(&mut self, delta: &MouseScrollDelta) -> bool {
+        if !self.enabled || self.tool != Tool::Brush {
+            return false;
+        }
+
+        let lines = match delta {
+            MouseScrollDelta::LineDelta(_, lines) => *lines,
+            MouseScrollDelta::PixelDelta(PhysicalPosition { y, .. }) => *y as f32 / PIXELS_PER_LINE,
+        };
+        if self.control_held {
+            self.brush.falloff = (self.brush.falloff + lines * FALLOFF_STEP).clamp(0.0, 1.0);
+        } else {
+            self.brush.radius = (self.brush.radius + lines * RADIUS_STEP).clamp(brush::MIN_RADIUS, brush::MAX_RADIUS);
+        }
+        true
+    }
+
+    /// The voxel under the crosshair.
+    pub fn target(&self, world: &World, camera: &Camera) -> Option<RaycastHit> {
+        world.raycast(camera.position, camera.forward(), self.reach)
+    }
+
+    /// Where the brush would be centered. Adding builds out from the hit face, everything else
+    /// works on the hit voxel.
+    pub fn brush_center(&self, world: &World, camera: &Camera) -> Option<Vector3<i32>> {
+        let hit = world.raycast(camera.position, camera.forward(), self.brush_reach)?;
+        match self.brush.mode {
+            BrushMode::Add => Some(hit.previous),
+            _ => Some(hit.position),
+        }
+    }
+
+    /// Starts applying the brush, everything until `end_stroke` is undone as one edit.
+    pub fn begin_stroke(&mut self, world: &mut World, camera: &Camera) {
+        if self.stroke.is_some() {
+            return;
+        }
+
+        world.history.begin();
+        self.stroke = Some(Duration::ZERO);
+        self.apply_brush(world, camera);
+    }
+
+    pub fn end_stroke(&mut self, world: &mut World) {
+        if self.stroke.take().is_some() {
+            world.history.end();
+        }
+    }
+
+    /// Key and mouse releases don't arrive while the window is unfocused, so the stroke and Ctrl
+    /// are let go of when it loses focus.
+    pub fn focus_lost(&mut self, world: &mut World) {
+        self.end_stroke(world);
+        self.control_held = false;
+    }
+
+    /// Keeps applying the brush while a stroke is held.
+    pub fn update(&mut self, world: &mut World, camera: &Camera, dt: Duration) {
+        if !self.enabled || self.tool != Tool::Brush {
+            self.end_stroke(world);
+        }
+        let Some(elapsed) = &mut self.stroke else {
+            return;
+        };
+
+        *elapsed += dt;
+        if *elapsed >= BRUSH_INTERVAL {
+            *elapsed = Duration::ZERO;
+            self.apply_brush(world, camera);
+        }
+    }
+
+    fn apply_brush(&self, world: &mut World, camera: &Camera) {
+        if let Some(center) = self.brush_center(world, camera) {
+            self.brush.apply(world, center, self.selected_block);
+        }
+    }
+
+    /// Outlines the brush shape where it would be applied.
+    pub fn draw_brush(&self, world: &World, camera: &Camera, debug_draw: &mut DebugDraw) {
+        if !self.enabled || self.tool != Tool::Brush {
+            return;
+        }
+        let Some(center) = self.brush_center(world, camera) else {
+            return;
+        };
+
+        let center = Point3::new(center.x as f32 + 0.5, center.y as f32 + 0.5, center.z as f32 + 0.5);
+        let radius = self.brush.radius.max(brush::MIN_RADIUS) + 0.5;
+        let color = match self.brush.mode {
+            BrushMode::Add => debug_draw::GREEN,
+            BrushMode::Remove => debug_draw::RED,
+            BrushMode::Paint => debug_draw::BLUE,
+            BrushMode::Smooth => debug_draw::YELLOW,
+        };
+        match self.brush.shape {
+            BrushShape::Sphere => debug_draw.sphere(center, radius, color),
+            BrushShape::Cube => debug_draw.aabb(center - Vector3::new(radius, radius, radius), center + Vector3::new(radius, radius, radius), color),
+            BrushShape::Cylinder => debug_draw.cylinder(center, radius, radius, color),
+        }
+    }
+
+    /// One line describing the current tool, for the HUD.
+    pub fn status(&self) -> String {
+        let block = self.selected_block.definition().name;
+        match self.tool {
+            Tool::Block => format!("build: {}", block),
+            Tool::Brush => format!("{}, {}", self.brush, block),
+            Tool::Select => {
+                let selected = match &self.selection {
+                    Some(selection) => {
+                        let size = selection.bounds().size();
+                        format!("{}x{}x{} selected", size.x, size.y, size.z)
+                    }
+                    None => "nothing selected".to_string(),
+                };
+                format!("select {:?}: {} points picked, {}", self.selection_shape, self.picked.len(), selected)
+            }
+            Tool::Place => "place: click to paste the clipboard".to_string(),
+        }
+    }
+
+    /// Adds the targeted voxel to the selection being picked. A box is done after its second corner,
+    /// polygons wait for `finish_polygon`.
+    pub fn pick(&mut self, world: &World, camera: &Camera) -> bool {
+        let Some(hit) = self.target(world, camera) else {
+            return false;
+        };
+
+        self.picked.push(hit.position);
+        if self.selection_shape == SelectionShape::Box && self.picked.len() == 2 {
+            self.selection = Some(Selection::Box(Region::new(self.picked[0], self.picked[1])));
+            self.picked.clear();
+        }
+        true
+    }
+
+    /// Closes the polygon being picked, or throws away the picked points if there are too few.
+    pub fn finish_polygon(&mut self) -> bool {
+        if self.selection_shape == SelectionShape::Polygon {
+            if let Some(selection) = Selection::polygon(&self.picked) {
+                self.selection = Some(selection);
+            }
+        }
+        self.picked.clear();
+        true
+    }
+
+    /// Outlines the selection and the points picked so far, with a line to the targeted voxel
+    /// while a polygon is being picked.
+    pub fn draw_selection(&self, world: &World, camera: &Camera, debug_draw: &mut DebugDraw) {
+        if !self.enabled {
+            return;
+        }
+
+        let corner = |position: Vector3<i32>| Point3::new(position.x as f32, position.y as f32, position.z as f32);
+        let center = |position: Vector3<i32>| corner(position) + Vector3::new(0.5, 0.5, 0.5);
+        match &self.selection {
+            Some(Selection::Box(region)) => debug_draw.aabb(corner(region.min), corner(region.max + Vector3::new(1, 1, 1)), SELECTION_COLOR),
+            Some(Selection::Polygon { points, min_y, max_y }) => {
+                let point = |x: i32, y: f32, z: i32| Point3::new(x as f32 + 0.5, y, z as f32 + 0.5);
+                let (bottom, top) = (*min_y as f32, (*max_y + 1) as f32);
+                for (a, b) in points.iter().zip(points.iter().cycle().skip(1)) {
+                    debug_draw.line(point(a.x, bottom, a.y), point(b.x, bottom, b.y), SELECTION_COLOR);
+                    debug_draw.line(point(a.x, top, a.y), point(b.x, top, b.y), SELECTION_COLOR);
+                    debug_draw.line(point(a.x, bottom, a.y), point(a.x, top, a.y), SELECTION_COLOR);
+                }
+            }
+            None => {}
+        }
+
+        if self.tool != Tool::Select {
+            return;
+        }
+        for position in &self.picked {
+            debug_draw.aabb(corner(*position), corner(position + Vector3::new(1, 1, 1)), PICKED_COLOR);
+        }
+        for (a, b) in self.picked.iter().zip(self.picked.iter().skip(1)) {
+            debug_draw.line(center(*a), center(*b), PICKED_COLOR);
+        }
+        if let (Some(last), Some(hit)) = (self.picked.last(), self.target(world, camera)) {
+            debug_draw.line(center(*last), center(hit.position), PICKED_COLOR);
+        }
+    }
+
+    /// Where a clipboard of `size` gets pasted with the place tool: standing on the targeted face,
+    /// centered horizontally on it.
+    pub fn place_origin(&self, world: &World, camera: &Camera, size: Vector3<i32>) -> Option<Vector3<i32>> {
+        if !self.enabled || self.tool != Tool::Place {
+            return None;
+        }
+
+        let hit = world.raycast(camera.position, camera.forward(), self.brush_reach)?;
+        Some(hit.previous - Vector3::new(size.x / 2, 0, size.z / 2))
+    }
+
+    pub fn break_block(&self, world: &mut World, camera: &Camera) -> bool {
+        match self.target(world, camera) {
+            Some(hit) => world.set_voxel(hit.position, voxel::Voxel::new_empty()),
+            None => false,
+        }
+    }
+
+    /// Places the selected block against the targeted face, unless that would put it inside the camera.
+    pub fn place_block(&self, world: &mut World, camera: &Camera) -> bool {
+        let Some(hit) = self.target(world, camera) else {
+            return false;
+        };
+
+        let camera_cell = cgmath::Vector3::new(
+            camera.position.x.floor() as i32,
+            camera.position.y.floor() as i32,
+            camera.position.z.floor() as i32,
+        );
+        if hit.previous == camera_cell {
+            return false;
+        }
+
+        world.set_voxel(hit.previous, voxel::Voxel::new(self.selected_block))
+    }
+
+    /// Ctrl+Z undoes and Ctrl+Y redoes the last edit while building. Ignored during a brush
+    /// stroke, whose changes are still being grouped.
+    pub fn process_history_keys(&self, key: VirtualKeyCode, state: ElementState, world: &mut World) -> bool {
+        if !self.enabled || !self.control_held || self.stroke.is_some() || state != ElementState::Pressed {
+            return false;
+        }
+
+        match key {
+            VirtualKeyCode::Z => {
+                world.undo();
+                true
+            }
+            VirtualKeyCode::Y => {
+                world.redo();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The item after `current` in `all`, wrapping around.
+fn next<T: Copy + PartialEq>(all: &[T], current: T) -> T {
+    let index = all.iter().position(|item| *item == current).unwrap_or(0);
+    all[(index + 1) % all.len()]
+}
@@ -0,0 +1,130 @@
+use cgmath::Vector3;
+use wgpu::util::DeviceExt;
+
+use crate::line::LineVertex;
+use crate::render_utilities;
+use crate::texture;
+use crate::voxel_things::{direction::Direction, quad::Quad, region::Clipboard, vertex_desc::VertexDesc, voxel};
+
+const GHOST_COLOR: [f32; 3] = [0.45, 0.75, 1.0];
+const GHOST_ALPHA: f32 = 0.35;
+
+/// Translucent preview of a clipboard, drawn where it would be pasted.
+pub struct Ghost {
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: Option<wgpu::Buffer>,
+    vertex_count: u32,
+    placement_buffer: wgpu::Buffer,
+    placement_bind_group: wgpu::BindGroup,
+    visible: bool,
+}
+
+impl Ghost {
+    pub fn new(device: &wgpu::Device, color_format: wgpu::TextureFormat, camera_bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+        let placement_bind_group_layout = render_utilities::create_bind_group_layout(device, "ghost_placement_bind_group_layout", 0, wgpu::ShaderStages::VERTEX);
+        let placement_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Ghost Placement Buffer"),
+            contents: bytemuck::cast_slice(&[0.0f32; 4]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let placement_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("ghost_placement_bind_group"),
+            layout: &placement_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: placement_buffer.as_entire_binding(),
+            }],
+        });
+
+        let pipeline_layout = render_utilities::create_pipeline_layout(device, "ghost_pipeline", &[camera_bind_group_layout, &placement_bind_group_layout]);
+        let shader = device.create_shader_module(wgpu::include_wgsl!("ghost.wgsl"));
+        // Blended over the world without writing depth, so the terrain it overlaps stays visible.
+        let options = render_utilities::PipelineOptions {
+            blend: wgpu::BlendState::ALPHA_BLENDING,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            ..render_utilities::PipelineOptions::new("ghost pipeline")
+        };
+        let pipeline = render_utilities::create_render_pipeline_with_options(device, &pipeline_layout, color_format, Some(texture::Texture::DEPTH_FORMAT), &[LineVertex::desc()], shader, options);
+
+        Self {
+            pipeline,
+            vertex_buffer: None,
+            vertex_count: 0,
+            placement_buffer,
+            placement_bind_group,
+            visible: false,
+        }
+    }
+
+    /// Builds the preview mesh, `None` clears it.
+    pub fn set_clipboard(&mut self, device: &wgpu::Device, clipboard: Option<&Clipboard>) {
+        let vertices = clipboard.map(mesh_clipboard).unwrap_or_default();
+        self.vertex_count = vertices.len() as u32;
+        self.vertex_buffer = (!vertices.is_empty()).then(|| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Ghost Vertex Buffer"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            })
+        });
+    }
+
+    /// Moves the preview so its minimum corner is at `origin`, `None` hides it.
+    pub fn update(&mut self, queue: &wgpu::Queue, origin: Option<Vector3<i32>>) {
+        self.visible = origin.is_some();
+        if let Some(origin) = origin {
+            let offset = origin.cast::<f32>().unwrap() * voxel::SIZE;
+            queue.write_buffer(&self.placement_buffer, 0, bytemuck::cast_slice(&[offset.x, offset.y, offset.z, 0.0]));
+        }
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera_bind_group: &'a wgpu::BindGroup) {
+        let Some(vertex_buffer) = &self.vertex_buffer else {
+            return;
+        };
+        if !self.visible {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.placement_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
+
+/// Faces of the clipboard's solid voxels that don't touch another solid voxel, relative to its
+/// minimum corner. Faces are shaded by direction so the shape reads without textures.
+fn mesh_clipboard(clipboard: &Clipboard) -> Vec<LineVertex> {
+    let size = clipboard.size();
+    let is_solid = |position: Vector3<i32>| (0..3).all(|axis| position[axis] >= 0 && position[axis] < size[axis]) && !clipboard.get(position).is_empty();
+
+    let mut vertices = Vec::new();
+    for position in clipboard.positions() {
+        if !is_solid(position) {
+            continue;
+        }
+
+        for direction in Direction::ALL {
+            if is_solid(position + direction.get_offset()) {
+                continue;
+            }
+
+            let shade = match direction {
+                Direction::Up => 1.0,
+                Direction::Down => 0.55,
+                Direction::Left | Direction::Right => 0.8,
+                Direction::Back | Direction::Forward => 0.7,
+            };
+            let color = [GHOST_COLOR[0] * shade, GHOST_COLOR[1] * shade, GHOST_COLOR[2] * shade, GHOST_ALPHA];
+            let center = (position.cast::<f32>().unwrap() + Vector3::new(0.5, 0.5, 0.5)) * voxel::SIZE;
+            let quad = Quad::new(direction, center, 0, [1.0; 3]);
+            for index in [0, 1, 2, 0, 2, 3] {
+                vertices.push(LineVertex::new(quad.corners[index], color));
+            }
+        }
+    }
+    vertices
+}
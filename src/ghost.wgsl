@@ -0,0 +1,41 @@
+struct CameraUniform {
+	view_pos: vec4<f32>,
+	view_proj: mat4x4<f32>,
+	inv_view_proj: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// Where the ghost's minimum corner is in the world.
+struct Placement {
+	offset: vec4<f32>,
+};
+@group(1) @binding(0)
+var<uniform> placement: Placement;
+
+struct VertexInput {
+	@location(0) position: vec3<f32>,
+	@location(1) color: vec4<f32>,
+}
+
+struct VertexOutput {
+	@builtin(position) clip_position: vec4<f32>,
+	@location(0) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(
+	in: VertexInput,
+) -> VertexOutput {
+	var out: VertexOutput;
+	out.clip_position = camera.view_proj * vec4<f32>(in.position + placement.offset.xyz, 1.0);
+	out.color = in.color;
+	return out;
+}
+
+@fragment
+fn fs_main(
+	in: VertexOutput,
+) -> @location(0) vec4<f32> {
+	return in.color;
+}
@@ -0,0 +1,452 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use winit::event::{ElementState, ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent};
+
+use crate::render_utilities;
+use crate::texture;
+
+/// Points scrolled per line of a mouse wheel.
+const SCROLL_LINE_HEIGHT: f32 = 24.0;
+const INITIAL_VERTEX_CAPACITY: usize = 16 * 1024;
+const INITIAL_INDEX_CAPACITY: usize = 32 * 1024;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ScreenUniform {
+    size: [f32; 2],
+    _padding: [f32; 2],
+}
+
+/// egui on top of the rendered frame, toggled with F2. Input is only forwarded while it is open.
+pub struct Gui {
+    pub enabled: bool,
+    pub context: egui::Context,
+    painter: GuiPainter,
+    events: Vec<egui::Event>,
+    modifiers: egui::Modifiers,
+    pointer_position: egui::Pos2,
+    size: [u32; 2],
+    pixels_per_point: f32,
+    start_time: Instant,
+    primitives: Vec<egui::ClippedPrimitive>,
+    textures_delta: egui::TexturesDelta,
+}
+
+impl Gui {
+    pub fn new(device: &wgpu::Device, color_format: wgpu::TextureFormat, width: u32, height: u32, scale_factor: f64) -> Self {
+        Self {
+            enabled: false,
+            context: egui::Context::default(),
+            painter: GuiPainter::new(device, color_format),
+            events: Vec::new(),
+            modifiers: egui::Modifiers::default(),
+            pointer_position: egui::Pos2::ZERO,
+            size: [width, height],
+            pixels_per_point: scale_factor as f32,
+            start_time: Instant::now(),
+            primitives: Vec::new(),
+            textures_delta: egui::TexturesDelta::default(),
+        }
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.size = [width, height];
+    }
+
+    /// Translates window events for egui, returns true when the GUI used the event and the rest
+    /// of the engine shouldn't see it.
+    pub fn process_event(&mut self, event: &WindowEvent) -> bool {
+        if let WindowEvent::KeyboardInput { input, .. } = event {
+            if input.virtual_keycode == Some(VirtualKeyCode::F2) && input.state == ElementState::Pressed {
+                self.enabled = !self.enabled;
+                return true;
+            }
+        }
+
+        match event {
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = convert_modifiers(*modifiers),
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => self.pixels_per_point = *scale_factor as f32,
+            _ => {}
+        }
+
+        if !self.enabled {
+            return false;
+        }
+
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.pointer_position = egui::pos2(position.x as f32 / self.pixels_per_point, position.y as f32 / self.pixels_per_point);
+                self.events.push(egui::Event::PointerMoved(self.pointer_position));
+                false
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.events.push(egui::Event::PointerGone);
+                false
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    MouseButton::Left => egui::PointerButton::Primary,
+                    MouseButton::Right => egui::PointerButton::Secondary,
+                    MouseButton::Middle => egui::PointerButton::Middle,
+                    MouseButton::Other(_) => return false,
+                };
+                let pressed = *state == ElementState::Pressed;
+                // Releases always go to egui so drags that end outside of it don't get stuck.
+                let wants_pointer = self.context.wants_pointer_input() || self.context.is_pointer_over_area();
+                if pressed && !wants_pointer {
+                    return false;
+                }
+
+                self.events.push(egui::Event::PointerButton {
+                    pos: self.pointer_position,
+                    button,
+                    pressed,
+                    modifiers: self.modifiers,
+                });
+                pressed
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                if !self.context.is_pointer_over_area() {
+                    return false;
+                }
+
+                let delta = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => egui::vec2(*x, *y) * SCROLL_LINE_HEIGHT,
+                    MouseScrollDelta::PixelDelta(delta) => egui::vec2(delta.x as f32, delta.y as f32) / self.pixels_per_point,
+                };
+                self.events.push(egui::Event::Scroll(delta));
+                true
+            }
+            WindowEvent::ReceivedCharacter(character) => {
+                if !self.context.wants_keyboard_input() || character.is_control() {
+                    return false;
+                }
+
+                self.events.push(egui::Event::Text(character.to_string()));
+                true
+            }
+            WindowEvent::KeyboardInput { input, .. } => {
+                if !self.context.wants_keyboard_input() {
+                    return false;
+                }
+
+                if let Some(key) = input.virtual_keycode.and_then(convert_key) {
+                    self.events.push(egui::Event::Key {
+                        key,
+                        pressed: input.state == ElementState::Pressed,
+                        modifiers: self.modifiers,
+                    });
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Builds this frame's GUI with `build_ui`, nothing is shown while the GUI is closed.
+    pub fn run(&mut self, build_ui: impl FnOnce(&egui::Context)) {
+        if !self.enabled {
+            self.events.clear();
+            self.primitives.clear();
+            return;
+        }
+
+        let screen_size = egui::vec2(self.size[0] as f32, self.size[1] as f32) / self.pixels_per_point;
+        let input = egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(egui::Pos2::ZERO, screen_size)),
+            pixels_per_point: Some(self.pixels_per_point),
+            time: Some(self.start_time.elapsed().as_secs_f64()),
+            modifiers: self.modifiers,
+            events: std::mem::take(&mut self.events),
+            ..Default::default()
+        };
+
+        let output = self.context.run(input, build_ui);
+        self.textures_delta.append(output.textures_delta);
+        self.primitives = self.context.tessellate(output.shapes);
+    }
+
+    /// Draws the GUI built by the last `run` over `view`.
+    pub fn render(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        for (id, delta) in &self.textures_delta.set {
+            self.painter.update_texture(device, queue, *id, delta);
+        }
+
+        if !self.primitives.is_empty() {
+            self.painter.render(device, queue, encoder, view, &self.primitives, self.size, self.pixels_per_point);
+        }
+
+        for id in &self.textures_delta.free {
+            self.painter.textures.remove(id);
+        }
+        self.textures_delta.clear();
+    }
+}
+
+/// Draws tessellated egui meshes with wgpu.
+struct GuiPainter {
+    pipeline: wgpu::RenderPipeline,
+    screen_buffer: wgpu::Buffer,
+    screen_bind_group: wgpu::BindGroup,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    textures: HashMap<egui::TextureId, (texture::Texture, wgpu::BindGroup)>,
+    vertex_buffer: wgpu::Buffer,
+    vertex_capacity: usize,
+    index_buffer: wgpu::Buffer,
+    index_capacity: usize,
+}
+
+impl GuiPainter {
+    fn new(device: &wgpu::Device, color_format: wgpu::TextureFormat) -> Self {
+        let screen_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gui_screen_buffer"),
+            size: std::mem::size_of::<ScreenUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let screen_bind_group_layout = render_utilities::create_bind_group_layout(device, "gui_screen_bind_group_layout", 0, wgpu::ShaderStages::VERTEX);
+        let screen_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("gui_screen_bind_group"),
+            layout: &screen_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: screen_buffer.as_entire_binding(),
+            }],
+        });
+        let texture_bind_group_layout = texture::create_texture_bind_group_layout(device, "gui_texture_bind_group_layout");
+
+        let pipeline_layout = render_utilities::create_pipeline_layout(device, "gui_pipeline", &[&screen_bind_group_layout, &texture_bind_group_layout]);
+        let shader = device.create_shader_module(wgpu::include_wgsl!("gui.wgsl"));
+        let options = render_utilities::PipelineOptions {
+            blend: wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            cull_mode: None,
+            ..render_utilities::PipelineOptions::new("gui pipeline")
+        };
+        let vertex_layout = wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<egui::epaint::Vertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Unorm8x4],
+        };
+        let pipeline = render_utilities::create_render_pipeline_with_options(device, &pipeline_layout, color_format, None, &[vertex_layout], shader, options);
+
+        Self {
+            pipeline,
+            screen_buffer,
+            screen_bind_group,
+            texture_bind_group_layout,
+            textures: HashMap::new(),
+            vertex_buffer: create_buffer(device, "Gui Vertex Buffer", wgpu::BufferUsages::VERTEX, INITIAL_VERTEX_CAPACITY * std::mem::size_of::<egui::epaint::Vertex>()),
+            vertex_capacity: INITIAL_VERTEX_CAPACITY,
+            index_buffer: create_buffer(device, "Gui Index Buffer", wgpu::BufferUsages::INDEX, INITIAL_INDEX_CAPACITY * std::mem::size_of::<u32>()),
+            index_capacity: INITIAL_INDEX_CAPACITY,
+        }
+    }
+
+    fn update_texture(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, id: egui::TextureId, delta: &egui::epaint::ImageDelta) {
+        let pixels = match &delta.image {
+            egui::ImageData::Color(image) => image.pixels.clone(),
+            egui::ImageData::Font(image) => image.srgba_pixels(1.0).collect(),
+        };
+        let [width, height] = delta.image.size().map(|size| size as u32);
+
+        let origin = match delta.pos {
+            Some([x, y]) => wgpu::Origin3d {
+                x: x as u32,
+                y: y as u32,
+                z: 0,
+            },
+            None => {
+                let texture = create_gui_texture(device, width, height, delta.filter);
+                let bind_group = texture::create_texture_bind_group(device, &self.texture_bind_group_layout, &texture, "gui_texture_bind_group");
+                self.textures.insert(id, (texture, bind_group));
+                wgpu::Origin3d::ZERO
+            }
+        };
+        let Some((texture, _)) = self.textures.get(&id) else {
+            log::warn!("gui texture {:?} was updated before being created", id);
+            return;
+        };
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture.texture,
+                mip_level: 0,
+                origin,
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(&pixels),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(4 * width),
+                rows_per_image: std::num::NonZeroU32::new(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn render(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView, primitives: &[egui::ClippedPrimitive], size: [u32; 2], pixels_per_point: f32) {
+        let meshes = primitives
+            .iter()
+            .filter_map(|primitive| match &primitive.primitive {
+                egui::epaint::Primitive::Mesh(mesh) => Some((primitive.clip_rect, mesh)),
+                egui::epaint::Primitive::Callback(_) => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for (_, mesh) in &meshes {
+            vertices.extend_from_slice(&mesh.vertices);
+            indices.extend_from_slice(&mesh.indices);
+        }
+
+        if vertices.len() > self.vertex_capacity {
+            self.vertex_capacity = vertices.len().next_power_of_two();
+            self.vertex_buffer = create_buffer(device, "Gui Vertex Buffer", wgpu::BufferUsages::VERTEX, self.vertex_capacity * std::mem::size_of::<egui::epaint::Vertex>());
+        }
+        if indices.len() > self.index_capacity {
+            self.index_capacity = indices.len().next_power_of_two();
+            self.index_buffer = create_buffer(device, "Gui Index Buffer", wgpu::BufferUsages::INDEX, self.index_capacity * std::mem::size_of::<u32>());
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+        queue.write_buffer(&self.index_buffer, 0, bytemuck::cast_slice(&indices));
+
+        let screen = ScreenUniform {
+            size: [size[0] as f32 / pixels_per_point, size[1] as f32 / pixels_per_point],
+            _padding: [0.0; 2],
+        };
+        queue.write_buffer(&self.screen_buffer, 0, bytemuck::cast_slice(&[screen]));
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Gui Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.screen_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+
+        let (mut first_vertex, mut first_index) = (0, 0);
+        for (clip_rect, mesh) in meshes {
+            let index_range = first_index..first_index + mesh.indices.len() as u32;
+            let base_vertex = first_vertex;
+            first_vertex += mesh.vertices.len() as i32;
+            first_index = index_range.end;
+
+            // Clip rectangles are in points, the scissor rectangle is in pixels inside the surface.
+            let min_x = (clip_rect.min.x * pixels_per_point).round().clamp(0.0, size[0] as f32) as u32;
+            let min_y = (clip_rect.min.y * pixels_per_point).round().clamp(0.0, size[1] as f32) as u32;
+            let max_x = (clip_rect.max.x * pixels_per_point).round().clamp(min_x as f32, size[0] as f32) as u32;
+            let max_y = (clip_rect.max.y * pixels_per_point).round().clamp(min_y as f32, size[1] as f32) as u32;
+            if max_x == min_x || max_y == min_y {
+                continue;
+            }
+
+            let Some((_, bind_group)) = self.textures.get(&mesh.texture_id) else {
+                continue;
+            };
+            render_pass.set_bind_group(1, bind_group, &[]);
+            render_pass.set_scissor_rect(min_x, min_y, max_x - min_x, max_y - min_y);
+            render_pass.draw_indexed(index_range, base_vertex, 0..1);
+        }
+    }
+}
+
+fn create_buffer(device: &wgpu::Device, label: &str, usage: wgpu::BufferUsages, size: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: size as wgpu::BufferAddress,
+        usage: usage | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_gui_texture(device: &wgpu::Device, width: u32, height: u32, filter: egui::TextureFilter) -> texture::Texture {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Gui Texture"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+    });
+
+    let filter = match filter {
+        egui::TextureFilter::Nearest => wgpu::FilterMode::Nearest,
+        egui::TextureFilter::Linear => wgpu::FilterMode::Linear,
+    };
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: filter,
+        min_filter: filter,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        ..Default::default()
+    });
+
+    texture::Texture {
+        texture,
+        view,
+        sampler,
+    }
+}
+
+fn convert_modifiers(modifiers: ModifiersState) -> egui::Modifiers {
+    egui::Modifiers {
+        alt: modifiers.alt(),
+        ctrl: modifiers.ctrl(),
+        shift: modifiers.shift(),
+        mac_cmd: cfg!(target_os = "macos") && modifiers.logo(),
+        command: if cfg!(target_os = "macos") { modifiers.logo() } else { modifiers.ctrl() },
+    }
+}
+
+fn convert_key(key: VirtualKeyCode) -> Option<egui::Key> {
+    use egui::Key;
+
+    Some(match key {
+        VirtualKeyCode::Down => Key::ArrowDown,
+        VirtualKeyCode::Left => Key::ArrowLeft,
+        VirtualKeyCode::Right => Key::ArrowRight,
+        VirtualKeyCode::Up => Key::ArrowUp,
+        VirtualKeyCode::Escape => Key::Escape,
+        VirtualKeyCode::Tab => Key::Tab,
+        VirtualKeyCode::Back => Key::Backspace,
+        VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => Key::Enter,
+        VirtualKeyCode::Space => Key::Space,
+        VirtualKeyCode::Insert => Key::Insert,
+        VirtualKeyCode::Delete => Key::Delete,
+        VirtualKeyCode::Home => Key::Home,
+        VirtualKeyCode::End => Key::End,
+        VirtualKeyCode::PageUp => Key::PageUp,
+        VirtualKeyCode::PageDown => Key::PageDown,
+        VirtualKeyCode::A => Key::A,
+        VirtualKeyCode::C => Key::C,
+        VirtualKeyCode::V => Key::V,
+        VirtualKeyCode::X => Key::X,
+        VirtualKeyCode::Z => Key::Z,
+        _ => return None,
+    })
+}
@@ -0,0 +1,50 @@
+struct Screen {
+	size: vec2<f32>,
+}
+@group(0) @binding(0)
+var<uniform> screen: Screen;
+
+@group(1) @binding(0)
+var t_gui: texture_2d<f32>;
+@group(1) @binding(1)
+var s_gui: sampler;
+
+struct VertexInput {
+	@location(0) position: vec2<f32>,
+	@location(1) uv: vec2<f32>,
+	@location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+	@builtin(position) clip_position: vec4<f32>,
+	@location(0) uv: vec2<f32>,
+	@location(1) color: vec4<f32>,
+}
+
+fn linear_from_srgb(srgb: vec3<f32>) -> vec3<f32> {
+	let lower = srgb / 12.92;
+	let higher = pow((srgb + 0.055) / 1.055, vec3<f32>(2.4));
+	return select(higher, lower, srgb <= vec3<f32>(0.04045));
+}
+
+// Positions are in points with the origin in the top left corner of the screen, vertex colors
+// are premultiplied sRGB and get converted to linear since the surface is sRGB.
+@vertex
+fn vs_main(
+	in: VertexInput,
+) -> VertexOutput {
+	let ndc = in.position / screen.size * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0);
+
+	var out: VertexOutput;
+	out.clip_position = vec4<f32>(ndc, 0.0, 1.0);
+	out.uv = in.uv;
+	out.color = vec4<f32>(linear_from_srgb(in.color.rgb), in.color.a);
+	return out;
+}
+
+@fragment
+fn fs_main(
+	in: VertexOutput,
+) -> @location(0) vec4<f32> {
+	return in.color * textureSample(t_gui, s_gui, in.uv);
+}
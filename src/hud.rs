@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use cgmath::Vector3;
+use winit::event::{ElementState, VirtualKeyCode};
+
+use crate::camera::Camera;
+use crate::text::TextRenderer;
+
+/// How long frame times are averaged over before the displayed numbers change.
+const SAMPLE_PERIOD: Duration = Duration::from_millis(500);
+const MARGIN: f32 = 8.0;
+const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.45];
+
+/// Frame rate, camera and world info in the top left corner, toggled with F1.
+pub struct Hud {
+    pub enabled: bool,
+    frames: u32,
+    elapsed: Duration,
+    fps: f32,
+    frame_time: Duration,
+}
+
+impl Default for Hud {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hud {
+    pub fn new() -> Self {
+        Self {
+            enabled: true,
+            frames: 0,
+            elapsed: Duration::ZERO,
+            fps: 0.0,
+            frame_time: Duration::ZERO,
+        }
+    }
+
+    pub fn process_keyboard(&mut self, key: VirtualKeyCode, state: ElementState) -> bool {
+        if key != VirtualKeyCode::F1 || state != ElementState::Pressed {
+            return false;
+        }
+
+        self.enabled = !self.enabled;
+        true
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        self.frames += 1;
+        self.elapsed += dt;
+        if self.elapsed >= SAMPLE_PERIOD {
+            self.fps = self.frames as f32 / self.elapsed.as_secs_f32();
+            self.frame_time = self.elapsed / self.frames;
+            self.frames = 0;
+            self.elapsed = Duration::ZERO;
+        }
+    }
+
+    pub fn fps(&self) -> f32 {
+        self.fps
+    }
+
+    pub fn frame_time(&self) -> Duration {
+        self.frame_time
+    }
+
+    pub fn draw(&self, text: &mut TextRenderer, camera: &Camera, chunk_count: usize) {
+        if !self.enabled {
+            return;
+        }
+
+        let position = camera.position;
+        let forward = camera.forward();
+        let lines = format!(
+            "{:.0} fps ({:.2} ms)\nposition: {:.1} {:.1} {:.1}\nfacing: {} (yaw {:.0}, pitch {:.0})\nchunks: {}",
+            self.fps,
+            self.frame_time.as_secs_f64() * 1000.0,
+            position.x,
+            position.y,
+            position.z,
+            compass_direction(forward),
+            forward.z.atan2(forward.x).to_degrees(),
+            forward.y.asin().to_degrees(),
+            chunk_count,
+        );
+
+        let [width, height] = text.measure(&lines);
+        text.queue_rect([MARGIN / 2.0, MARGIN / 2.0], [width + MARGIN * 1.5, height + MARGIN * 1.5], BACKGROUND_COLOR);
+        text.queue(&lines, [MARGIN, MARGIN], TEXT_COLOR);
+    }
+
+    /// A line of text in the bottom left corner, e.g. the current build tool.
+    pub fn draw_status(&self, text: &mut TextRenderer, status: &str, screen_height: u32) {
+        if !self.enabled {
+            return;
+        }
+
+        let [width, height] = text.measure(status);
+        let top = screen_height as f32 - height - MARGIN;
+        text.queue_rect([MARGIN / 2.0, top - MARGIN / 2.0], [width + MARGIN * 1.5, top + height + MARGIN / 2.0], BACKGROUND_COLOR);
+        text.queue(status, [MARGIN, top], TEXT_COLOR);
+    }
+}
+
+/// Cardinal direction `forward` points the most towards on the horizontal plane, north is -Z.
+pub fn compass_direction(forward: Vector3<f32>) -> &'static str {
+    if forward.x.abs() > forward.z.abs() {
+        if forward.x > 0.0 {
+            "east"
+        } else {
+            "west"
+        }
+    } else if forward.z > 0.0 {
+        "south"
+    } else {
+        "north"
+    }
+}
@@ -1,21 +1,45 @@
-use rand::Rng;
+use std::collections::HashMap;
+use std::io::BufRead;
 use std::mem;
+use std::path::PathBuf;
 
-use cgmath::Rotation3;
+use anyhow::Context;
+
+use cgmath::{MetricSpace, Rotation3};
 use wgpu::util::DeviceExt;
 use winit::{
     event::*,
     event_loop::{ControlFlow, EventLoop},
-    window::{Window, WindowBuilder},
+    window::{CursorGrabMode, Window, WindowBuilder},
 };
 
-mod camera;
-mod texture;
-mod light;
-mod render_utilities;
-mod voxel_things;
+pub mod blueprint;
+pub mod brush;
+pub mod camera;
+pub mod chunk_overlay;
+pub mod commands;
+pub mod console;
+pub mod debug_draw;
+pub mod editor;
+pub mod ghost;
+pub mod gui;
+pub mod hud;
+pub mod texture;
+pub mod light;
+pub mod line;
+pub mod mesh_export;
+pub mod outline;
+pub mod render_utilities;
+pub mod save;
+pub mod screenshot;
+pub mod settings_panel;
+pub mod shadow;
+pub mod sky;
+pub mod text;
+pub mod vox;
+pub mod voxel_things;
+pub mod voxelizer;
 use crate::voxel_things::*;
-use crate::voxel_things::vertex_desc::VertexDesc;
 
 struct Instance {
     position: cgmath::Vector3<f32>,
@@ -71,7 +95,71 @@ impl InstanceRaw {
     }
 }
 
-const NUM_INSTANCES_PER_ROW: u32 = 10;
+const WORLD_SIZE_IN_CHUNKS: i32 = 8;
+const WORLD_SEED: u32 = 484;
+/// How far the camera moves before translucent faces are sorted again.
+const TRANSLUCENT_RESORT_DISTANCE: f32 = 1.0;
+
+fn world_center() -> cgmath::Point3<f32> {
+    cgmath::Point3::new(
+        (WORLD_SIZE_IN_CHUNKS * chunk::CHUNK_SIZE as i32) as f32 / 2.0,
+        chunk::CHUNK_SIZE as f32 / 2.0,
+        (WORLD_SIZE_IN_CHUNKS * chunk::CHUNK_SIZE as i32) as f32 / 2.0,
+    )
+}
+
+fn world_chunk_positions() -> impl Iterator<Item = cgmath::Vector3<i32>> {
+    (0..WORLD_SIZE_IN_CHUNKS).flat_map(|x| (0..WORLD_SIZE_IN_CHUNKS).map(move |z| cgmath::Vector3::new(x, 0, z)))
+}
+
+/// The storage backend named by `--storage <name>` on the command line, region files without it.
+fn storage_backend() -> anyhow::Result<save::StorageBackend> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--storage");
+    if args.next().is_none() {
+        return Ok(save::StorageBackend::default());
+    }
+
+    let names = save::StorageBackend::ALL.map(|backend| backend.name()).join(", ");
+    let name = args.next().with_context(|| format!("--storage needs one of: {}", names))?;
+    save::StorageBackend::from_name(&name).with_context(|| format!("unknown storage backend '{}', expected one of: {}", name, names))
+}
+
+fn generate_world() -> world::World {
+    let mut world = world::World::new(WORLD_SEED);
+    for position in world_chunk_positions() {
+        world.generate_chunk(position);
+    }
+    world
+}
+
+fn create_camera() -> camera::Camera {
+    camera::Camera::new((0.0, 5.0, 10.0), cgmath::Deg(-90.0), cgmath::Deg(-20.0))
+}
+
+/// The world in `save` with its camera and time of day, or a newly generated one if nothing was
+/// saved. A save that can't be read is logged and marked as failed, so it's left alone until the
+/// `save` command writes over it. Saving keeps using the compression the world was saved with.
+fn load_world(save: &mut save::WorldSave) -> (world::World, camera::Camera, f32) {
+    match save.load(WORLD_SEED, world_chunk_positions()) {
+        Ok(loaded) => {
+            for warning in &loaded.warnings {
+                log::warn!("{}", warning);
+            }
+            match loaded.metadata {
+                Some(metadata) => {
+                    save.compression = metadata.compression;
+                    (loaded.world, metadata.camera(), metadata.time_of_day)
+                }
+                None => (loaded.world, create_camera(), light::DEFAULT_TIME_OF_DAY),
+            }
+        }
+        Err(error) => {
+            log::error!("failed to load the world, autosaving is paused until you run 'save': {:#}", error);
+            save.load_failed = true;
+            (generate_world(), create_camera(), light::DEFAULT_TIME_OF_DAY)
+        }
+    }
+}
 
 struct State {
     surface: wgpu::Surface,
@@ -89,17 +177,45 @@ struct State {
 
     depth_texture: texture::Texture,
 
-    render_pipeline: wgpu::RenderPipeline,
-
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    num_indices: u32,
+    light: light::Light,
+    time_of_day: f32,
+    light_buffer: wgpu::Buffer,
+    light_bind_group: wgpu::BindGroup,
+    shadow_map: shadow::ShadowMap,
+    sky: sky::Sky,
+    block_texture_bind_group: wgpu::BindGroup,
 
+    render_pipeline: wgpu::RenderPipeline,
+    translucent_pipeline: wgpu::RenderPipeline,
+
+    world: world::World,
+    chunk_meshes: HashMap<cgmath::Vector3<i32>, chunk::ChunkMesh>,
+    translucent_meshes: HashMap<cgmath::Vector3<i32>, chunk::ChunkMesh>,
+    last_sort_position: cgmath::Point3<f32>,
+
+    editor: editor::Editor,
+    outline: outline::Outline,
+    ghost: ghost::Ghost,
+    debug_draw: debug_draw::DebugDraw,
+    chunk_overlay: chunk_overlay::ChunkOverlay,
+    terrain_draft: settings_panel::TerrainDraft,
+    text: text::TextRenderer,
+    hud: hud::Hud,
+    gui: gui::Gui,
+    commands: commands::CommandRegistry,
+    console: console::Console,
+    /// Path the next frame gets saved to, set by the `screenshot` command.
+    screenshot: Option<PathBuf>,
+    clipboard: Option<region::Clipboard>,
+    blueprints: blueprint::BlueprintLibrary,
+    world_save: save::WorldSave,
+    autosave: save::Autosave,
+    cursor_grabbed: bool,
     mouse_pressed: bool,
 }
 
 impl State {
-    async fn new(window: &Window) -> Self {
+    async fn new(window: &Window, commands: commands::CommandRegistry, storage: save::StorageBackend) -> Self {
         let size = window.inner_size();
 
         let instance = wgpu::Instance::new(wgpu::Backends::all());
@@ -126,7 +242,7 @@ impl State {
             .unwrap();
 
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
             format: surface.get_supported_formats(&adapter)[0],
             width: size.width,
             height: size.height,
@@ -135,7 +251,8 @@ impl State {
         };
         surface.configure(&device, &config);
 
-        let camera = camera::Camera::new((0.0, 5.0, 10.0), cgmath::Deg(-90.0), cgmath::Deg(-20.0));
+        let mut world_save = save::WorldSave::new(save::DEFAULT_DIRECTORY, storage);
+        let (world, camera, time_of_day) = load_world(&mut world_save);
         let projection =
             camera::Projection::new(config.width, config.height, cgmath::Deg(45.0), 0.1, 100.0);
         let camera_controller = camera::CameraController::new(4.0, 0.4);
@@ -149,20 +266,12 @@ impl State {
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
-        let camera_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
-                label: Some("Camera Bind Group Layout"),
-            });
+        let camera_bind_group_layout = render_utilities::create_bind_group_layout(
+            &device,
+            "Camera Bind Group Layout",
+            0,
+            wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+        );
 
         let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &camera_bind_group_layout,
@@ -176,14 +285,9 @@ impl State {
         let depth_texture =
             texture::Texture::create_depth_texture(&device, &config, "Depth Texture");
 
-        let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
-
-        let light = light::Light {
-            position: [50.0, 2.0, 50.0],
-            _padding: 0,
-            color: [1.0, 1.0, 1.0],
-            _padding2: 0,
-        };
+        let shadow_config = shadow::ShadowConfig::default();
+        let mut light = light::Light::new(light::sun_position(world_center(), time_of_day).into(), [1.0, 1.0, 1.0]);
+        light.update_view_proj(world_center(), &shadow_config);
 
         let light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("light_buffer"),
@@ -193,32 +297,44 @@ impl State {
 
         let light_bind_group_layout = light::create_light_bind_group_layout(&device);
 
-        let light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("light_bind_group"),
-            layout: &light_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: light_buffer.as_entire_binding(),
-            }],
-        });
+        let block_textures = texture::Texture::load_block_textures(&device, &queue).unwrap();
+        let block_texture_bind_group_layout = texture::create_texture_array_bind_group_layout(&device, "block_texture_bind_group_layout");
+        let block_texture_bind_group = texture::create_texture_bind_group(&device, &block_texture_bind_group_layout, &block_textures, "block_texture_bind_group");
 
-        let render_pipeline = voxel::create_voxel_pipeline(device, config.format, &light_bind_group_layout, shader);
+        let shadow_map = shadow::ShadowMap::new(&device, shadow_config, &light_buffer, &block_texture_bind_group_layout);
 
-        let mut chunk = chunk::Chunk::new();
+        let light_bind_group = light::create_light_bind_group(&device, &light_bind_group_layout, &light_buffer, &shadow_map.texture);
 
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(&chunk.vertices),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
+        let sky = sky::Sky::new(&device, config.format, &camera_bind_group_layout, &light_bind_group_layout, sky::SkyConfig::default());
+        sky.update(&queue);
 
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(&chunk.indices),
-            usage: wgpu::BufferUsages::INDEX,
-        });
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
+        let render_pipeline = voxel::create_voxel_pipeline(&device, config.format, &camera_bind_group_layout, &light_bind_group_layout, &sky.bind_group_layout, &block_texture_bind_group_layout, shader);
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
+        let translucent_pipeline = voxel::create_translucent_voxel_pipeline(&device, config.format, &camera_bind_group_layout, &light_bind_group_layout, &sky.bind_group_layout, &block_texture_bind_group_layout, shader);
+
+        let outline = outline::Outline::new(&device, config.format, &camera_bind_group_layout);
+        let ghost = ghost::Ghost::new(&device, config.format, &camera_bind_group_layout);
+        let debug_draw = debug_draw::DebugDraw::new(&device, config.format, &camera_bind_group_layout);
+        let text = text::TextRenderer::new(&device, &queue, config.format, config.width, config.height).unwrap();
+        let gui = gui::Gui::new(&device, config.format, config.width, config.height, window.scale_factor());
+
+        let mut chunk_overlay = chunk_overlay::ChunkOverlay::new();
+        let mut chunk_meshes = HashMap::new();
+        let mut translucent_meshes = HashMap::new();
+        for chunk in world.chunks.values() {
+            let mut mesh = chunk::ChunkMesh::new();
+            let mut translucent_mesh = chunk::ChunkMesh::new();
+            let stats = chunk::build_chunk_mesh(&world, chunk, &mut mesh, &mut translucent_mesh, &device);
+            chunk_overlay.record(chunk.position, stats);
+            translucent_mesh.sort_back_to_front(&queue, camera.position);
+
+            chunk_meshes.insert(chunk.position, mesh);
+            translucent_meshes.insert(chunk.position, translucent_mesh);
+        }
 
-        let num_indices = chunk.indices.len() as u32;
+        let last_sort_position = camera.position;
+        let terrain_draft = settings_panel::TerrainDraft::new(world.seed, world.generator);
 
         Self {
             surface,
@@ -236,12 +352,39 @@ impl State {
 
             depth_texture,
 
-            render_pipeline,
-
-            vertex_buffer,
-            index_buffer,
-            num_indices,
+            light,
+            time_of_day,
+            light_buffer,
+            light_bind_group,
+            shadow_map,
+            sky,
+            block_texture_bind_group,
 
+            render_pipeline,
+            translucent_pipeline,
+
+            world,
+            chunk_meshes,
+            translucent_meshes,
+            last_sort_position,
+
+            editor: editor::Editor::new(),
+            outline,
+            ghost,
+            debug_draw,
+            chunk_overlay,
+            terrain_draft,
+            text,
+            hud: hud::Hud::new(),
+            gui,
+            commands,
+            console: console::Console::new(),
+            screenshot: None,
+            clipboard: None,
+            blueprints: blueprint::BlueprintLibrary::default(),
+            world_save,
+            autosave: save::Autosave::default(),
+            cursor_grabbed: false,
             mouse_pressed: false,
         }
     }
@@ -255,10 +398,23 @@ impl State {
             self.surface.configure(&self.device, &self.config);
             self.depth_texture =
                 texture::Texture::create_depth_texture(&self.device, &self.config, "Depth Texture");
+            self.text.resize(&self.queue, new_size.width, new_size.height);
+            self.gui.resize(new_size.width, new_size.height);
         }
     }
 
     fn input(&mut self, event: &WindowEvent) -> bool {
+        if let WindowEvent::Focused(false) = event {
+            self.editor.focus_lost(&mut self.world);
+            self.mouse_pressed = false;
+        }
+        if self.console.process_event(event, &self.commands) {
+            return true;
+        }
+        if self.gui.process_event(event) {
+            return true;
+        }
+
         match event {
             WindowEvent::KeyboardInput {
                 input:
@@ -268,9 +424,42 @@ impl State {
                         ..
                     },
                 ..
-            } => self.camera_controller.process_keyboard(*key, *state),
+            } => self.editor.process_keyboard(*key, *state) || self.editor.process_history_keys(*key, *state, &mut self.world) || self.chunk_overlay.process_keyboard(*key, *state) || self.hud.process_keyboard(*key, *state) || self.camera_controller.process_keyboard(*key, *state),
             WindowEvent::MouseWheel { delta, .. } => {
-                self.camera_controller.process_scroll(delta);
+                if !self.editor.process_scroll(delta) {
+                    self.camera_controller.process_scroll(delta);
+                }
+                true
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button,
+                ..
+            } if self.editor.enabled => {
+                match button {
+                    MouseButton::Left if self.editor.tool == editor::Tool::Brush => {
+                        self.editor.begin_stroke(&mut self.world, &self.camera);
+                        true
+                    }
+                    MouseButton::Left if self.editor.tool == editor::Tool::Select => self.editor.pick(&self.world, &self.camera),
+                    MouseButton::Left if self.editor.tool == editor::Tool::Place => self.paste_at_cursor(),
+                    MouseButton::Left => self.editor.break_block(&mut self.world, &self.camera),
+                    MouseButton::Right if self.editor.tool == editor::Tool::Select => self.editor.finish_polygon(),
+                    MouseButton::Right if self.editor.tool == editor::Tool::Place => {
+                        self.editor.tool = editor::Tool::Block;
+                        true
+                    }
+                    MouseButton::Right => self.editor.place_block(&mut self.world, &self.camera),
+                    _ => false,
+                };
+                true
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Released,
+                button: MouseButton::Left,
+                ..
+            } if self.editor.enabled => {
+                self.editor.end_stroke(&mut self.world);
                 true
             }
             WindowEvent::MouseInput {
@@ -285,8 +474,107 @@ impl State {
         }
     }
 
+    /// Pastes the clipboard where the placement preview is, returns whether anything was pasted.
+    fn paste_at_cursor(&mut self) -> bool {
+        let Some(clipboard) = &self.clipboard else {
+            return false;
+        };
+        match self.editor.place_origin(&self.world, &self.camera, clipboard.size()) {
+            Some(origin) => clipboard.paste(&mut self.world, origin, false).changed > 0,
+            None => false,
+        }
+    }
+
+    /// Build mode looks around with the mouse all the time, so it keeps the cursor captured unless
+    /// the GUI or the console is open.
+    fn update_cursor(&mut self, window: &Window) {
+        let grab = self.editor.enabled && !self.gui.enabled && !self.console.open;
+        if self.cursor_grabbed == grab {
+            return;
+        }
+
+        self.cursor_grabbed = grab;
+        if self.cursor_grabbed {
+            if window.set_cursor_grab(CursorGrabMode::Locked).is_err() {
+                let _ = window.set_cursor_grab(CursorGrabMode::Confined);
+            }
+        } else {
+            let _ = window.set_cursor_grab(CursorGrabMode::None);
+        }
+        window.set_cursor_visible(!self.cursor_grabbed);
+    }
+
+    /// Rebuilds the meshes of every chunk edited since the last frame.
+    fn remesh_dirty_chunks(&mut self) {
+        for position in self.world.dirty_chunks.drain().collect::<Vec<_>>() {
+            let Some(chunk) = self.world.chunks.get(&position) else {
+                continue;
+            };
+
+            let mesh = self.chunk_meshes.entry(position).or_default();
+            let translucent_mesh = self.translucent_meshes.entry(position).or_default();
+            let stats = chunk::build_chunk_mesh(&self.world, chunk, mesh, translucent_mesh, &self.device);
+            self.chunk_overlay.record(position, stats);
+            translucent_mesh.sort_back_to_front(&self.queue, self.camera.position);
+        }
+    }
+
+    fn run_console_commands(&mut self) {
+        let time_of_day = self.time_of_day;
+        let lines = self.console.take_submitted();
+        let ran_commands = !lines.is_empty();
+        for line in lines {
+            let mut context = commands::CommandContext {
+                world: &mut self.world,
+                camera: &mut self.camera,
+                time_of_day: &mut self.time_of_day,
+                clipboard: &mut self.clipboard,
+                editor: &mut self.editor,
+                blueprints: &self.blueprints,
+                world_save: &mut self.world_save,
+                autosave: &mut self.autosave,
+                screenshot: Some(&mut self.screenshot),
+            };
+            match self.commands.execute(&mut context, &line) {
+                Ok(output) => self.console.print(&output),
+                Err(error) => self.console.print(&format!("error: {:#}", error)),
+            }
+        }
+
+        // Any command may have replaced or transformed the clipboard.
+        if ran_commands {
+            self.ghost.set_clipboard(&self.device, self.clipboard.as_ref());
+        }
+
+        if self.time_of_day != time_of_day {
+            self.light.position = light::sun_position(world_center(), self.time_of_day).into();
+            self.light.update_view_proj(world_center(), &self.shadow_map.config);
+        }
+    }
+
+    /// Saves the world, logging instead of failing since nobody asked for it. Does nothing while
+    /// the save that failed to load is still on disk.
+    fn save_world(&mut self) {
+        if self.world_save.load_failed {
+            log::warn!("not saving over {}, it failed to load, run 'save' to overwrite it", self.world_save.directory.display());
+            return;
+        }
+        let metadata = save::WorldMetadata::new(&self.world, &self.camera, self.time_of_day, self.world_save.compression);
+        match self.world_save.save(&mut self.world, &metadata) {
+            Ok(summary) => log::info!("autosave: {}", summary),
+            Err(error) => log::error!("autosave failed: {:#}", error),
+        }
+    }
+
     fn update(&mut self, dt: std::time::Duration) {
+        self.run_console_commands();
+        if self.autosave.tick(dt) {
+            self.save_world();
+        }
+        self.remesh_dirty_chunks();
+
         self.camera_controller.update_camera(&mut self.camera, dt);
+        self.editor.update(&mut self.world, &self.camera, dt);
         self.camera_uniform
             .update_view_proj(&self.camera, &self.projection);
         self.queue.write_buffer(
@@ -294,6 +582,56 @@ impl State {
             0,
             bytemuck::cast_slice(&[self.camera_uniform]),
         );
+        self.queue
+            .write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(&[self.light]));
+
+        let target = if self.editor.enabled && self.editor.tool == editor::Tool::Block {
+            self.editor.target(&self.world, &self.camera)
+        } else {
+            None
+        };
+        self.outline.update(&self.queue, target.as_ref());
+        self.editor.draw_brush(&self.world, &self.camera, &mut self.debug_draw);
+        self.editor.draw_selection(&self.world, &self.camera, &mut self.debug_draw);
+        let place_origin = self.clipboard.as_ref().and_then(|clipboard| self.editor.place_origin(&self.world, &self.camera, clipboard.size()));
+        self.ghost.update(&self.queue, place_origin);
+        self.chunk_overlay.update(&self.world, self.camera.position, &mut self.debug_draw);
+
+        self.hud.update(dt);
+        self.hud.draw(&mut self.text, &self.camera, self.world.chunks.len());
+        if self.editor.enabled {
+            self.hud.draw_status(&mut self.text, &self.editor.status(), self.size.height);
+        }
+        self.chunk_overlay.draw(&mut self.text, self.camera.position, self.size.width);
+        self.console.draw(&mut self.text, self.size.width, self.size.height);
+
+        let mut changes = settings_panel::SettingsChanges::default();
+        self.gui.run(|context| {
+            let settings = settings_panel::Settings {
+                camera_controller: &mut self.camera_controller,
+                light: &mut self.light,
+                sky: &mut self.sky.config,
+                terrain: &mut self.terrain_draft,
+                world_seed: self.world.seed,
+                world_generator: self.world.generator,
+            };
+            changes = settings_panel::show(context, settings);
+        });
+        if changes.sky {
+            self.sky.update(&self.queue);
+        }
+        if changes.regenerate {
+            self.world.seed = self.terrain_draft.seed;
+            self.world.generator = self.terrain_draft.generator;
+            self.world.regenerate();
+        }
+
+        if self.camera.position.distance2(self.last_sort_position) > TRANSLUCENT_RESORT_DISTANCE * TRANSLUCENT_RESORT_DISTANCE {
+            for mesh in self.translucent_meshes.values_mut() {
+                mesh.sort_back_to_front(&self.queue, self.camera.position);
+            }
+            self.last_sort_position = self.camera.position;
+        }
     }
 
     fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
@@ -309,18 +647,21 @@ impl State {
                 label: Some("Render Encode"),
             });
 
+        let mut shadow_pass = self.shadow_map.begin_pass(&mut encoder, &self.block_texture_bind_group);
+        for mesh in self.chunk_meshes.values() {
+            mesh.draw(&mut shadow_pass);
+        }
+        drop(shadow_pass);
+
+        self.sky.render(&mut encoder, &view, &self.camera_bind_group, &self.light_bind_group);
+
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Render Pass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                 view: &view,
                 resolve_target: None,
                 ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color {
-                        r: 0.1,
-                        g: 0.2,
-                        b: 0.3,
-                        a: 1.0,
-                    }),
+                    load: wgpu::LoadOp::Load,
                     store: true,
                 },
             })],
@@ -336,15 +677,46 @@ impl State {
 
         render_pass.set_pipeline(&self.render_pipeline);
         render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
-        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.set_bind_group(1, &self.light_bind_group, &[]);
+        render_pass.set_bind_group(2, &self.sky.bind_group, &[]);
+        render_pass.set_bind_group(3, &self.block_texture_bind_group, &[]);
+        for mesh in self.chunk_meshes.values() {
+            mesh.draw(&mut render_pass);
+        }
 
-        render_pass.draw_indexed(0..self.num_indices, 0, 0..self.instances.len() as _);
+        let mut translucent_meshes = self.translucent_meshes.values().collect::<Vec<_>>();
+        translucent_meshes.sort_by(|a, b| {
+            let a = a.center().distance2(self.camera.position);
+            let b = b.center().distance2(self.camera.position);
+            b.total_cmp(&a)
+        });
+        render_pass.set_pipeline(&self.translucent_pipeline);
+        for mesh in translucent_meshes {
+            mesh.draw(&mut render_pass);
+        }
+
+        self.outline.draw(&mut render_pass, &self.camera_bind_group);
+        self.ghost.draw(&mut render_pass, &self.camera_bind_group);
 
         drop(render_pass);
 
+        self.debug_draw.render(&self.device, &self.queue, &mut encoder, &view, &self.depth_texture.view, &self.camera_bind_group);
+        self.text.render(&self.device, &self.queue, &mut encoder, &view);
+        self.gui.render(&self.device, &self.queue, &mut encoder, &view);
+
+        let capture = self.screenshot.take().map(|path| {
+            let capture = screenshot::capture(&self.device, &mut encoder, &output.texture, self.config.width, self.config.height, self.config.format);
+            (path, capture)
+        });
+
         self.queue.submit(std::iter::once(encoder.finish()));
+
+        if let Some((path, capture)) = capture {
+            match capture.save(&self.device, &path) {
+                Ok(()) => log::info!("saved screenshot to {}", path.display()),
+                Err(error) => log::error!("screenshot failed: {:#}", error),
+            }
+        }
         output.present();
 
         Ok(())
@@ -352,27 +724,38 @@ impl State {
 }
 
 pub async fn run() {
+    run_with_commands(commands::CommandRegistry::with_builtins()).await;
+}
+
+/// Like `run`, with a command registry that can hold commands of its own.
+pub async fn run_with_commands(commands: commands::CommandRegistry) {
     env_logger::init();
+    let storage = match storage_backend() {
+        Ok(storage) => storage,
+        Err(error) => {
+            log::error!("{:#}", error);
+            return;
+        }
+    };
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new().build(&event_loop).unwrap();
 
-    let mut state = State::new(&window).await;
+    let mut state = State::new(&window, commands, storage).await;
     let mut last_render_time = std::time::Instant::now();
 
     event_loop.run(move |event, _, control_flow| match event {
         Event::DeviceEvent {
             event: DeviceEvent::MouseMotion { delta },
             ..
-        } => {
-            if state.mouse_pressed {
-                state.camera_controller.process_mouse(delta.0, delta.1)
-            }
-        }
+        } if state.mouse_pressed || state.cursor_grabbed => state.camera_controller.process_mouse(delta.0, delta.1),
         Event::WindowEvent {
             window_id,
             ref event,
         } if window_id == window.id() && !state.input(event) => match event {
-            WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+            WindowEvent::CloseRequested => {
+                state.save_world();
+                *control_flow = ControlFlow::Exit;
+            }
             WindowEvent::Resized(physical_size) => state.resize(*physical_size),
             WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
                 state.resize(**new_inner_size)
@@ -391,7 +774,54 @@ pub async fn run() {
                 Err(e) => eprintln!("{:?}", e),
             }
         }
-        Event::MainEventsCleared => window.request_redraw(),
+        Event::MainEventsCleared => {
+            state.update_cursor(&window);
+            window.request_redraw();
+        }
         _ => {}
     });
 }
+
+/// Runs commands read line by line from stdin against a world without opening a window.
+pub fn run_headless(commands: commands::CommandRegistry) {
+    env_logger::init();
+    let storage = match storage_backend() {
+        Ok(storage) => storage,
+        Err(error) => {
+            eprintln!("error: {:#}", error);
+            return;
+        }
+    };
+    let mut world_save = save::WorldSave::new(save::DEFAULT_DIRECTORY, storage);
+    let (mut world, mut camera, mut time_of_day) = load_world(&mut world_save);
+    // Only the `save` command saves here, nothing ticks the timer.
+    let mut autosave = save::Autosave::default();
+    let mut clipboard = None;
+    let mut editor = editor::Editor::new();
+    let blueprints = blueprint::BlueprintLibrary::default();
+
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut context = commands::CommandContext {
+            world: &mut world,
+            camera: &mut camera,
+            time_of_day: &mut time_of_day,
+            clipboard: &mut clipboard,
+            editor: &mut editor,
+            blueprints: &blueprints,
+            world_save: &mut world_save,
+            autosave: &mut autosave,
+            screenshot: None,
+        };
+        match commands.execute(&mut context, &line) {
+            Ok(output) => println!("{}", output),
+            Err(error) => eprintln!("error: {:#}", error),
+        }
+    }
+}
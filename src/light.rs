@@ -1,24 +1,124 @@
+use cgmath::{InnerSpace, Matrix4, Point3, SquareMatrix, Vector3};
+
+use crate::{camera, shadow, texture};
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Light {
     pub position: [f32; 3],
     pub _padding: u32,
     pub color: [f32; 3],
+    pub shadow_bias: f32,
+    /// Unit vector pointing from the lit scene towards the sun.
+    pub direction: [f32; 3],
     pub _padding2: u32,
+    pub view_proj: [[f32; 4]; 4],
+}
+
+impl Light {
+    pub fn new(position: [f32; 3], color: [f32; 3]) -> Self {
+        Self {
+            position,
+            _padding: 0,
+            color,
+            shadow_bias: 0.0,
+            direction: [0.0, 1.0, 0.0],
+            _padding2: 0,
+            view_proj: Matrix4::identity().into(),
+        }
+    }
+
+    /// Points the sun at `target` and fits an orthographic shadow frustum around it.
+    pub fn update_view_proj(&mut self, target: Point3<f32>, config: &shadow::ShadowConfig) {
+        let position = Point3::from(self.position);
+        let to_light = position - target;
+        let distance = to_light.magnitude();
+        let direction = to_light / distance;
+
+        let up = if direction.y.abs() > 0.99 {
+            Vector3::unit_z()
+        } else {
+            Vector3::unit_y()
+        };
+        let view = Matrix4::look_at_rh(position, target, up);
+        let proj = cgmath::ortho(
+            -config.extent,
+            config.extent,
+            -config.extent,
+            config.extent,
+            (distance - config.extent * 2.0).max(0.1),
+            distance + config.extent * 2.0,
+        );
+
+        self.direction = direction.into();
+        self.shadow_bias = config.bias;
+        self.view_proj = (camera::OPENGL_TO_WGPU_MATRIX * proj * view).into();
+    }
+}
+
+/// Hours after midnight the engine starts at.
+pub const DEFAULT_TIME_OF_DAY: f32 = 10.0;
+const SUN_DISTANCE: f32 = 90.0;
+
+/// Where the sun is at `hours` after midnight. It rises in the east (+X) at 6, is highest at noon
+/// and sets in the west at 18, tilted a bit to the south (+Z) so it never stands straight up.
+pub fn sun_position(target: Point3<f32>, hours: f32) -> Point3<f32> {
+    let angle = (hours - 6.0) / 12.0 * std::f32::consts::PI;
+    let direction = Vector3::new(angle.cos(), angle.sin(), 0.3).normalize();
+    target + direction * SUN_DISTANCE
 }
 
 pub fn create_light_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
     device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
         label: Some("create_light_bind_group_layout"),
-        entries: &[wgpu::BindGroupLayoutEntry {
-            binding: 0,
-            visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-            ty: wgpu::BindingType::Buffer {
-                ty: wgpu::BufferBindingType::Uniform,
-                has_dynamic_offset: false,
-                min_binding_size: None,
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Depth,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                count: None,
+            },
+        ],
+    })
+}
+
+pub fn create_light_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, light_buffer: &wgpu::Buffer, shadow_texture: &texture::Texture) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("light_bind_group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: light_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&shadow_texture.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::Sampler(&shadow_texture.sampler),
             },
-            count: None,
-        }],
+        ],
     })
 }
@@ -0,0 +1,72 @@
+use cgmath::Vector3;
+
+use crate::voxel_things::vertex_desc::VertexDesc;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LineVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+}
+
+impl LineVertex {
+    pub fn new(position: Vector3<f32>, color: [f32; 4]) -> Self {
+        Self {
+            position: position.into(),
+            color,
+        }
+    }
+}
+
+impl VertexDesc for LineVertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<LineVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+/// The 12 edges of an axis aligned box as pairs of line list vertices.
+pub fn box_edges(min: Vector3<f32>, max: Vector3<f32>, color: [f32; 4]) -> [LineVertex; 24] {
+    let corner = |x: bool, y: bool, z: bool| {
+        LineVertex::new(
+            Vector3::new(
+                if x { max.x } else { min.x },
+                if y { max.y } else { min.y },
+                if z { max.z } else { min.z },
+            ),
+            color,
+        )
+    };
+
+    [
+        // bottom
+        corner(false, false, false), corner(true, false, false),
+        corner(true, false, false), corner(true, false, true),
+        corner(true, false, true), corner(false, false, true),
+        corner(false, false, true), corner(false, false, false),
+        // top
+        corner(false, true, false), corner(true, true, false),
+        corner(true, true, false), corner(true, true, true),
+        corner(true, true, true), corner(false, true, true),
+        corner(false, true, true), corner(false, true, false),
+        // sides
+        corner(false, false, false), corner(false, true, false),
+        corner(true, false, false), corner(true, true, false),
+        corner(true, false, true), corner(true, true, true),
+        corner(false, false, true), corner(false, true, true),
+    ]
+}
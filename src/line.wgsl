@@ -0,0 +1,39 @@
+struct CameraUniform {
+	view_pos: vec4<f32>,
+	view_proj: mat4x4<f32>,
+	inv_view_proj: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// Fraction of the distance to the camera every vertex is moved towards it, so lines drawn over
+// faces win the depth test. Depth bias isn't applied to line primitives on every backend.
+let DEPTH_PULL: f32 = 0.002;
+
+struct VertexInput {
+	@location(0) position: vec3<f32>,
+	@location(1) color: vec4<f32>,
+}
+
+struct VertexOutput {
+	@builtin(position) clip_position: vec4<f32>,
+	@location(0) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(
+	in: VertexInput,
+) -> VertexOutput {
+	var out: VertexOutput;
+	let position = mix(in.position, camera.view_pos.xyz, DEPTH_PULL);
+	out.clip_position = camera.view_proj * vec4<f32>(position, 1.0);
+	out.color = in.color;
+	return out;
+}
+
+@fragment
+fn fs_main(
+	in: VertexOutput,
+) -> @location(0) vec4<f32> {
+	return in.color;
+}
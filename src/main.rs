@@ -1,5 +1,9 @@
-use voxel_engine::run;
+use voxel_engine::{commands::CommandRegistry, run, run_headless};
 
 fn main() {
-    pollster::block_on(run());
+    if std::env::args().any(|arg| arg == "--headless") {
+        run_headless(CommandRegistry::with_builtins());
+    } else {
+        pollster::block_on(run());
+    }
 }
@@ -0,0 +1,353 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{ensure, Context};
+use cgmath::Vector3;
+use serde_json::{json, Value};
+
+use crate::voxel_things::{
+    block::{self, Transparency},
+    chunk::{self, ChunkMesh},
+    direction::Direction,
+    region::Region,
+    vertex::Vertex,
+    voxel::BlockType,
+    world::World,
+};
+
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const GLB_VERSION: u32 = 2;
+const GLB_JSON_CHUNK: u32 = 0x4E4F_534A;
+const GLB_BIN_CHUNK: u32 = 0x004E_4942;
+const GL_FLOAT: u32 = 5126;
+const GL_UNSIGNED_INT: u32 = 5125;
+const GL_ARRAY_BUFFER: u32 = 34962;
+const GL_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const GL_NEAREST: u32 = 9728;
+const GL_REPEAT: u32 = 10497;
+/// Where `write_obj` copies the textures to, next to the OBJ.
+const OBJ_TEXTURE_DIRECTORY: &str = "textures";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshFormat {
+    /// Wavefront OBJ with an MTL file and the textures next to it.
+    Obj,
+    /// Binary glTF 2.0 with the textures inside.
+    Glb,
+}
+
+impl MeshFormat {
+    pub const ALL: [MeshFormat; 2] = [MeshFormat::Obj, MeshFormat::Glb];
+
+    /// Also the file extension.
+    pub fn name(&self) -> &'static str {
+        match self {
+            MeshFormat::Obj => "obj",
+            MeshFormat::Glb => "glb",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<MeshFormat> {
+        Self::ALL.into_iter().find(|format| format.name() == name)
+    }
+
+    pub fn write(&self, path: &Path, chunks: &[ExportedChunk]) -> anyhow::Result<ExportSummary> {
+        match self {
+            MeshFormat::Obj => write_obj(path, chunks),
+            MeshFormat::Glb => write_glb(path, chunks),
+        }
+    }
+}
+
+/// The faces of a chunk that use one texture.
+pub struct Primitive {
+    /// Layer of `block::BLOCK_TEXTURES`.
+    pub texture_layer: u32,
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+/// A chunk's mesh split by texture, positions are relative to `offset`.
+pub struct ExportedChunk {
+    pub position: Vector3<i32>,
+    pub offset: Vector3<f32>,
+    pub primitives: Vec<Primitive>,
+}
+
+/// What an export wrote.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExportSummary {
+    pub chunks: usize,
+    pub triangles: usize,
+}
+
+impl fmt::Display for ExportSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "exported {} triangles in {} chunks", self.triangles, self.chunks)
+    }
+}
+
+/// Meshes the loaded chunks `region` touches, leaving out the ones without faces. With `greedy`
+/// faces are merged by `chunk::mesh_chunk_greedy`, otherwise the meshes match what is rendered.
+pub fn mesh_region(world: &World, region: Region, greedy: bool) -> Vec<ExportedChunk> {
+    // The loaded chunks are filtered rather than every chunk position in the region visited, the
+    // corners can be arbitrarily far apart.
+    let (min, _) = World::split_position(region.min);
+    let (max, _) = World::split_position(region.max);
+    let chunk_region = Region::new(min, max);
+    let mut positions = world.chunks.keys().copied().filter(|position| chunk_region.contains(*position)).collect::<Vec<_>>();
+    positions.sort_by_key(|position| (position.x, position.y, position.z));
+
+    let mut exported = Vec::new();
+    for position in positions {
+        let chunk = &world.chunks[&position];
+
+        let (mut mesh, mut translucent_mesh) = (ChunkMesh::new(), ChunkMesh::new());
+        if greedy {
+            chunk::mesh_chunk_greedy(world, chunk, &mut mesh, &mut translucent_mesh);
+        } else {
+            chunk::mesh_chunk(world, chunk, &mut mesh, &mut translucent_mesh);
+        }
+
+        // Every quad is four vertices and six indices, see `ChunkMesh::push_quad`.
+        let mut primitives = BTreeMap::new();
+        for mesh in [&mesh, &translucent_mesh] {
+            for (quad, (vertices, indices)) in mesh.vertices.chunks_exact(4).zip(mesh.indices.chunks_exact(6)).enumerate() {
+                let primitive = primitives.entry(vertices[0].texture_layer).or_insert_with(|| Primitive {
+                    texture_layer: vertices[0].texture_layer,
+                    vertices: Vec::new(),
+                    indices: Vec::new(),
+                });
+                let start = primitive.vertices.len() as u32;
+                primitive.vertices.extend_from_slice(vertices);
+                primitive.indices.extend(indices.iter().map(|index| start + *index - quad as u32 * 4));
+            }
+        }
+
+        if !primitives.is_empty() {
+            exported.push(ExportedChunk {
+                position,
+                offset: chunk.world_offset(),
+                primitives: primitives.into_values().collect(),
+            });
+        }
+    }
+    exported
+}
+
+fn textures_directory() -> PathBuf {
+    Path::new(env!("OUT_DIR")).join("res").join("textures")
+}
+
+fn texture_name(texture_layer: u32) -> &'static str {
+    block::BLOCK_TEXTURES[texture_layer as usize]
+}
+
+/// How the blocks using a texture are drawn.
+fn texture_transparency(texture_layer: u32) -> Transparency {
+    BlockType::ALL
+        .into_iter()
+        .filter(|block_type| *block_type != BlockType::Empty)
+        .find(|block_type| Direction::ALL.iter().any(|direction| block_type.definition().texture_layer(*direction) == texture_layer))
+        .map_or(Transparency::Opaque, |block_type| block_type.definition().transparency)
+}
+
+fn summarize(chunks: &[ExportedChunk]) -> ExportSummary {
+    ExportSummary {
+        chunks: chunks.len(),
+        triangles: chunks.iter().flat_map(|chunk| &chunk.primitives).map(|primitive| primitive.indices.len() / 3).sum(),
+    }
+}
+
+/// Writes an object per chunk in world space, with a material per texture in an MTL file of the
+/// same name. Vertex colors follow the positions, as most tools that read OBJ expect.
+pub fn write_obj(path: &Path, chunks: &[ExportedChunk]) -> anyhow::Result<ExportSummary> {
+    ensure!(!chunks.is_empty(), "there is nothing to export");
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let material_path = path.with_extension("mtl");
+    let material_file = material_path.file_name().context("the path has no file name")?.to_string_lossy();
+
+    let mut out = BufWriter::new(File::create(path).with_context(|| format!("failed to create {}", path.display()))?);
+    writeln!(out, "mtllib {}", material_file)?;
+    let mut next_index = 1;
+    let mut texture_layers = BTreeSet::new();
+    for chunk in chunks {
+        writeln!(out, "o chunk_{}_{}_{}", chunk.position.x, chunk.position.y, chunk.position.z)?;
+        for primitive in &chunk.primitives {
+            for vertex in &primitive.vertices {
+                let [x, y, z] = vertex.position;
+                let [r, g, b] = vertex.color;
+                writeln!(out, "v {} {} {} {} {} {}", x + chunk.offset.x, y + chunk.offset.y, z + chunk.offset.z, r, g, b)?;
+            }
+            // v points up in OBJ and down in the engine.
+            for vertex in &primitive.vertices {
+                writeln!(out, "vt {} {}", vertex.uv[0], 1.0 - vertex.uv[1])?;
+            }
+            for vertex in &primitive.vertices {
+                let [x, y, z] = vertex.normal;
+                writeln!(out, "vn {} {} {}", x, y, z)?;
+            }
+
+            writeln!(out, "usemtl {}", texture_name(primitive.texture_layer))?;
+            for triangle in primitive.indices.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|corner| next_index + triangle[corner]);
+                writeln!(out, "f {0}/{0}/{0} {1}/{1}/{1} {2}/{2}/{2}", a, b, c)?;
+            }
+            next_index += primitive.vertices.len() as u32;
+            texture_layers.insert(primitive.texture_layer);
+        }
+    }
+    out.flush().with_context(|| format!("failed to write {}", path.display()))?;
+
+    let texture_directory = directory.join(OBJ_TEXTURE_DIRECTORY);
+    fs::create_dir_all(&texture_directory).with_context(|| format!("failed to create {}", texture_directory.display()))?;
+    let mut materials = String::new();
+    for texture_layer in texture_layers {
+        let name = texture_name(texture_layer);
+        let file = format!("{}.png", name);
+        fs::copy(textures_directory().join(&file), texture_directory.join(&file)).with_context(|| format!("failed to copy the {} texture", name))?;
+
+        materials.push_str(&format!("newmtl {}\nKd 1 1 1\nmap_Kd {}/{}\n", name, OBJ_TEXTURE_DIRECTORY, file));
+        if texture_transparency(texture_layer) != Transparency::Opaque {
+            materials.push_str(&format!("map_d {}/{}\n", OBJ_TEXTURE_DIRECTORY, file));
+        }
+        materials.push('\n');
+    }
+    fs::write(&material_path, materials).with_context(|| format!("failed to write {}", material_path.display()))?;
+
+    Ok(summarize(chunks))
+}
+
+/// The binary chunk of a GLB and the views and accessors into it.
+#[derive(Default)]
+struct GlbBuffer {
+    bytes: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl GlbBuffer {
+    fn view(&mut self, bytes: &[u8], target: Option<u32>) -> usize {
+        self.bytes.resize(self.bytes.len().next_multiple_of(4), 0);
+        let mut view = json!({ "buffer": 0, "byteOffset": self.bytes.len(), "byteLength": bytes.len() });
+        if let Some(target) = target {
+            view["target"] = json!(target);
+        }
+        self.bytes.extend_from_slice(bytes);
+        self.views.push(view);
+        self.views.len() - 1
+    }
+
+    /// An accessor of float vectors, POSITION ones also need their bounds.
+    fn floats<const N: usize>(&mut self, values: impl Iterator<Item = [f32; N]>, bounds: bool) -> usize {
+        let values = values.collect::<Vec<_>>();
+        let bytes = values.iter().flatten().flat_map(|value| value.to_le_bytes()).collect::<Vec<_>>();
+        let view = self.view(&bytes, Some(GL_ARRAY_BUFFER));
+        let mut accessor = json!({ "bufferView": view, "componentType": GL_FLOAT, "count": values.len(), "type": format!("VEC{}", N) });
+        if bounds {
+            accessor["min"] = json!((0..N).map(|axis| values.iter().map(|value| value[axis]).fold(f32::MAX, f32::min)).collect::<Vec<_>>());
+            accessor["max"] = json!((0..N).map(|axis| values.iter().map(|value| value[axis]).fold(f32::MIN, f32::max)).collect::<Vec<_>>());
+        }
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    fn indices(&mut self, indices: &[u32]) -> usize {
+        let bytes = indices.iter().flat_map(|index| index.to_le_bytes()).collect::<Vec<_>>();
+        let view = self.view(&bytes, Some(GL_ELEMENT_ARRAY_BUFFER));
+        self.accessors.push(json!({ "bufferView": view, "componentType": GL_UNSIGNED_INT, "count": indices.len(), "type": "SCALAR" }));
+        self.accessors.len() - 1
+    }
+}
+
+/// Writes a node and a mesh per chunk, the node placing the chunk in the world. Every texture is a
+/// material with the PNG embedded, vertex colors tint it as they do in the engine.
+pub fn write_glb(path: &Path, chunks: &[ExportedChunk]) -> anyhow::Result<ExportSummary> {
+    ensure!(!chunks.is_empty(), "there is nothing to export");
+
+    let mut buffer = GlbBuffer::default();
+    let mut materials = BTreeMap::new();
+    let (mut images, mut textures, mut material_list) = (Vec::new(), Vec::new(), Vec::new());
+    let (mut meshes, mut nodes) = (Vec::new(), Vec::new());
+    for chunk in chunks {
+        let mut primitives = Vec::new();
+        for primitive in &chunk.primitives {
+            let material = match materials.get(&primitive.texture_layer) {
+                Some(material) => *material,
+                None => {
+                    let name = texture_name(primitive.texture_layer);
+                    let image_path = textures_directory().join(format!("{}.png", name));
+                    let image = fs::read(&image_path).with_context(|| format!("failed to read {}", image_path.display()))?;
+                    let view = buffer.view(&image, None);
+                    images.push(json!({ "bufferView": view, "mimeType": "image/png" }));
+                    textures.push(json!({ "sampler": 0, "source": images.len() - 1 }));
+
+                    let alpha_mode = match texture_transparency(primitive.texture_layer) {
+                        Transparency::Opaque => "OPAQUE",
+                        Transparency::Cutout => "MASK",
+                        Transparency::Translucent => "BLEND",
+                    };
+                    material_list.push(json!({
+                        "name": name,
+                        "pbrMetallicRoughness": { "baseColorTexture": { "index": textures.len() - 1 }, "metallicFactor": 0.0, "roughnessFactor": 1.0 },
+                        "alphaMode": alpha_mode,
+                    }));
+                    materials.insert(primitive.texture_layer, material_list.len() - 1);
+                    material_list.len() - 1
+                }
+            };
+
+            let vertices = &primitive.vertices;
+            let position = buffer.floats(vertices.iter().map(|vertex| vertex.position), true);
+            let normal = buffer.floats(vertices.iter().map(|vertex| vertex.normal), false);
+            let color = buffer.floats(vertices.iter().map(|vertex| vertex.color), false);
+            let uv = buffer.floats(vertices.iter().map(|vertex| vertex.uv), false);
+            let indices = buffer.indices(&primitive.indices);
+            primitives.push(json!({
+                "attributes": { "POSITION": position, "NORMAL": normal, "COLOR_0": color, "TEXCOORD_0": uv },
+                "indices": indices,
+                "material": material,
+            }));
+        }
+
+        let name = format!("chunk_{}_{}_{}", chunk.position.x, chunk.position.y, chunk.position.z);
+        meshes.push(json!({ "name": name, "primitives": primitives }));
+        nodes.push(json!({ "name": name, "mesh": meshes.len() - 1, "translation": [chunk.offset.x, chunk.offset.y, chunk.offset.z] }));
+    }
+    buffer.bytes.resize(buffer.bytes.len().next_multiple_of(4), 0);
+
+    let document = json!({
+        "asset": { "version": "2.0", "generator": env!("CARGO_PKG_NAME") },
+        "scene": 0,
+        "scenes": [{ "nodes": (0..nodes.len()).collect::<Vec<_>>() }],
+        "nodes": nodes,
+        "meshes": meshes,
+        "materials": material_list,
+        "textures": textures,
+        "images": images,
+        "samplers": [{ "magFilter": GL_NEAREST, "minFilter": GL_NEAREST, "wrapS": GL_REPEAT, "wrapT": GL_REPEAT }],
+        "accessors": buffer.accessors,
+        "bufferViews": buffer.views,
+        "buffers": [{ "byteLength": buffer.bytes.len() }],
+    });
+    let mut json = serde_json::to_vec(&document)?;
+    json.resize(json.len().next_multiple_of(4), b' ');
+
+    // A 12 byte header, then the JSON and binary chunks, each with its length and type.
+    let total_len = 12 + 8 + json.len() + 8 + buffer.bytes.len();
+    let mut bytes = Vec::with_capacity(total_len);
+    for value in [GLB_MAGIC, GLB_VERSION, total_len as u32, json.len() as u32, GLB_JSON_CHUNK] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes.extend_from_slice(&json);
+    for value in [buffer.bytes.len() as u32, GLB_BIN_CHUNK] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes.extend_from_slice(&buffer.bytes);
+    fs::write(path, bytes).with_context(|| format!("failed to write {}", path.display()))?;
+
+    Ok(summarize(chunks))
+}
@@ -0,0 +1,98 @@
+use cgmath::Vector3;
+
+use crate::line::{self, LineVertex};
+use crate::render_utilities;
+use crate::texture;
+use crate::voxel_things::{quad::Quad, raycast::RaycastHit, vertex_desc::VertexDesc, voxel};
+
+const OUTLINE_COLOR: [f32; 4] = [0.05, 0.05, 0.05, 0.9];
+const FACE_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.25];
+/// The outline sits this much outside of the voxel on top of the pull towards the camera in `line.wgsl`.
+const INFLATE: f32 = 0.002;
+/// Keeps the highlighted face in front of the face it covers, it's made of triangles so the bias applies.
+const FACE_DEPTH_BIAS: wgpu::DepthBiasState = wgpu::DepthBiasState {
+    constant: -4,
+    slope_scale: -1.0,
+    clamp: 0.0,
+};
+
+const EDGE_VERTEX_COUNT: usize = 24;
+const FACE_VERTEX_COUNT: usize = 6;
+
+/// Wireframe cube around the targeted voxel with the hit face shaded.
+pub struct Outline {
+    line_pipeline: wgpu::RenderPipeline,
+    face_pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    visible: bool,
+}
+
+impl Outline {
+    pub fn new(device: &wgpu::Device, color_format: wgpu::TextureFormat, camera_bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+        let pipeline_layout = render_utilities::create_pipeline_layout(device, "outline_pipeline", &[camera_bind_group_layout]);
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("line.wgsl"));
+        let line_pipeline = render_utilities::create_line_pipeline(device, &pipeline_layout, color_format, Some(texture::Texture::DEPTH_FORMAT), &[LineVertex::desc()], shader, "outline line pipeline");
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("line.wgsl"));
+        let options = render_utilities::PipelineOptions {
+            blend: wgpu::BlendState::ALPHA_BLENDING,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            depth_bias: FACE_DEPTH_BIAS,
+            cull_mode: None,
+            ..render_utilities::PipelineOptions::new("outline face pipeline")
+        };
+        let face_pipeline = render_utilities::create_render_pipeline_with_options(device, &pipeline_layout, color_format, Some(texture::Texture::DEPTH_FORMAT), &[LineVertex::desc()], shader, options);
+
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Outline Vertex Buffer"),
+            size: (std::mem::size_of::<LineVertex>() * (EDGE_VERTEX_COUNT + FACE_VERTEX_COUNT)) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            line_pipeline,
+            face_pipeline,
+            vertex_buffer,
+            visible: false,
+        }
+    }
+
+    pub fn update(&mut self, queue: &wgpu::Queue, target: Option<&RaycastHit>) {
+        self.visible = target.is_some();
+        let Some(target) = target else {
+            return;
+        };
+
+        let min = target.position.cast::<f32>().unwrap() * voxel::SIZE - Vector3::new(INFLATE, INFLATE, INFLATE);
+        let max = min + Vector3::new(voxel::SIZE, voxel::SIZE, voxel::SIZE) + Vector3::new(INFLATE, INFLATE, INFLATE) * 2.0;
+        let mut vertices = line::box_edges(min, max, OUTLINE_COLOR).to_vec();
+
+        let center = (min + max) / 2.0;
+        let face = Quad::new(target.face, center, 0, [1.0; 3]);
+        let corners = face.corners.map(|corner| center + (corner - center) * (1.0 + INFLATE * 2.0));
+        for index in [0, 1, 2, 0, 2, 3] {
+            vertices.push(LineVertex::new(corners[index], FACE_COLOR));
+        }
+
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera_bind_group: &'a wgpu::BindGroup) {
+        if !self.visible {
+            return;
+        }
+
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+
+        render_pass.set_pipeline(&self.face_pipeline);
+        let faces_start = EDGE_VERTEX_COUNT as u32;
+        render_pass.draw(faces_start..faces_start + FACE_VERTEX_COUNT as u32, 0..1);
+
+        render_pass.set_pipeline(&self.line_pipeline);
+        render_pass.draw(0..EDGE_VERTEX_COUNT as u32, 0..1);
+    }
+}
@@ -1,8 +1,39 @@
 use wgpu::BindGroupLayout;
 
+pub struct PipelineOptions<'a> {
+    pub label: &'a str,
+    pub blend: wgpu::BlendState,
+    pub depth_write_enabled: bool,
+    pub depth_compare: wgpu::CompareFunction,
+    pub depth_bias: wgpu::DepthBiasState,
+    pub topology: wgpu::PrimitiveTopology,
+    pub cull_mode: Option<wgpu::Face>,
+    pub fragment_entry_point: &'a str,
+}
+
+impl<'a> PipelineOptions<'a> {
+    /// Opaque triangles with back face culling and depth writes, what `create_render_pipeline` uses.
+    pub fn new(label: &'a str) -> Self {
+        Self {
+            label,
+            blend: wgpu::BlendState::REPLACE,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            depth_bias: wgpu::DepthBiasState::default(),
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            cull_mode: Some(wgpu::Face::Back),
+            fragment_entry_point: "fs_main",
+        }
+    }
+}
+
 pub fn create_render_pipeline(device: &wgpu::Device, layout: &wgpu::PipelineLayout, color_format: wgpu::TextureFormat, depth_format: Option<wgpu::TextureFormat>, vertex_layouts: &[wgpu::VertexBufferLayout], shader_module: wgpu::ShaderModule, label: &str) -> wgpu::RenderPipeline {
+    create_render_pipeline_with_options(device, layout, color_format, depth_format, vertex_layouts, shader_module, PipelineOptions::new(label))
+}
+
+pub fn create_render_pipeline_with_options(device: &wgpu::Device, layout: &wgpu::PipelineLayout, color_format: wgpu::TextureFormat, depth_format: Option<wgpu::TextureFormat>, vertex_layouts: &[wgpu::VertexBufferLayout], shader_module: wgpu::ShaderModule, options: PipelineOptions) -> wgpu::RenderPipeline {
     device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: Some(label),
+        label: Some(options.label),
         layout: Some(layout),
         vertex: wgpu::VertexState {
             module: &shader_module,
@@ -11,28 +42,83 @@ pub fn create_render_pipeline(device: &wgpu::Device, layout: &wgpu::PipelineLayo
         },
         fragment: Some(wgpu::FragmentState {
             module: &shader_module,
-            entry_point: "fs_main",
+            entry_point: options.fragment_entry_point,
             targets: &[Some(wgpu::ColorTargetState {
                 format: color_format,
-                blend: Some(wgpu::BlendState::REPLACE),
+                blend: Some(options.blend),
                 write_mask: wgpu::ColorWrites::ALL,
             })],
         }),
         primitive: wgpu::PrimitiveState {
-            topology: wgpu::PrimitiveTopology::TriangleList,
+            topology: options.topology,
             strip_index_format: None,
             front_face: wgpu::FrontFace::Ccw,
             unclipped_depth: false,
-            cull_mode: Some(wgpu::Face::Back),
+            cull_mode: options.cull_mode,
             conservative: false,
             polygon_mode: wgpu::PolygonMode::Fill,
         },
         depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
             format,
+            depth_write_enabled: options.depth_write_enabled,
+            depth_compare: options.depth_compare,
+            stencil: wgpu::StencilState::default(),
+            bias: options.depth_bias,
+        }),
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+/// Alpha blended line list. Its shader has to pull the lines towards the camera itself, so they
+/// don't z-fight with the faces they are drawn on, depth bias doesn't apply to lines everywhere.
+pub fn create_line_pipeline(device: &wgpu::Device, layout: &wgpu::PipelineLayout, color_format: wgpu::TextureFormat, depth_format: Option<wgpu::TextureFormat>, vertex_layouts: &[wgpu::VertexBufferLayout], shader_module: wgpu::ShaderModule, label: &str) -> wgpu::RenderPipeline {
+    let options = PipelineOptions {
+        blend: wgpu::BlendState::ALPHA_BLENDING,
+        depth_write_enabled: false,
+        depth_compare: wgpu::CompareFunction::LessEqual,
+        topology: wgpu::PrimitiveTopology::LineList,
+        cull_mode: None,
+        ..PipelineOptions::new(label)
+    };
+    create_render_pipeline_with_options(device, layout, color_format, depth_format, vertex_layouts, shader_module, options)
+}
+
+/// Pipeline that only writes depth. Its `fs_main` has no color outputs and is there to discard
+/// alpha tested texels.
+pub fn create_depth_only_pipeline(device: &wgpu::Device, layout: &wgpu::PipelineLayout, depth_format: wgpu::TextureFormat, vertex_layouts: &[wgpu::VertexBufferLayout], shader_module: wgpu::ShaderModule, bias: wgpu::DepthBiasState, label: &str) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader_module,
+            entry_point: "vs_main",
+            buffers: vertex_layouts,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader_module,
+            entry_point: "fs_main",
+            targets: &[],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            unclipped_depth: false,
+            cull_mode: None,
+            conservative: false,
+            polygon_mode: wgpu::PolygonMode::Fill,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: depth_format,
             depth_write_enabled: true,
-            depth_compare: wgpu::CompareFunction::Less,
+            depth_compare: wgpu::CompareFunction::LessEqual,
             stencil: wgpu::StencilState::default(),
-            bias: wgpu::DepthBiasState::default(),
+            bias,
         }),
         multisample: wgpu::MultisampleState {
             count: 1,
@@ -0,0 +1,336 @@
+pub mod chunk_format;
+pub mod journal;
+pub mod region_file;
+pub mod region_storage;
+pub mod sqlite_storage;
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{ensure, Context};
+use cgmath::{Point3, Rad, Vector3};
+
+use crate::camera::Camera;
+use crate::voxel_things::{chunk::GeneratorConfig, world::World};
+use chunk_format::Compression;
+use region_storage::RegionStorage;
+use sqlite_storage::SqliteStorage;
+
+pub const DEFAULT_DIRECTORY: &str = "world";
+pub const DEFAULT_AUTOSAVE_INTERVAL: Duration = Duration::from_secs(300);
+/// Version of the metadata fields, worlds from before it was saved are version 1.
+pub const METADATA_VERSION: u32 = 3;
+
+/// Everything about a saved world besides its chunks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldMetadata {
+    pub seed: u32,
+    pub generator: GeneratorConfig,
+    pub camera_position: Point3<f32>,
+    pub camera_yaw: Rad<f32>,
+    pub camera_pitch: Rad<f32>,
+    /// Hours after midnight, see `light::sun_position`.
+    pub time_of_day: f32,
+    /// Used for the chunks the world saves.
+    pub compression: Compression,
+}
+
+impl WorldMetadata {
+    pub fn new(world: &World, camera: &Camera, time_of_day: f32, compression: Compression) -> Self {
+        Self {
+            seed: world.seed,
+            generator: world.generator,
+            camera_position: camera.position,
+            camera_yaw: camera.yaw(),
+            camera_pitch: camera.pitch(),
+            time_of_day,
+            compression,
+        }
+    }
+
+    pub fn camera(&self) -> Camera {
+        Camera::new(self.camera_position, self.camera_yaw, self.camera_pitch)
+    }
+
+    /// Every field as a name and a value, the storage decides how to keep them.
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let generator = &self.generator;
+        let position = self.camera_position;
+        vec![
+            ("format_version", METADATA_VERSION.to_string()),
+            ("seed", self.seed.to_string()),
+            ("noise_scale", generator.noise_scale.to_string()),
+            ("surface_level", generator.surface_level.to_string()),
+            ("surface_falloff", generator.surface_falloff.to_string()),
+            ("solid_threshold", generator.solid_threshold.to_string()),
+            ("dirt_depth", generator.dirt_depth.to_string()),
+            ("water_level", generator.water_level.to_string()),
+            ("camera_position", format!("{} {} {}", position.x, position.y, position.z)),
+            ("camera_yaw", self.camera_yaw.0.to_string()),
+            ("camera_pitch", self.camera_pitch.0.to_string()),
+            ("time_of_day", self.time_of_day.to_string()),
+            ("compression", self.compression.name().to_string()),
+        ]
+    }
+
+    fn from_fields(fields: &HashMap<String, String>) -> anyhow::Result<Self> {
+        let field = |name: &str| fields.get(name).map(|value| value.trim()).with_context(|| format!("missing {}", name));
+        fn parse<T: FromStr>(name: &str, value: &str) -> anyhow::Result<T> {
+            value.parse().ok().with_context(|| format!("invalid {} '{}'", name, value))
+        }
+        let number = |name: &str| field(name).and_then(|value| parse::<f64>(name, value));
+
+        // Version 3 added the compression, older worlds switch to the default one since every
+        // chunk records how it was compressed anyway.
+        let version = fields.get("format_version").map_or(Ok(1), |value| parse::<u32>("format_version", value))?;
+        ensure!(version <= METADATA_VERSION, "metadata version {} is newer than the supported version {}", version, METADATA_VERSION);
+
+        let position = field("camera_position")?.split_whitespace().map(|value| parse::<f32>("camera_position", value)).collect::<anyhow::Result<Vec<_>>>()?;
+        ensure!(position.len() == 3, "camera_position needs three coordinates");
+
+        Ok(Self {
+            seed: parse("seed", field("seed")?)?,
+            generator: GeneratorConfig {
+                noise_scale: number("noise_scale")?,
+                surface_level: number("surface_level")?,
+                surface_falloff: number("surface_falloff")?,
+                solid_threshold: number("solid_threshold")?,
+                dirt_depth: parse("dirt_depth", field("dirt_depth")?)?,
+                water_level: number("water_level")?,
+            },
+            camera_position: Point3::new(position[0], position[1], position[2]),
+            camera_yaw: Rad(parse("camera_yaw", field("camera_yaw")?)?),
+            camera_pitch: Rad(parse("camera_pitch", field("camera_pitch")?)?),
+            time_of_day: parse("time_of_day", field("time_of_day")?)?,
+            compression: match fields.get("compression") {
+                Some(name) => Compression::from_name(name.trim()).with_context(|| format!("unknown compression '{}'", name.trim()))?,
+                None => Compression::default(),
+            },
+        })
+    }
+}
+
+/// A world read by `WorldSave::load`.
+pub struct LoadedWorld {
+    pub world: World,
+    /// `None` if nothing was saved yet.
+    pub metadata: Option<WorldMetadata>,
+    /// Why chunks that couldn't be read were generated again instead.
+    pub warnings: Vec<String>,
+}
+
+/// What `WorldSave::save` wrote.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SaveSummary {
+    pub chunks: usize,
+}
+
+impl fmt::Display for SaveSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "saved {} chunks", self.chunks)
+    }
+}
+
+/// Decides when the world saves itself.
+#[derive(Debug, Clone, Copy)]
+pub struct Autosave {
+    /// `None` turns autosaving off.
+    pub interval: Option<Duration>,
+    elapsed: Duration,
+}
+
+impl Default for Autosave {
+    fn default() -> Self {
+        Self {
+            interval: Some(DEFAULT_AUTOSAVE_INTERVAL),
+            elapsed: Duration::ZERO,
+        }
+    }
+}
+
+impl Autosave {
+    /// Advances the timer by a frame, returns true when it is time to save.
+    pub fn tick(&mut self, dt: Duration) -> bool {
+        let Some(interval) = self.interval else {
+            return false;
+        };
+
+        self.elapsed += dt;
+        if self.elapsed < interval {
+            return false;
+        }
+        self.elapsed = Duration::ZERO;
+        true
+    }
+
+    /// Starts the interval over, e.g. after saving by hand.
+    pub fn reset(&mut self) {
+        self.elapsed = Duration::ZERO;
+    }
+}
+
+/// Saved chunks returned by `WorldStorage::read_chunks`.
+#[derive(Debug, Default)]
+pub struct ChunkReads {
+    /// Encoded chunks by position, see `chunk_format`. Positions that were never saved are missing.
+    pub chunks: HashMap<Vector3<i32>, Vec<u8>>,
+    /// Positions whose saved data can't be read.
+    pub damaged: HashSet<Vector3<i32>>,
+    /// What went wrong, once per problem rather than once per damaged chunk.
+    pub warnings: Vec<String>,
+}
+
+/// Where a saved world's metadata and chunks are kept. Storages only move fields and encoded
+/// chunks around, `WorldSave` does the encoding.
+pub trait WorldStorage {
+    /// Cleans up after an interrupted save and describes what it did. Runs before anything else.
+    fn recover(&mut self) -> anyhow::Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    /// Whether the directory holds a save of this kind.
+    fn exists(&self) -> bool;
+
+    /// The metadata fields by name, `None` if nothing was saved yet.
+    fn read_metadata(&mut self) -> anyhow::Result<Option<HashMap<String, String>>>;
+
+    fn read_chunks(&mut self, positions: &[Vector3<i32>]) -> anyhow::Result<ChunkReads>;
+
+    /// Replaces the metadata and stores the chunks over any saved at the same positions. A crash
+    /// while writing must leave either the old save or the new one.
+    fn write(&mut self, metadata: &[(&'static str, String)], chunks: &[(Vector3<i32>, Vec<u8>)]) -> anyhow::Result<()>;
+}
+
+/// The kinds of `WorldStorage`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StorageBackend {
+    /// See `RegionStorage`.
+    #[default]
+    RegionFiles,
+    /// See `SqliteStorage`.
+    Sqlite,
+}
+
+impl StorageBackend {
+    pub const ALL: [StorageBackend; 2] = [StorageBackend::RegionFiles, StorageBackend::Sqlite];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            StorageBackend::RegionFiles => "regions",
+            StorageBackend::Sqlite => "sqlite",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<StorageBackend> {
+        Self::ALL.into_iter().find(|backend| backend.name() == name)
+    }
+
+    /// The storage in `directory`, which is only created once something is written.
+    pub fn open(&self, directory: &Path) -> Box<dyn WorldStorage> {
+        match self {
+            StorageBackend::RegionFiles => Box::new(RegionStorage::new(directory)),
+            StorageBackend::Sqlite => Box::new(SqliteStorage::new(directory)),
+        }
+    }
+}
+
+/// A world saved to a directory by one of the `StorageBackend`s. Only chunks that were edited are
+/// stored, the rest are generated again from the seed when loading.
+#[derive(Debug, Clone)]
+pub struct WorldSave {
+    pub directory: PathBuf,
+    pub backend: StorageBackend,
+    /// What the next save compresses chunks with, the metadata remembers it for the next load.
+    pub compression: Compression,
+    /// The save couldn't be loaded, so only an explicit `save` may write over it.
+    pub load_failed: bool,
+}
+
+impl Default for WorldSave {
+    fn default() -> Self {
+        Self::new(DEFAULT_DIRECTORY, StorageBackend::default())
+    }
+}
+
+impl WorldSave {
+    pub fn new(directory: impl Into<PathBuf>, backend: StorageBackend) -> Self {
+        Self {
+            directory: directory.into(),
+            backend,
+            compression: Compression::default(),
+            load_failed: false,
+        }
+    }
+
+    /// Loads the saved world, or starts a new one with `seed` if nothing is saved. Chunks at
+    /// `positions` load from the storage when they were saved and are generated otherwise.
+    ///
+    /// Only unreadable metadata fails the load. Damaged chunks are generated again and reported in
+    /// `LoadedWorld::warnings`. They count as modified, so the next save replaces them.
+    pub fn load(&self, seed: u32, positions: impl IntoIterator<Item = Vector3<i32>>) -> anyhow::Result<LoadedWorld> {
+        self.check_backend()?;
+        let mut storage = self.backend.open(&self.directory);
+        let mut warnings = storage.recover()?;
+
+        let metadata = match storage.read_metadata()? {
+            Some(fields) => Some(WorldMetadata::from_fields(&fields).with_context(|| format!("the metadata in {} is invalid", self.directory.display()))?),
+            None => None,
+        };
+        let mut world = World::new(metadata.map_or(seed, |metadata| metadata.seed));
+        if let Some(metadata) = &metadata {
+            world.generator = metadata.generator;
+        }
+
+        let positions = positions.into_iter().collect::<Vec<_>>();
+        let reads = storage.read_chunks(&positions)?;
+        warnings.extend(reads.warnings);
+        for position in positions {
+            if reads.damaged.contains(&position) {
+                world.generate_chunk(position);
+                world.modified_chunks.insert(position);
+                continue;
+            }
+
+            match reads.chunks.get(&position).map(|data| chunk_format::decode(position, data)) {
+                Some(Ok(chunk)) => {
+                    world.chunks.insert(position, chunk);
+                }
+                None => {
+                    world.generate_chunk(position);
+                }
+                Some(Err(error)) => {
+                    warnings.push(format!("chunk {} {} {} is corrupt and was generated again: {:#}", position.x, position.y, position.z, error));
+                    world.generate_chunk(position);
+                    world.modified_chunks.insert(position);
+                }
+            }
+        }
+        Ok(LoadedWorld { world, metadata, warnings })
+    }
+
+    /// Fails if the directory holds a save of another backend, which would otherwise be ignored
+    /// when loading and end up next to a second world when saving.
+    fn check_backend(&self) -> anyhow::Result<()> {
+        for backend in StorageBackend::ALL.into_iter().filter(|backend| *backend != self.backend) {
+            ensure!(!backend.open(&self.directory).exists(), "{} holds a '{}' save, start with --storage {} to use it", self.directory.display(), backend.name(), backend.name());
+        }
+        Ok(())
+    }
+
+    /// Writes the metadata and every chunk modified since the last save.
+    pub fn save(&self, world: &mut World, metadata: &WorldMetadata) -> anyhow::Result<SaveSummary> {
+        self.check_backend()?;
+        let mut storage = self.backend.open(&self.directory);
+        for warning in storage.recover()? {
+            log::warn!("{}", warning);
+        }
+
+        let chunks = world.modified_chunks.iter().filter_map(|position| world.chunks.get(position).map(|chunk| (*position, chunk_format::encode(chunk, metadata.compression)))).collect::<Vec<_>>();
+        storage.write(&metadata.fields(), &chunks)?;
+        world.modified_chunks.clear();
+        Ok(SaveSummary { chunks: chunks.len() })
+    }
+}
@@ -0,0 +1,318 @@
+use std::io::{Read, Write};
+
+use anyhow::{bail, ensure, Context};
+use cgmath::Vector3;
+
+use crate::voxel_things::{
+    chunk::{Chunk, CHUNK_SIZE},
+    voxel::{BlockType, Voxel},
+};
+
+/// Version of the chunk encoding `encode` writes.
+pub const VERSION: u16 = 3;
+/// Version and checksum in front of every encoded chunk.
+const HEADER_LEN: usize = 6;
+const VOXEL_COUNT: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+/// Largest possible palette and runs, decompression stops there so damaged data can't expand
+/// without bounds.
+const MAX_BODY_LEN: usize = 1 + 255 * 256 + VOXEL_COUNT * 3;
+/// Version 1 chunks stored `BlockType as u8` for every voxel, this is what those ids meant. They
+/// change whenever a block is added in the middle of `BlockType`, so newer versions store names.
+const V1_BLOCK_NAMES: [&str; 7] = ["empty", "grass", "dirt", "stone", "water", "glass", "leaves"];
+
+/// General purpose compression applied after the run-length encoding. Every chunk records its
+/// own, so changing it only affects chunks written afterwards.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    /// Smaller than LZ4 but slower.
+    Deflate,
+    #[default]
+    Lz4,
+}
+
+impl Compression {
+    pub const ALL: [Compression; 3] = [Compression::None, Compression::Deflate, Compression::Lz4];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            Compression::Deflate => "deflate",
+            Compression::Lz4 => "lz4",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Compression> {
+        Self::ALL.into_iter().find(|compression| compression.name() == name)
+    }
+
+    fn id(&self) -> u8 {
+        *self as u8
+    }
+
+    fn from_id(id: u8) -> Option<Compression> {
+        Self::ALL.get(id as usize).copied()
+    }
+}
+
+/// Encodes the voxels of a chunk. Its position isn't included, the region file or whatever else
+/// stores the bytes keeps track of it.
+///
+/// Every chunk starts with a little endian u16 version and the CRC32 of the rest as a u32. In
+/// version 3 a u8 `Compression` id follows, then the body, compressed as that says. The body is a
+/// palette, a u8 count of block names each prefixed by a u8 length, then runs of voxels in x, y, z
+/// order as a u16 length and a u8 palette index.
+pub fn encode(chunk: &Chunk, compression: Compression) -> Vec<u8> {
+    let (mut body, indices) = encode_palette(chunk.voxels.iter().flatten().flatten());
+    body.extend_from_slice(&run_length_encode(&indices));
+
+    let mut payload = vec![compression.id()];
+    match compression {
+        Compression::None => payload.extend_from_slice(&body),
+        Compression::Deflate => {
+            let mut encoder = flate2::write::DeflateEncoder::new(payload, flate2::Compression::default());
+            // Writing to a Vec can't fail.
+            encoder.write_all(&body).unwrap();
+            payload = encoder.finish().unwrap();
+        }
+        Compression::Lz4 => payload.extend_from_slice(&lz4_flex::compress_prepend_size(&body)),
+    }
+    with_header(VERSION, &payload)
+}
+
+/// Prefixes a payload of the given version with the chunk header.
+pub fn with_header(version: u16, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(&version.to_le_bytes());
+    bytes.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+    bytes.extend_from_slice(payload);
+    bytes
+}
+
+/// Decodes a chunk of any known version, checking its checksum and migrating older encodings.
+pub fn decode(position: Vector3<i32>, data: &[u8]) -> anyhow::Result<Chunk> {
+    ensure!(data.len() >= HEADER_LEN, "chunk is too short for its header");
+    let version = u16::from_le_bytes([data[0], data[1]]);
+    let checksum = u32::from_le_bytes(data[2..HEADER_LEN].try_into()?);
+    let payload = &data[HEADER_LEN..];
+    ensure!(crc32fast::hash(payload) == checksum, "checksum mismatch");
+
+    let payload = migrate(version, payload)?;
+    decode_payload(position, &payload)
+}
+
+/// The palette and the palette index of every voxel. Blueprints store their blocks the same way.
+pub fn encode_palette<'a>(voxels: impl IntoIterator<Item = &'a Voxel>) -> (Vec<u8>, Vec<u8>) {
+    let mut palette = Vec::<BlockType>::new();
+    let mut indices = Vec::with_capacity(VOXEL_COUNT);
+    for voxel in voxels {
+        let index = match palette.iter().position(|block_type| *block_type == voxel.block_type) {
+            Some(index) => index,
+            None => {
+                palette.push(voxel.block_type);
+                palette.len() - 1
+            }
+        };
+        indices.push(index as u8);
+    }
+
+    let mut bytes = vec![palette.len() as u8];
+    for block_type in palette {
+        let name = block_type.definition().name;
+        bytes.push(name.len() as u8);
+        bytes.extend_from_slice(name.as_bytes());
+    }
+    (bytes, indices)
+}
+
+/// Reads a palette off the front of `bytes`, returns it and what follows.
+pub fn decode_palette(bytes: &[u8]) -> anyhow::Result<(Vec<BlockType>, &[u8])> {
+    let (&palette_len, mut rest) = bytes.split_first().context("missing palette")?;
+    let mut palette = Vec::with_capacity(palette_len as usize);
+    for _ in 0..palette_len {
+        let (&name_len, tail) = rest.split_first().context("palette ends early")?;
+        ensure!(tail.len() >= name_len as usize, "palette ends early");
+        let (name, tail) = tail.split_at(name_len as usize);
+        let name = std::str::from_utf8(name).context("block name is not UTF-8")?;
+        palette.push(BlockType::from_name(name).with_context(|| format!("unknown block '{}'", name))?);
+        rest = tail;
+    }
+    Ok((palette, rest))
+}
+
+fn run_length_encode(indices: &[u8]) -> Vec<u8> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    while start < indices.len() {
+        let index = indices[start];
+        let len = indices[start..].iter().take(u16::MAX as usize).take_while(|other| **other == index).count();
+        runs.extend_from_slice(&(len as u16).to_le_bytes());
+        runs.push(index);
+        start += len;
+    }
+    runs
+}
+
+fn run_length_decode(runs: &[u8]) -> anyhow::Result<Vec<u8>> {
+    ensure!(runs.len().is_multiple_of(3), "runs end early");
+
+    let mut indices = Vec::with_capacity(VOXEL_COUNT);
+    for run in runs.chunks_exact(3) {
+        let len = u16::from_le_bytes([run[0], run[1]]) as usize;
+        ensure!(len > 0 && indices.len() + len <= VOXEL_COUNT, "runs don't add up to {} voxels", VOXEL_COUNT);
+        indices.resize(indices.len() + len, run[2]);
+    }
+    ensure!(indices.len() == VOXEL_COUNT, "expected {} voxels, got {}", VOXEL_COUNT, indices.len());
+    Ok(indices)
+}
+
+/// Upgrades a payload one version at a time until it is at `VERSION`.
+fn migrate(mut version: u16, payload: &[u8]) -> anyhow::Result<Vec<u8>> {
+    ensure!(version <= VERSION, "chunk version {} is newer than the supported version {}", version, VERSION);
+
+    let mut payload = payload.to_vec();
+    while version < VERSION {
+        payload = match version {
+            1 => migrate_v1(&payload)?,
+            2 => migrate_v2(&payload)?,
+            _ => bail!("no migration from chunk version {}", version),
+        };
+        version += 1;
+    }
+    Ok(payload)
+}
+
+/// Raw block ids to a palette of the names they stood for.
+fn migrate_v1(payload: &[u8]) -> anyhow::Result<Vec<u8>> {
+    ensure!(payload.len() == VOXEL_COUNT, "expected {} voxels, got {}", VOXEL_COUNT, payload.len());
+
+    let mut migrated = Vec::with_capacity(VOXEL_COUNT + 64);
+    migrated.push(V1_BLOCK_NAMES.len() as u8);
+    for name in V1_BLOCK_NAMES {
+        migrated.push(name.len() as u8);
+        migrated.extend_from_slice(name.as_bytes());
+    }
+    for id in payload {
+        ensure!((*id as usize) < V1_BLOCK_NAMES.len(), "unknown version 1 block id {}", id);
+        migrated.push(*id);
+    }
+    Ok(migrated)
+}
+
+/// A palette index per voxel to runs of them, left uncompressed.
+fn migrate_v2(payload: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let (_, indices) = decode_palette(payload)?;
+    ensure!(indices.len() == VOXEL_COUNT, "expected {} voxels, got {}", VOXEL_COUNT, indices.len());
+
+    let mut migrated = vec![Compression::None.id()];
+    migrated.extend_from_slice(&payload[..payload.len() - indices.len()]);
+    migrated.extend_from_slice(&run_length_encode(indices));
+    Ok(migrated)
+}
+
+fn decode_payload(position: Vector3<i32>, payload: &[u8]) -> anyhow::Result<Chunk> {
+    let (&compression, body) = payload.split_first().context("missing compression")?;
+    let compression = Compression::from_id(compression).with_context(|| format!("unknown compression {}", compression))?;
+    let body = match compression {
+        Compression::None => body.to_vec(),
+        Compression::Deflate => {
+            let mut decompressed = Vec::new();
+            flate2::read::DeflateDecoder::new(body).take(MAX_BODY_LEN as u64 + 1).read_to_end(&mut decompressed).context("failed to decompress")?;
+            ensure!(decompressed.len() <= MAX_BODY_LEN, "decompresses to more than a chunk can hold");
+            decompressed
+        }
+        Compression::Lz4 => {
+            // The decompressed size comes first, checked before it is used to allocate.
+            let size = body.get(..4).context("missing decompressed size")?;
+            ensure!(u32::from_le_bytes(size.try_into()?) as usize <= MAX_BODY_LEN, "decompresses to more than a chunk can hold");
+            lz4_flex::decompress_size_prepended(body).context("failed to decompress")?
+        }
+    };
+
+    let (palette, runs) = decode_palette(&body)?;
+    let indices = run_length_decode(runs)?;
+    let mut chunk = Chunk::new(position);
+    for (voxel, index) in chunk.voxels.iter_mut().flatten().flatten().zip(indices) {
+        *voxel = Voxel::new(*palette.get(index as usize).with_context(|| format!("palette index {} out of range", index))?);
+    }
+    Ok(chunk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel_things::chunk::GeneratorConfig;
+
+    /// Version 1 ids cycling through every block that existed then.
+    fn v1_payload() -> Vec<u8> {
+        (0..VOXEL_COUNT).map(|index| (index % V1_BLOCK_NAMES.len()) as u8).collect()
+    }
+
+    fn block_types(chunk: &Chunk) -> Vec<BlockType> {
+        chunk.voxels.iter().flatten().flatten().map(|voxel| voxel.block_type).collect()
+    }
+
+    #[test]
+    fn migrates_version_1_chunks() {
+        let chunk = decode(Vector3::new(0, 0, 0), &with_header(1, &v1_payload())).unwrap();
+        let expected = v1_payload().iter().map(|id| BlockType::from_name(V1_BLOCK_NAMES[*id as usize]).unwrap()).collect::<Vec<_>>();
+        assert_eq!(block_types(&chunk), expected);
+    }
+
+    fn generated_chunk() -> Chunk {
+        let mut chunk = Chunk::new(Vector3::new(1, 0, 2));
+        chunk.build_voxels(7, &GeneratorConfig::default());
+        chunk
+    }
+
+    /// A chunk header and compression id in front of `body`.
+    fn with_body(compression: Compression, body: &[u8]) -> Vec<u8> {
+        let mut payload = vec![compression.id()];
+        payload.extend_from_slice(body);
+        with_header(VERSION, &payload)
+    }
+
+    #[test]
+    fn round_trips_every_compression() {
+        let chunk = generated_chunk();
+        for compression in Compression::ALL {
+            let decoded = decode(chunk.position, &encode(&chunk, compression)).unwrap();
+            assert_eq!(block_types(&decoded), block_types(&chunk), "{}", compression.name());
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_lz4_sizes() {
+        let mut body = ((MAX_BODY_LEN + 1) as u32).to_le_bytes().to_vec();
+        body.extend_from_slice(&[0; 16]);
+        let error = decode(Vector3::new(0, 0, 0), &with_body(Compression::Lz4, &body)).err().expect("decoded an oversized body");
+        assert!(error.to_string().contains("more than a chunk can hold"), "{}", error);
+    }
+
+    #[test]
+    fn stops_deflate_bombs() {
+        let mut encoder = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(&vec![0; MAX_BODY_LEN * 4]).unwrap();
+        let body = encoder.finish().unwrap();
+        let error = decode(Vector3::new(0, 0, 0), &with_body(Compression::Deflate, &body)).err().expect("decoded an oversized body");
+        assert!(error.to_string().contains("more than a chunk can hold"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_runs_that_dont_fill_a_chunk() {
+        let run = |len: u16| [len.to_le_bytes()[0], len.to_le_bytes()[1], 0];
+        assert_eq!(run_length_decode(&[run(4000), run(96)].concat()).unwrap().len(), VOXEL_COUNT);
+        assert!(run_length_decode(&run(4095)).is_err());
+        assert!(run_length_decode(&[run(4000), run(97)].concat()).is_err());
+        assert!(run_length_decode(&[run(4096), run(0)].concat()).is_err());
+        assert!(run_length_decode(&run(4096)[..2]).is_err());
+    }
+
+    #[test]
+    fn rejects_damaged_chunks() {
+        let mut bytes = with_header(1, &v1_payload());
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert!(decode(Vector3::new(0, 0, 0), &bytes).is_err());
+    }
+}
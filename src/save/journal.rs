@@ -0,0 +1,119 @@
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+const JOURNAL_FILE: &str = "save.journal";
+const TEMP_SUFFIX: &str = ".tmp";
+
+/// Makes a save that writes several files all or nothing.
+///
+/// New contents are staged in temporary files next to the files they replace. Committing lists
+/// them in the journal and only then renames them over the old files, so a crash before the journal
+/// is written leaves the old save untouched and a crash after it leaves everything needed to
+/// finish the save. `recover` sorts out either case before the next load.
+pub struct Journal {
+    directory: PathBuf,
+    staged: Vec<PathBuf>,
+}
+
+/// What `Journal::recover` found left behind by an interrupted save.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Recovery {
+    /// Files a committed save hadn't moved into place yet.
+    pub completed: usize,
+    /// Temporary files of a save that crashed before committing, deleted.
+    pub discarded: usize,
+}
+
+impl Journal {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            staged: Vec::new(),
+        }
+    }
+
+    /// Writes `bytes` to a temporary file next to `path`, which stays untouched until `commit`.
+    pub fn stage(&mut self, path: &Path, bytes: &[u8]) -> anyhow::Result<()> {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).with_context(|| format!("failed to create {}", directory.display()))?;
+        }
+        write_synced(&temp_path(path), bytes)?;
+        self.staged.push(path.to_path_buf());
+        Ok(())
+    }
+
+    /// Moves every staged file into place, see `Journal`.
+    pub fn commit(self) -> anyhow::Result<()> {
+        if self.staged.is_empty() {
+            return Ok(());
+        }
+
+        // The journal itself is staged and renamed, so it is either complete or missing.
+        let journal_path = self.directory.join(JOURNAL_FILE);
+        let listing = self.staged.iter().map(|path| format!("{}\n", path.strip_prefix(&self.directory).unwrap_or(path).display())).collect::<String>();
+        write_synced(&temp_path(&journal_path), listing.as_bytes())?;
+        fs::rename(temp_path(&journal_path), &journal_path).with_context(|| format!("failed to write {}", journal_path.display()))?;
+
+        for path in &self.staged {
+            fs::rename(temp_path(path), path).with_context(|| format!("failed to replace {}", path.display()))?;
+        }
+        fs::remove_file(&journal_path).with_context(|| format!("failed to remove {}", journal_path.display()))
+    }
+
+    /// Finishes a save that crashed after committing and deletes the temporary files of one that
+    /// crashed before. Has to run before anything in `directory` is read.
+    pub fn recover(directory: &Path) -> anyhow::Result<Recovery> {
+        let mut recovery = Recovery::default();
+        let journal_path = directory.join(JOURNAL_FILE);
+        if journal_path.exists() {
+            let listing = fs::read_to_string(&journal_path).with_context(|| format!("failed to read {}", journal_path.display()))?;
+            for line in listing.lines().filter(|line| !line.is_empty()) {
+                let path = directory.join(line);
+                if temp_path(&path).exists() {
+                    fs::rename(temp_path(&path), &path).with_context(|| format!("failed to replace {}", path.display()))?;
+                    recovery.completed += 1;
+                }
+            }
+            fs::remove_file(&journal_path).with_context(|| format!("failed to remove {}", journal_path.display()))?;
+        }
+
+        recovery.discarded = remove_temp_files(directory)?;
+        Ok(recovery)
+    }
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(TEMP_SUFFIX);
+    name.into()
+}
+
+/// Writes a file and waits until it is on disk, so a rename after it can't expose a partial file.
+fn write_synced(path: &Path, bytes: &[u8]) -> anyhow::Result<()> {
+    let mut file = File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+    file.write_all(bytes).with_context(|| format!("failed to write {}", path.display()))?;
+    file.sync_all().with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Deletes every temporary file under `directory`, returns how many there were.
+fn remove_temp_files(directory: &Path) -> anyhow::Result<usize> {
+    if !directory.is_dir() {
+        return Ok(0);
+    }
+
+    let mut removed = 0;
+    for entry in fs::read_dir(directory).with_context(|| format!("failed to list {}", directory.display()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            removed += remove_temp_files(&path)?;
+        } else if path.to_string_lossy().ends_with(TEMP_SUFFIX) {
+            fs::remove_file(&path).with_context(|| format!("failed to remove {}", path.display()))?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
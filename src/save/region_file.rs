@@ -0,0 +1,265 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, ensure, Context};
+use cgmath::Vector3;
+
+use super::chunk_format;
+
+/// Chunks along x and z in a region file. Regions are one chunk tall, so every chunk layer gets
+/// its own files.
+pub const REGION_SIZE: i32 = 32;
+/// Version of the region layout `encode` writes.
+pub const VERSION: u16 = 2;
+const TABLE_LEN: usize = (REGION_SIZE * REGION_SIZE) as usize;
+const MAGIC: &[u8; 4] = b"VXRG";
+/// Offset, length and checksum of a chunk.
+const ENTRY_LEN: usize = 12;
+/// Magic, version and the table's checksum.
+const HEADER_LEN: usize = MAGIC.len() + 2 + 4;
+const TABLE_END: usize = HEADER_LEN + TABLE_LEN * ENTRY_LEN;
+/// Version 1 files had no version or checksums, only an offset and a length per chunk after the
+/// magic. Their first offset is either 0 or right after their table, neither of which is a valid
+/// version, which is how they're told apart.
+const V1_TABLE_END: usize = MAGIC.len() + TABLE_LEN * 8;
+
+/// Position of the region a chunk belongs to.
+pub fn region_position(chunk_position: Vector3<i32>) -> Vector3<i32> {
+    Vector3::new(chunk_position.x.div_euclid(REGION_SIZE), chunk_position.y, chunk_position.z.div_euclid(REGION_SIZE))
+}
+
+fn table_index(chunk_position: Vector3<i32>) -> usize {
+    (chunk_position.x.rem_euclid(REGION_SIZE) * REGION_SIZE + chunk_position.z.rem_euclid(REGION_SIZE)) as usize
+}
+
+#[derive(Debug, Clone)]
+enum Entry {
+    Empty,
+    Chunk(Vec<u8>),
+    /// Kept so loading can report why the chunk was generated again. The data and checksum are
+    /// written back unchanged, so saving doesn't erase a chunk nobody loaded.
+    Corrupt { reason: String, data: Option<(Vec<u8>, u32)> },
+}
+
+/// The encoded chunks of one region.
+///
+/// On disk it starts with `VXRG`, a little endian u16 version and the CRC32 of the offset table
+/// as a u32. The table follows with a u32 offset from the start of the file, a u32 length and the
+/// CRC32 of the data for every chunk, x changing slowest. Chunks that were never saved have a
+/// length of 0. The chunk data, see `chunk_format`, follows the table.
+#[derive(Debug, Clone)]
+pub struct RegionFile {
+    entries: Vec<Entry>,
+}
+
+impl Default for RegionFile {
+    fn default() -> Self {
+        Self { entries: vec![Entry::Empty; TABLE_LEN] }
+    }
+}
+
+impl RegionFile {
+    /// Reads a region file, a file that doesn't exist yet reads as an empty region.
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let bytes = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        Self::decode(&bytes).with_context(|| format!("{} is not a valid region file", path.display()))
+    }
+
+    /// Fails if the header or the table is damaged. Damaged chunks only fail `get`, so the rest of
+    /// the region still loads.
+    pub fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+        ensure!(bytes.len() >= HEADER_LEN && &bytes[..MAGIC.len()] == MAGIC, "missing region header");
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version == 0 || version as usize == V1_TABLE_END {
+            return Self::decode_v1(bytes);
+        }
+        ensure!(version <= VERSION, "region version {} is newer than the supported version {}", version, VERSION);
+        ensure!(bytes.len() >= TABLE_END, "region table ends early");
+
+        let checksum = u32::from_le_bytes(bytes[6..HEADER_LEN].try_into()?);
+        let table = &bytes[HEADER_LEN..TABLE_END];
+        ensure!(crc32fast::hash(table) == checksum, "region table checksum mismatch");
+
+        let mut region = Self::default();
+        for (entry, fields) in region.entries.iter_mut().zip(table.chunks_exact(ENTRY_LEN)) {
+            let offset = u32::from_le_bytes(fields[..4].try_into()?) as usize;
+            let len = u32::from_le_bytes(fields[4..8].try_into()?) as usize;
+            let checksum = u32::from_le_bytes(fields[8..].try_into()?);
+            if len == 0 {
+                continue;
+            }
+
+            *entry = match offset.checked_add(len).and_then(|end| bytes.get(offset..end)) {
+                Some(data) if crc32fast::hash(data) == checksum => Entry::Chunk(data.to_vec()),
+                Some(data) => Entry::Corrupt {
+                    reason: "checksum mismatch".to_string(),
+                    data: Some((data.to_vec(), checksum)),
+                },
+                None => Entry::Corrupt {
+                    reason: "data is outside of the region file".to_string(),
+                    data: None,
+                },
+            };
+        }
+        Ok(region)
+    }
+
+    /// Version 1 chunks were raw voxels, they get a version 1 chunk header so `chunk_format` can
+    /// migrate them.
+    fn decode_v1(bytes: &[u8]) -> anyhow::Result<Self> {
+        ensure!(bytes.len() >= V1_TABLE_END, "region table ends early");
+
+        let mut region = Self::default();
+        for (entry, fields) in region.entries.iter_mut().zip(bytes[MAGIC.len()..V1_TABLE_END].chunks_exact(8)) {
+            let offset = u32::from_le_bytes(fields[..4].try_into()?) as usize;
+            let len = u32::from_le_bytes(fields[4..].try_into()?) as usize;
+            if len == 0 {
+                continue;
+            }
+
+            *entry = match offset.checked_add(len).and_then(|end| bytes.get(offset..end)) {
+                Some(data) => Entry::Chunk(chunk_format::with_header(1, data)),
+                None => Entry::Corrupt {
+                    reason: "data is outside of the region file".to_string(),
+                    data: None,
+                },
+            };
+        }
+        Ok(region)
+    }
+
+    /// Always writes the current version. Corrupt chunks keep their damaged data and checksum,
+    /// unless their data was missing.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut table = Vec::with_capacity(TABLE_LEN * ENTRY_LEN);
+        let mut data = Vec::new();
+        for entry in &self.entries {
+            let (bytes, checksum) = match entry {
+                Entry::Chunk(bytes) => (bytes, crc32fast::hash(bytes)),
+                Entry::Corrupt { data: Some((bytes, checksum)), .. } => (bytes, *checksum),
+                Entry::Empty | Entry::Corrupt { data: None, .. } => {
+                    table.extend_from_slice(&[0; ENTRY_LEN]);
+                    continue;
+                }
+            };
+            table.extend_from_slice(&((TABLE_END + data.len()) as u32).to_le_bytes());
+            table.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            table.extend_from_slice(&checksum.to_le_bytes());
+            data.extend_from_slice(bytes);
+        }
+
+        let mut bytes = Vec::with_capacity(TABLE_END + data.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&crc32fast::hash(&table).to_le_bytes());
+        bytes.extend_from_slice(&table);
+        bytes.extend_from_slice(&data);
+        bytes
+    }
+
+    /// Encoded chunk at a world chunk position inside this region, `None` if it wasn't saved and
+    /// an error if its data is damaged.
+    pub fn get(&self, chunk_position: Vector3<i32>) -> anyhow::Result<Option<&[u8]>> {
+        match &self.entries[table_index(chunk_position)] {
+            Entry::Empty => Ok(None),
+            Entry::Chunk(data) => Ok(Some(data)),
+            Entry::Corrupt { reason, .. } => bail!("{}", reason),
+        }
+    }
+
+    pub fn insert(&mut self, chunk_position: Vector3<i32>, data: Vec<u8>) {
+        self.entries[table_index(chunk_position)] = Entry::Chunk(data);
+    }
+
+    /// Number of chunks stored in the region.
+    pub fn chunk_count(&self) -> usize {
+        self.entries.iter().filter(|entry| matches!(entry, Entry::Chunk(_))).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel_things::{
+        chunk::{Chunk, CHUNK_SIZE},
+        voxel::{BlockType, Voxel},
+    };
+
+    fn chunk_with(position: Vector3<i32>, block_type: BlockType) -> Chunk {
+        let mut chunk = Chunk::new(position);
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                chunk.voxels[x][0][z] = Voxel::new(block_type);
+            }
+        }
+        chunk
+    }
+
+    fn decode_chunk(region: &RegionFile, position: Vector3<i32>) -> anyhow::Result<Chunk> {
+        chunk_format::decode(position, region.get(position)?.context("chunk is missing")?)
+    }
+
+    /// A version 1 file holding raw stone voxels at the first table entry and raw grass at `second`.
+    fn v1_region(second: Vector3<i32>) -> Vec<u8> {
+        let voxel_count = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+        let mut table = vec![0; TABLE_LEN * 8];
+        let mut data = Vec::new();
+        for (position, id) in [(Vector3::new(0, 0, 0), 3), (second, 1)] {
+            let index = table_index(position) * 8;
+            table[index..index + 4].copy_from_slice(&((V1_TABLE_END + data.len()) as u32).to_le_bytes());
+            table[index + 4..index + 8].copy_from_slice(&(voxel_count as u32).to_le_bytes());
+            data.extend((0..voxel_count).map(|index| if index % CHUNK_SIZE == 0 { id } else { 0 }));
+        }
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&table);
+        bytes.extend_from_slice(&data);
+        bytes
+    }
+
+    #[test]
+    fn reads_version_1_files() {
+        let second = Vector3::new(3, 0, 5);
+        let region = RegionFile::decode(&v1_region(second)).unwrap();
+        assert_eq!(region.chunk_count(), 2);
+
+        for (position, block_type) in [(Vector3::new(0, 0, 0), BlockType::Stone), (second, BlockType::Grass)] {
+            let chunk = decode_chunk(&region, position).unwrap();
+            for (index, voxel) in chunk.voxels.iter().flatten().flatten().enumerate() {
+                let expected = if index % CHUNK_SIZE == 0 { block_type } else { BlockType::Empty };
+                assert_eq!(voxel.block_type, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn damaged_chunks_only_fail_themselves() {
+        let (first, second) = (Vector3::new(0, 0, 0), Vector3::new(1, 0, 2));
+        let mut region = RegionFile::default();
+        region.insert(first, chunk_format::encode(&chunk_with(first, BlockType::Stone), chunk_format::Compression::Lz4));
+        region.insert(second, chunk_format::encode(&chunk_with(second, BlockType::Dirt), chunk_format::Compression::Lz4));
+        let mut bytes = region.encode();
+
+        // The first chunk's data starts right after the table.
+        bytes[TABLE_END + 8] ^= 0xff;
+        let region = RegionFile::decode(&bytes).unwrap();
+        assert!(region.get(first).is_err());
+        let chunk = decode_chunk(&region, second).unwrap();
+        assert_eq!(chunk.voxels[0][0][0].block_type, BlockType::Dirt);
+    }
+
+    #[test]
+    fn rejects_damaged_tables() {
+        let position = Vector3::new(0, 0, 0);
+        let mut region = RegionFile::default();
+        region.insert(position, chunk_format::encode(&chunk_with(position, BlockType::Stone), chunk_format::Compression::None));
+        let mut bytes = region.encode();
+
+        bytes[HEADER_LEN + 4] ^= 1;
+        assert!(RegionFile::decode(&bytes).is_err());
+    }
+}
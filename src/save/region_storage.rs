@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use cgmath::Vector3;
+
+use super::{
+    journal::Journal,
+    region_file::{self, RegionFile},
+    ChunkReads, WorldStorage,
+};
+
+const METADATA_FILE: &str = "world.txt";
+const REGION_DIRECTORY: &str = "regions";
+const REGION_EXTENSION: &str = "region";
+const CORRUPT_EXTENSION: &str = "corrupt";
+
+/// Keeps a world in plain files, `world.txt` with a `name value` line per metadata field and a
+/// `regions` directory of `RegionFile`s. Saves go through a `Journal`, so a crash leaves either
+/// the old save or the new one.
+pub struct RegionStorage {
+    directory: PathBuf,
+}
+
+impl RegionStorage {
+    pub fn new(directory: &Path) -> Self {
+        Self { directory: directory.to_path_buf() }
+    }
+
+    fn metadata_path(&self) -> PathBuf {
+        self.directory.join(METADATA_FILE)
+    }
+
+    fn region_path(&self, region_position: Vector3<i32>) -> PathBuf {
+        self.directory.join(REGION_DIRECTORY).join(format!("r.{}.{}.{}.{}", region_position.x, region_position.y, region_position.z, REGION_EXTENSION))
+    }
+}
+
+impl WorldStorage for RegionStorage {
+    fn exists(&self) -> bool {
+        self.metadata_path().exists() || self.directory.join(REGION_DIRECTORY).exists()
+    }
+
+    fn recover(&mut self) -> anyhow::Result<Vec<String>> {
+        let recovery = Journal::recover(&self.directory)?;
+        let mut warnings = Vec::new();
+        if recovery.completed > 0 {
+            warnings.push(format!("finished an interrupted save by replacing {} files", recovery.completed));
+        }
+        if recovery.discarded > 0 {
+            warnings.push(format!("rolled back an interrupted save by deleting {} unfinished files", recovery.discarded));
+        }
+        Ok(warnings)
+    }
+
+    fn read_metadata(&mut self) -> anyhow::Result<Option<HashMap<String, String>>> {
+        let path = self.metadata_path();
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let text = fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+        let fields = text.lines().filter_map(|line| line.trim().split_once(' ')).map(|(name, value)| (name.to_string(), value.trim().to_string())).collect();
+        Ok(Some(fields))
+    }
+
+    fn read_chunks(&mut self, positions: &[Vector3<i32>]) -> anyhow::Result<ChunkReads> {
+        let mut reads = ChunkReads::default();
+        let mut by_region = HashMap::<_, Vec<_>>::new();
+        for position in positions {
+            by_region.entry(region_file::region_position(*position)).or_default().push(*position);
+        }
+
+        for (region_position, positions) in by_region {
+            let region = match RegionFile::read(&self.region_path(region_position)) {
+                Ok(region) => region,
+                Err(error) => {
+                    reads.warnings.push(format!("{:#}, its chunks were generated again", error));
+                    reads.damaged.extend(positions);
+                    continue;
+                }
+            };
+
+            for position in positions {
+                match region.get(position) {
+                    Ok(Some(data)) => {
+                        reads.chunks.insert(position, data.to_vec());
+                    }
+                    Ok(None) => {}
+                    Err(error) => {
+                        reads.warnings.push(format!("chunk {} {} {} is corrupt and was generated again: {:#}", position.x, position.y, position.z, error));
+                        reads.damaged.insert(position);
+                    }
+                }
+            }
+        }
+        Ok(reads)
+    }
+
+    /// Rewrites only the region files the chunks are in.
+    fn write(&mut self, metadata: &[(&'static str, String)], chunks: &[(Vector3<i32>, Vec<u8>)]) -> anyhow::Result<()> {
+        let mut journal = Journal::new(&self.directory);
+        let text = metadata.iter().map(|(name, value)| format!("{} {}\n", name, value)).collect::<String>();
+        journal.stage(&self.metadata_path(), text.as_bytes())?;
+
+        let mut by_region = HashMap::<_, Vec<_>>::new();
+        for (position, data) in chunks {
+            by_region.entry(region_file::region_position(*position)).or_default().push((*position, data));
+        }
+
+        for (region_position, chunks) in by_region {
+            let path = self.region_path(region_position);
+            let mut region = match RegionFile::read(&path) {
+                Ok(region) => region,
+                // Its chunks were generated again when loading, the damaged file is kept next to
+                // the new one in case anything in it can be recovered by hand. The copy goes through
+                // the journal, so a failed save leaves the file where it was.
+                Err(error) => {
+                    let backup = path.with_extension(CORRUPT_EXTENSION);
+                    log::warn!("{:#}, keeping a copy at {}", error, backup.display());
+                    let bytes = fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+                    journal.stage(&backup, &bytes)?;
+                    RegionFile::default()
+                }
+            };
+            for (position, data) in chunks {
+                region.insert(position, data.clone());
+            }
+            journal.stage(&path, &region.encode())?;
+        }
+
+        journal.commit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save::chunk_format::{self, Compression};
+    use crate::voxel_things::{
+        chunk::Chunk,
+        voxel::{BlockType, Voxel},
+    };
+
+    fn encoded_chunk(position: Vector3<i32>, block_type: BlockType) -> Vec<u8> {
+        let mut chunk = Chunk::new(position);
+        chunk.voxels[0][0][0] = Voxel::new(block_type);
+        chunk_format::encode(&chunk, Compression::None)
+    }
+
+    #[test]
+    fn saving_keeps_corrupt_chunks_that_were_not_loaded() {
+        let directory = std::env::temp_dir().join(format!("region-storage-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let mut storage = RegionStorage::new(&directory);
+        let (corrupt, saved) = (Vector3::new(0, 0, 0), Vector3::new(1, 0, 0));
+
+        let mut region = RegionFile::default();
+        let mut damaged = encoded_chunk(corrupt, BlockType::Stone);
+        region.insert(corrupt, damaged.clone());
+        let mut bytes = region.encode();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        *damaged.last_mut().unwrap() ^= 0xff;
+        let path = storage.region_path(region_file::region_position(corrupt));
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &bytes).unwrap();
+
+        storage.write(&[("seed", "1".to_string())], &[(saved, encoded_chunk(saved, BlockType::Dirt))]).unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        let region = RegionFile::decode(&bytes).unwrap();
+        assert!(region.get(corrupt).is_err());
+        assert!(bytes.windows(damaged.len()).any(|window| window == damaged.as_slice()));
+        let chunk = chunk_format::decode(saved, region.get(saved).unwrap().unwrap()).unwrap();
+        assert_eq!(chunk.voxels[0][0][0].block_type, BlockType::Dirt);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn saving_over_an_unreadable_region_keeps_a_copy() {
+        let directory = std::env::temp_dir().join(format!("region-storage-backup-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let mut storage = RegionStorage::new(&directory);
+        let position = Vector3::new(0, 0, 0);
+        let path = storage.region_path(region_file::region_position(position));
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, b"not a region").unwrap();
+
+        storage.write(&[("seed", "1".to_string())], &[(position, encoded_chunk(position, BlockType::Dirt))]).unwrap();
+
+        assert_eq!(fs::read(path.with_extension(CORRUPT_EXTENSION)).unwrap(), b"not a region");
+        assert!(RegionFile::read(&path).unwrap().get(position).unwrap().is_some());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use cgmath::Vector3;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::{ChunkReads, WorldStorage};
+
+const DATABASE_FILE: &str = "world.sqlite";
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS metadata (
+        name TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS chunks (
+        x INTEGER NOT NULL,
+        y INTEGER NOT NULL,
+        z INTEGER NOT NULL,
+        data BLOB NOT NULL,
+        PRIMARY KEY (x, y, z)
+    );
+";
+
+/// Keeps a world in `world.sqlite`, for tools that would rather query a database than parse
+/// region files. The `metadata` table has a `name`, `value` row per field and the `chunks` table
+/// has the chunk coordinates `x`, `y`, `z` and the chunk encoded by `chunk_format` as `data`.
+/// Every save is one transaction, so SQLite makes it all or nothing.
+pub struct SqliteStorage {
+    path: PathBuf,
+    connection: Option<Connection>,
+}
+
+impl SqliteStorage {
+    pub fn new(directory: &Path) -> Self {
+        Self {
+            path: directory.join(DATABASE_FILE),
+            connection: None,
+        }
+    }
+
+    /// The open database, `None` if it doesn't exist yet unless `create` is set. Loading a world
+    /// that was never saved shouldn't leave an empty database behind.
+    fn connection(&mut self, create: bool) -> anyhow::Result<Option<&mut Connection>> {
+        if self.connection.is_none() {
+            if !create && !self.path.exists() {
+                return Ok(None);
+            }
+            if let Some(directory) = self.path.parent() {
+                fs::create_dir_all(directory).with_context(|| format!("failed to create {}", directory.display()))?;
+            }
+
+            let connection = Connection::open(&self.path).with_context(|| format!("failed to open {}", self.path.display()))?;
+            connection.execute_batch(SCHEMA).with_context(|| format!("failed to set up {}", self.path.display()))?;
+            self.connection = Some(connection);
+        }
+        Ok(self.connection.as_mut())
+    }
+}
+
+impl WorldStorage for SqliteStorage {
+    fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    fn read_metadata(&mut self) -> anyhow::Result<Option<HashMap<String, String>>> {
+        let Some(connection) = self.connection(false)? else {
+            return Ok(None);
+        };
+
+        let mut statement = connection.prepare("SELECT name, value FROM metadata")?;
+        let fields = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?.collect::<Result<HashMap<_, _>, _>>()?;
+        Ok((!fields.is_empty()).then_some(fields))
+    }
+
+    fn read_chunks(&mut self, positions: &[Vector3<i32>]) -> anyhow::Result<ChunkReads> {
+        let mut reads = ChunkReads::default();
+        let Some(connection) = self.connection(false)? else {
+            return Ok(reads);
+        };
+
+        let mut statement = connection.prepare("SELECT data FROM chunks WHERE x = ?1 AND y = ?2 AND z = ?3")?;
+        for position in positions {
+            if let Some(data) = statement.query_row(params![position.x, position.y, position.z], |row| row.get(0)).optional()? {
+                reads.chunks.insert(*position, data);
+            }
+        }
+        Ok(reads)
+    }
+
+    fn write(&mut self, metadata: &[(&'static str, String)], chunks: &[(Vector3<i32>, Vec<u8>)]) -> anyhow::Result<()> {
+        let path = self.path.clone();
+        let connection = self.connection(true)?.context("failed to create the database")?;
+        let transaction = connection.transaction()?;
+        transaction.execute("DELETE FROM metadata", [])?;
+        {
+            let mut statement = transaction.prepare("INSERT INTO metadata (name, value) VALUES (?1, ?2)")?;
+            for (name, value) in metadata {
+                statement.execute(params![name, value])?;
+            }
+
+            let mut statement = transaction.prepare("INSERT OR REPLACE INTO chunks (x, y, z, data) VALUES (?1, ?2, ?3, ?4)")?;
+            for (position, data) in chunks {
+                statement.execute(params![position.x, position.y, position.z, data])?;
+            }
+        }
+        transaction.commit().with_context(|| format!("failed to write {}", path.display()))
+    }
+}
@@ -0,0 +1,97 @@
+use std::path::Path;
+
+use anyhow::{bail, Context};
+
+/// A frame copied out of a texture, waiting for the encoder to be submitted before it can be read.
+pub struct Capture {
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    format: wgpu::TextureFormat,
+}
+
+/// Records a copy of `texture` into a readable buffer. The texture needs `COPY_SRC` usage.
+pub fn capture(device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture, width: u32, height: u32, format: wgpu::TextureFormat) -> Capture {
+    // Rows of a texture to buffer copy have to be aligned to 256 bytes.
+    let bytes_per_row = 4 * width;
+    let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let padded_bytes_per_row = bytes_per_row.div_ceil(alignment) * alignment;
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Screenshot Buffer"),
+        size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(padded_bytes_per_row),
+                rows_per_image: std::num::NonZeroU32::new(height),
+            },
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+
+    Capture {
+        buffer,
+        width,
+        height,
+        padded_bytes_per_row,
+        format,
+    }
+}
+
+impl Capture {
+    /// Waits for the copy and writes it to `path` as a PNG, call after submitting the encoder.
+    pub fn save(&self, device: &wgpu::Device, path: &Path) -> anyhow::Result<()> {
+        let swap_red_blue = match self.format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            format => bail!("can't save a screenshot of a {:?} surface", format),
+        };
+
+        let slice = self.buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        receiver.recv().context("screenshot buffer was never mapped")??;
+
+        let mut pixels = Vec::with_capacity((4 * self.width * self.height) as usize);
+        {
+            let data = slice.get_mapped_range();
+            for row in data.chunks_exact(self.padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..(4 * self.width) as usize]);
+            }
+        }
+        self.buffer.unmap();
+
+        // Blending leaves partial alpha behind translucent faces, the window shows it opaque.
+        for pixel in pixels.chunks_exact_mut(4) {
+            if swap_red_blue {
+                pixel.swap(0, 2);
+            }
+            pixel[3] = u8::MAX;
+        }
+
+        let image = image::RgbaImage::from_raw(self.width, self.height, pixels).context("screenshot has the wrong size")?;
+        image.save(path).with_context(|| format!("failed to save {}", path.display()))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+use crate::camera::CameraController;
+use crate::light::Light;
+use crate::sky::SkyConfig;
+use crate::voxel_things::chunk::GeneratorConfig;
+
+/// What the panels changed this frame that has to be pushed somewhere else.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SettingsChanges {
+    /// The sky uniform needs to be rewritten.
+    pub sky: bool,
+    /// The world has to be generated again with the new seed and generator settings.
+    pub regenerate: bool,
+}
+
+/// The seed and generator settings being edited, they only reach the world when "Regenerate" is
+/// clicked.
+#[derive(Debug, Clone, Copy)]
+pub struct TerrainDraft {
+    pub seed: u32,
+    pub generator: GeneratorConfig,
+    /// What the world used when the draft was last synced, to notice changes made elsewhere.
+    applied: (u32, GeneratorConfig),
+}
+
+impl TerrainDraft {
+    pub fn new(seed: u32, generator: GeneratorConfig) -> Self {
+        Self {
+            seed,
+            generator,
+            applied: (seed, generator),
+        }
+    }
+
+    /// Starts over from the world's settings if they changed since the last sync, e.g. through
+    /// the `seed` command or by loading a save.
+    fn sync(&mut self, seed: u32, generator: GeneratorConfig) {
+        if self.applied != (seed, generator) {
+            *self = Self::new(seed, generator);
+        }
+    }
+
+    fn is_applied(&self) -> bool {
+        (self.seed, self.generator) == self.applied
+    }
+}
+
+/// Everything the settings window edits, borrowed from the engine for one frame.
+pub struct Settings<'a> {
+    pub camera_controller: &'a mut CameraController,
+    pub light: &'a mut Light,
+    pub sky: &'a mut SkyConfig,
+    pub terrain: &'a mut TerrainDraft,
+    /// The seed and generator settings the world currently uses.
+    pub world_seed: u32,
+    pub world_generator: GeneratorConfig,
+}
+
+pub fn show(context: &egui::Context, settings: Settings) -> SettingsChanges {
+    let mut changes = SettingsChanges::default();
+    settings.terrain.sync(settings.world_seed, settings.world_generator);
+
+    egui::Window::new("Settings").default_pos(egui::pos2(8.0, 104.0)).default_width(280.0).show(context, |ui| {
+        ui.collapsing("Camera", |ui| {
+            ui.add(egui::Slider::new(&mut settings.camera_controller.speed, 1.0..=50.0).text("speed"));
+            ui.add(egui::Slider::new(&mut settings.camera_controller.sensitivity, 0.05..=2.0).text("sensitivity"));
+        });
+
+        ui.collapsing("Light", |ui| {
+            ui.horizontal(|ui| {
+                ui.color_edit_button_rgb(&mut settings.light.color);
+                ui.label("sun light color");
+            });
+        });
+
+        ui.collapsing("Sky and fog", |ui| {
+            let fog = ui.add(egui::Slider::new(&mut settings.sky.fog_density, 0.0..=0.1).text("fog density"));
+            let sun_radius = ui.add(egui::Slider::new(&mut settings.sky.sun_radius, 0.5..=10.0).text("sun radius"));
+            changes.sky |= fog.changed() || sun_radius.changed();
+
+            for (color, label) in [
+                (&mut settings.sky.zenith_color, "zenith color"),
+                (&mut settings.sky.horizon_color, "horizon color"),
+                (&mut settings.sky.sun_color, "sun disc color"),
+            ] {
+                ui.horizontal(|ui| {
+                    changes.sky |= ui.color_edit_button_rgb(color).changed();
+                    ui.label(label);
+                });
+            }
+        });
+
+        ui.collapsing("Terrain generator", |ui| {
+            let terrain = &mut *settings.terrain;
+            let generator = &mut terrain.generator;
+            ui.add(egui::DragValue::new(&mut terrain.seed).prefix("seed: "));
+            ui.add(egui::Slider::new(&mut generator.noise_scale, 0.005..=0.3).logarithmic(true).text("noise scale"));
+            ui.add(egui::Slider::new(&mut generator.surface_level, 0.0..=16.0).text("surface level"));
+            ui.add(egui::Slider::new(&mut generator.surface_falloff, 1.0..=32.0).text("surface falloff"));
+            ui.add(egui::Slider::new(&mut generator.solid_threshold, -1.0..=1.0).text("solid threshold"));
+            ui.add(egui::Slider::new(&mut generator.dirt_depth, 1..=8).text("dirt depth"));
+            ui.add(egui::Slider::new(&mut generator.water_level, 0.0..=16.0).text("water level"));
+
+            ui.horizontal(|ui| {
+                changes.regenerate = ui.button("Regenerate").clicked();
+                if ui.button("Reset").clicked() {
+                    terrain.generator = GeneratorConfig::default();
+                }
+                if ui.add_enabled(!terrain.is_applied(), egui::Button::new("Revert")).clicked() {
+                    *terrain = TerrainDraft::new(settings.world_seed, settings.world_generator);
+                }
+            });
+            if !terrain.is_applied() {
+                ui.label("Changes apply when the world is regenerated.");
+            }
+        });
+    });
+
+    changes
+}
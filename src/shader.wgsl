@@ -1,14 +1,46 @@
 struct CameraUniform {
 	view_pos: vec4<f32>,
 	view_proj: mat4x4<f32>,
+	inv_view_proj: mat4x4<f32>,
 };
 @group(0) @binding(0)
 var<uniform> camera: CameraUniform;
 
+struct Light {
+	position: vec3<f32>,
+	color: vec3<f32>,
+	shadow_bias: f32,
+	direction: vec3<f32>,
+	view_proj: mat4x4<f32>,
+}
+@group(1) @binding(0)
+var<uniform> light: Light;
+@group(1) @binding(1)
+var t_shadow: texture_depth_2d;
+@group(1) @binding(2)
+var s_shadow: sampler_comparison;
+
+struct Sky {
+	zenith_color: vec3<f32>,
+	fog_density: f32,
+	horizon_color: vec3<f32>,
+	sun_cos: f32,
+	sun_color: vec3<f32>,
+}
+@group(2) @binding(0)
+var<uniform> sky: Sky;
+
+@group(3) @binding(0)
+var t_blocks: texture_2d_array<f32>;
+@group(3) @binding(1)
+var s_blocks: sampler;
+
 struct VertexInput {
 	@location(0) position: vec3<f32>,
 	@location(1) normal: vec3<f32>,
 	@location(2) color: vec3<f32>,
+	@location(3) uv: vec2<f32>,
+	@location(4) texture_layer: u32,
 }
 
 struct InstanceInput {
@@ -23,6 +55,8 @@ struct VertexOutput {
 	@location(1) normal: vec3<f32>,
 	@location(2) color: vec3<f32>,
 	@location(3) position: vec3<f32>,
+	@location(4) uv: vec2<f32>,
+	@location(5) @interpolate(flat) texture_layer: u32,
 }
 
 @vertex 
@@ -44,12 +78,77 @@ fn vs_main(
 	out.builtin_position = camera.view_proj * model_space;
 	out.color = in.color;
 	out.normal = in.normal;
+	out.uv = in.uv;
+	out.texture_layer = in.texture_layer;
 	return out;
 }
 
+let AMBIENT_STRENGTH: f32 = 0.3;
+
+// 3x3 percentage closer filtering of the sun's shadow map, 1.0 is fully lit.
+fn fetch_shadow(world_position: vec3<f32>) -> f32 {
+	let light_space = light.view_proj * vec4<f32>(world_position, 1.0);
+	if (light_space.w <= 0.0) {
+		return 1.0;
+	}
+
+	let ndc = light_space.xyz / light_space.w;
+	let uv = ndc.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5, 0.5);
+	if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || ndc.z > 1.0) {
+		return 1.0;
+	}
+
+	let texel = 1.0 / vec2<f32>(textureDimensions(t_shadow));
+	let depth = ndc.z - light.shadow_bias;
+	var visibility = 0.0;
+	for (var x = -1; x <= 1; x += 1) {
+		for (var y = -1; y <= 1; y += 1) {
+			let offset = vec2<f32>(f32(x), f32(y)) * texel;
+			visibility += textureSampleCompareLevel(t_shadow, s_shadow, uv + offset, depth);
+		}
+	}
+	return visibility / 9.0;
+}
+
+fn sky_gradient(direction: vec3<f32>) -> vec3<f32> {
+	let height = clamp(direction.y, 0.0, 1.0);
+	return mix(sky.horizon_color, sky.zenith_color, sqrt(height));
+}
+
+let ALPHA_CUTOFF: f32 = 0.5;
+
+fn shade(in: VertexOutput) -> vec4<f32> {
+	let normal = normalize(in.normal);
+	let diffuse = max(dot(normal, light.direction), 0.0);
+	let shadow = fetch_shadow(in.position);
+
+	let lighting = light.color * (AMBIENT_STRENGTH + diffuse * shadow);
+	let albedo = textureSample(t_blocks, s_blocks, in.uv, i32(in.texture_layer)) * vec4<f32>(in.color, 1.0);
+
+	// Fade into the sky behind the fragment so the edge of the loaded terrain disappears.
+	let to_fragment = in.position - camera.view_pos.xyz;
+	let distance = length(to_fragment);
+	let fog = 1.0 - exp(-pow(distance * sky.fog_density, 2.0));
+	let color = mix(albedo.rgb * lighting, sky_gradient(to_fragment / distance), fog);
+
+	return vec4<f32>(color, albedo.a);
+}
+
+// Opaque and cutout faces, cutout texels below the alpha cutoff are discarded.
 @fragment
 fn fs_main(
 	in: VertexOutput,
 ) -> @location(0) vec4<f32> {
-	return vec4<f32>(in.color, 1.0);
+	let color = shade(in);
+	if (color.a < ALPHA_CUTOFF) {
+		discard;
+	}
+	return vec4<f32>(color.rgb, 1.0);
+}
+
+@fragment
+fn fs_translucent(
+	in: VertexOutput,
+) -> @location(0) vec4<f32> {
+	return shade(in);
 }
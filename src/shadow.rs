@@ -0,0 +1,87 @@
+use crate::{render_utilities, texture, InstanceRaw};
+use crate::voxel_things::{vertex::Vertex, vertex_desc::VertexDesc};
+
+#[derive(Debug, Clone, Copy)]
+pub struct ShadowConfig {
+    /// Width and height of the shadow map in texels.
+    pub resolution: u32,
+    /// Depth offset subtracted in the fragment shader before comparing against the shadow map.
+    pub bias: f32,
+    /// Slope scaled depth bias applied while rendering the shadow map.
+    pub slope_bias: f32,
+    /// Half of the width of the area around the light target that casts shadows.
+    pub extent: f32,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self {
+            resolution: 2048,
+            bias: 0.0005,
+            slope_bias: 2.0,
+            extent: 80.0,
+        }
+    }
+}
+
+pub struct ShadowMap {
+    pub config: ShadowConfig,
+    pub texture: texture::Texture,
+    pub pipeline: wgpu::RenderPipeline,
+    pub light_bind_group: wgpu::BindGroup,
+}
+
+impl ShadowMap {
+    pub fn new(device: &wgpu::Device, config: ShadowConfig, light_buffer: &wgpu::Buffer, block_texture_bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+        let texture = texture::Texture::create_shadow_texture(device, config.resolution, "Shadow Texture");
+
+        // The voxel pass samples the shadow map through its light bind group, so the shadow pass
+        // gets one with only the uniform to avoid binding the texture it renders into.
+        let light_bind_group_layout = render_utilities::create_bind_group_layout(device, "shadow_light_bind_group_layout", 0, wgpu::ShaderStages::VERTEX);
+        let light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("shadow_light_bind_group"),
+            layout: &light_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: light_buffer.as_entire_binding(),
+            }],
+        });
+
+        // The block textures let cutout texels be discarded, so leaves cast shadows through their gaps.
+        let pipeline_layout = render_utilities::create_pipeline_layout(device, "shadow_pipeline", &[&light_bind_group_layout, block_texture_bind_group_layout]);
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shadow.wgsl"));
+        let bias = wgpu::DepthBiasState {
+            constant: 2,
+            slope_scale: config.slope_bias,
+            clamp: 0.0,
+        };
+        let pipeline = render_utilities::create_depth_only_pipeline(device, &pipeline_layout, texture::Texture::DEPTH_FORMAT, &[Vertex::desc(), InstanceRaw::desc()], shader, bias, "shadow pipeline");
+
+        Self {
+            config,
+            texture,
+            pipeline,
+            light_bind_group,
+        }
+    }
+
+    pub fn begin_pass<'a>(&'a self, encoder: &'a mut wgpu::CommandEncoder, block_texture_bind_group: &'a wgpu::BindGroup) -> wgpu::RenderPass<'a> {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Shadow Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.light_bind_group, &[]);
+        render_pass.set_bind_group(1, block_texture_bind_group, &[]);
+        render_pass
+    }
+}
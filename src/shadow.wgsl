@@ -0,0 +1,63 @@
+struct Light {
+	position: vec3<f32>,
+	color: vec3<f32>,
+	shadow_bias: f32,
+	direction: vec3<f32>,
+	view_proj: mat4x4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> light: Light;
+
+@group(1) @binding(0)
+var t_blocks: texture_2d_array<f32>;
+@group(1) @binding(1)
+var s_blocks: sampler;
+
+// Same cutoff as the voxel pass, so leaves shadow only where they are drawn.
+let ALPHA_CUTOFF: f32 = 0.5;
+
+struct VertexInput {
+	@location(0) position: vec3<f32>,
+	@location(3) uv: vec2<f32>,
+	@location(4) texture_layer: u32,
+}
+
+struct VertexOutput {
+	@builtin(position) clip_position: vec4<f32>,
+	@location(0) uv: vec2<f32>,
+	@location(1) @interpolate(flat) texture_layer: u32,
+}
+
+struct InstanceInput {
+	@location(5) model_matrix_0: vec4<f32>,
+	@location(6) model_matrix_1: vec4<f32>,
+	@location(7) model_matrix_2: vec4<f32>,
+	@location(8) model_matrix_3: vec4<f32>,
+}
+
+@vertex
+fn vs_main(
+	in: VertexInput,
+	instance: InstanceInput,
+) -> VertexOutput {
+	let model_matrix = mat4x4<f32>(
+		instance.model_matrix_0,
+		instance.model_matrix_1,
+		instance.model_matrix_2,
+		instance.model_matrix_3,
+	);
+
+	var out: VertexOutput;
+	out.clip_position = light.view_proj * model_matrix * vec4<f32>(in.position, 1.0);
+	out.uv = in.uv;
+	out.texture_layer = in.texture_layer;
+	return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) {
+	let alpha = textureSample(t_blocks, s_blocks, in.uv, i32(in.texture_layer)).a;
+	if (alpha < ALPHA_CUTOFF) {
+		discard;
+	}
+}
@@ -0,0 +1,117 @@
+use crate::render_utilities;
+
+#[derive(Debug, Clone, Copy)]
+pub struct SkyConfig {
+    pub zenith_color: [f32; 3],
+    pub horizon_color: [f32; 3],
+    pub sun_color: [f32; 3],
+    /// Angular radius of the sun disc in degrees.
+    pub sun_radius: f32,
+    /// Density of the exponential squared distance fog, terrain at `1.0 / fog_density` is ~63% fogged.
+    pub fog_density: f32,
+}
+
+impl Default for SkyConfig {
+    fn default() -> Self {
+        Self {
+            zenith_color: [0.12, 0.3, 0.7],
+            horizon_color: [0.6, 0.75, 0.9],
+            sun_color: [1.0, 0.95, 0.8],
+            sun_radius: 2.0,
+            fog_density: 0.018,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SkyUniform {
+    pub zenith_color: [f32; 3],
+    pub fog_density: f32,
+    pub horizon_color: [f32; 3],
+    pub sun_cos: f32,
+    pub sun_color: [f32; 3],
+    pub _padding: u32,
+}
+
+impl SkyUniform {
+    pub fn new(config: &SkyConfig) -> Self {
+        Self {
+            zenith_color: config.zenith_color,
+            fog_density: config.fog_density,
+            horizon_color: config.horizon_color,
+            sun_cos: config.sun_radius.to_radians().cos(),
+            sun_color: config.sun_color,
+            _padding: 0,
+        }
+    }
+}
+
+pub struct Sky {
+    pub config: SkyConfig,
+    pub buffer: wgpu::Buffer,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    pub pipeline: wgpu::RenderPipeline,
+}
+
+impl Sky {
+    pub fn new(device: &wgpu::Device, color_format: wgpu::TextureFormat, camera_bind_group_layout: &wgpu::BindGroupLayout, light_bind_group_layout: &wgpu::BindGroupLayout, config: SkyConfig) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("sky_buffer"),
+            size: std::mem::size_of::<SkyUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let visibility = wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT;
+        let bind_group_layout = render_utilities::create_bind_group_layout(device, "sky_bind_group_layout", 0, visibility);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("sky_bind_group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        let bind_group_layouts = &[camera_bind_group_layout, light_bind_group_layout, &bind_group_layout];
+        let pipeline_layout = render_utilities::create_pipeline_layout(device, "sky_pipeline", bind_group_layouts);
+        let shader = device.create_shader_module(wgpu::include_wgsl!("sky.wgsl"));
+        let pipeline = render_utilities::create_render_pipeline(device, &pipeline_layout, color_format, None, &[], shader, "sky pipeline");
+
+        Self {
+            config,
+            buffer,
+            bind_group_layout,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[SkyUniform::new(&self.config)]));
+    }
+
+    /// Clears `view` to the sky gradient and sun disc, the voxel pass then loads it instead of clearing.
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView, camera_bind_group: &wgpu::BindGroup, light_bind_group: &wgpu::BindGroup) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Sky Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, light_bind_group, &[]);
+        render_pass.set_bind_group(2, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
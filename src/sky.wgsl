@@ -0,0 +1,65 @@
+struct CameraUniform {
+	view_pos: vec4<f32>,
+	view_proj: mat4x4<f32>,
+	inv_view_proj: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct Light {
+	position: vec3<f32>,
+	color: vec3<f32>,
+	shadow_bias: f32,
+	direction: vec3<f32>,
+	view_proj: mat4x4<f32>,
+}
+@group(1) @binding(0)
+var<uniform> light: Light;
+
+struct Sky {
+	zenith_color: vec3<f32>,
+	fog_density: f32,
+	horizon_color: vec3<f32>,
+	sun_cos: f32,
+	sun_color: vec3<f32>,
+}
+@group(2) @binding(0)
+var<uniform> sky: Sky;
+
+struct VertexOutput {
+	@builtin(position) clip_position: vec4<f32>,
+	@location(0) ndc: vec2<f32>,
+}
+
+// One triangle covering the whole screen.
+@vertex
+fn vs_main(
+	@builtin(vertex_index) index: u32,
+) -> VertexOutput {
+	let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+	let ndc = uv * 2.0 - vec2<f32>(1.0, 1.0);
+
+	var out: VertexOutput;
+	out.clip_position = vec4<f32>(ndc, 1.0, 1.0);
+	out.ndc = ndc;
+	return out;
+}
+
+fn sky_gradient(direction: vec3<f32>) -> vec3<f32> {
+	let height = clamp(direction.y, 0.0, 1.0);
+	return mix(sky.horizon_color, sky.zenith_color, sqrt(height));
+}
+
+@fragment
+fn fs_main(
+	in: VertexOutput,
+) -> @location(0) vec4<f32> {
+	let far = camera.inv_view_proj * vec4<f32>(in.ndc, 1.0, 1.0);
+	let direction = normalize(far.xyz / far.w - camera.view_pos.xyz);
+
+	let sun_amount = dot(direction, light.direction);
+	let sun_edge = sky.sun_cos + (1.0 - sky.sun_cos) * 0.2;
+	let disc = smoothstep(sky.sun_cos, sun_edge, sun_amount);
+
+	return vec4<f32>(mix(sky_gradient(direction), sky.sun_color, disc), 1.0);
+}
@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+
+use crate::render_utilities;
+use crate::texture;
+use crate::voxel_things::vertex_desc::VertexDesc;
+
+/// Pixel height the glyphs are rasterized at.
+pub const FONT_SIZE: f32 = 16.0;
+const FONT_FILE: &str = "Hack-Regular.ttf";
+const ATLAS_WIDTH: u32 = 256;
+/// Printable ASCII, anything else is drawn as '?'.
+const FIRST_CHARACTER: char = ' ';
+const LAST_CHARACTER: char = '~';
+const FALLBACK_CHARACTER: char = '?';
+const INITIAL_CAPACITY: usize = 6 * 1024;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TextVertex {
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl VertexDesc for TextVertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<TextVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ScreenUniform {
+    size: [f32; 2],
+    _padding: [f32; 2],
+}
+
+/// Where a rasterized glyph sits in the atlas and how to place it relative to the pen.
+#[derive(Debug, Clone, Copy)]
+struct Glyph {
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+    /// Offset of the bitmap's top left corner from the pen position on the baseline.
+    offset: [f32; 2],
+    size: [f32; 2],
+    advance: f32,
+}
+
+/// Glyphs of the printable ASCII range packed into one coverage texture, with a fully covered
+/// texel reserved for drawing solid rectangles.
+struct GlyphAtlas {
+    image: image::GrayImage,
+    glyphs: HashMap<char, Glyph>,
+    solid_uv: [f32; 2],
+    ascent: f32,
+    line_height: f32,
+}
+
+impl GlyphAtlas {
+    fn new(font: &fontdue::Font, size: f32) -> anyhow::Result<Self> {
+        let line_metrics = font.horizontal_line_metrics(size).context("font has no horizontal line metrics")?;
+
+        let rasterized = (FIRST_CHARACTER..=LAST_CHARACTER)
+            .map(|character| (character, font.rasterize(character, size)))
+            .collect::<Vec<_>>();
+
+        // Shelf packing, glyphs go left to right and wrap to a new row when they don't fit. The
+        // first 2x2 cell is the solid block.
+        let padding = 1;
+        let row_height = rasterized.iter().map(|(_, (metrics, _))| metrics.height as u32).max().unwrap_or(0) + padding;
+        let mut placements = Vec::with_capacity(rasterized.len());
+        let (mut x, mut y) = (2 + padding, 0);
+        for (_, (metrics, _)) in &rasterized {
+            let width = metrics.width as u32;
+            if x + width > ATLAS_WIDTH {
+                x = 0;
+                y += row_height;
+            }
+            placements.push((x, y));
+            x += width + padding;
+        }
+        let height = (y + row_height).next_power_of_two();
+
+        let mut image = image::GrayImage::new(ATLAS_WIDTH, height);
+        for pixel_y in 0..2 {
+            for pixel_x in 0..2 {
+                image.put_pixel(pixel_x, pixel_y, image::Luma([255]));
+            }
+        }
+
+        let atlas_size = [ATLAS_WIDTH as f32, height as f32];
+        let mut glyphs = HashMap::new();
+        for ((character, (metrics, bitmap)), (x, y)) in rasterized.iter().zip(placements) {
+            for (index, coverage) in bitmap.iter().enumerate() {
+                let pixel_x = x + (index % metrics.width) as u32;
+                let pixel_y = y + (index / metrics.width) as u32;
+                image.put_pixel(pixel_x, pixel_y, image::Luma([*coverage]));
+            }
+
+            let size = [metrics.width as f32, metrics.height as f32];
+            glyphs.insert(
+                *character,
+                Glyph {
+                    uv_min: [x as f32 / atlas_size[0], y as f32 / atlas_size[1]],
+                    uv_max: [(x as f32 + size[0]) / atlas_size[0], (y as f32 + size[1]) / atlas_size[1]],
+                    offset: [metrics.xmin as f32, -(metrics.ymin as f32 + size[1])],
+                    size,
+                    advance: metrics.advance_width,
+                },
+            );
+        }
+
+        Ok(Self {
+            image,
+            glyphs,
+            solid_uv: [1.0 / atlas_size[0], 1.0 / atlas_size[1]],
+            ascent: line_metrics.ascent,
+            line_height: line_metrics.new_line_size,
+        })
+    }
+
+    fn glyph(&self, character: char) -> Option<&Glyph> {
+        self.glyphs.get(&character).or_else(|| self.glyphs.get(&FALLBACK_CHARACTER))
+    }
+}
+
+/// Screen space text and rectangles, queued during the frame and drawn over everything else.
+pub struct TextRenderer {
+    atlas: GlyphAtlas,
+    screen_buffer: wgpu::Buffer,
+    screen_bind_group: wgpu::BindGroup,
+    atlas_bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    vertices: Vec<TextVertex>,
+    vertex_buffer: wgpu::Buffer,
+    capacity: usize,
+}
+
+impl TextRenderer {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, color_format: wgpu::TextureFormat, width: u32, height: u32) -> anyhow::Result<Self> {
+        let path = std::path::Path::new(env!("OUT_DIR")).join("res").join("fonts").join(FONT_FILE);
+        let bytes = std::fs::read(&path).with_context(|| format!("failed to load {}", path.display()))?;
+        let font = fontdue::Font::from_bytes(bytes, fontdue::FontSettings::default()).map_err(anyhow::Error::msg)?;
+        let atlas = GlyphAtlas::new(&font, FONT_SIZE)?;
+        let atlas_texture = texture::Texture::from_coverage(device, queue, &atlas.image, "Glyph Atlas");
+
+        let screen_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("text_screen_buffer"),
+            size: std::mem::size_of::<ScreenUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let screen_bind_group_layout = render_utilities::create_bind_group_layout(device, "text_screen_bind_group_layout", 0, wgpu::ShaderStages::VERTEX);
+        let screen_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("text_screen_bind_group"),
+            layout: &screen_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: screen_buffer.as_entire_binding(),
+            }],
+        });
+
+        let atlas_bind_group_layout = texture::create_texture_bind_group_layout(device, "glyph_atlas_bind_group_layout");
+        let atlas_bind_group = texture::create_texture_bind_group(device, &atlas_bind_group_layout, &atlas_texture, "glyph_atlas_bind_group");
+
+        let pipeline_layout = render_utilities::create_pipeline_layout(device, "text_pipeline", &[&screen_bind_group_layout, &atlas_bind_group_layout]);
+        let shader = device.create_shader_module(wgpu::include_wgsl!("text.wgsl"));
+        let options = render_utilities::PipelineOptions {
+            blend: wgpu::BlendState::ALPHA_BLENDING,
+            cull_mode: None,
+            ..render_utilities::PipelineOptions::new("text pipeline")
+        };
+        let pipeline = render_utilities::create_render_pipeline_with_options(device, &pipeline_layout, color_format, None, &[TextVertex::desc()], shader, options);
+
+        let renderer = Self {
+            atlas,
+            screen_buffer,
+            screen_bind_group,
+            atlas_bind_group,
+            pipeline,
+            vertices: Vec::new(),
+            vertex_buffer: create_vertex_buffer(device, INITIAL_CAPACITY),
+            capacity: INITIAL_CAPACITY,
+        };
+        renderer.resize(queue, width, height);
+        Ok(renderer)
+    }
+
+    pub fn resize(&self, queue: &wgpu::Queue, width: u32, height: u32) {
+        let screen = ScreenUniform {
+            size: [width as f32, height as f32],
+            _padding: [0.0; 2],
+        };
+        queue.write_buffer(&self.screen_buffer, 0, bytemuck::cast_slice(&[screen]));
+    }
+
+    pub fn line_height(&self) -> f32 {
+        self.atlas.line_height
+    }
+
+    /// Width and height in pixels `text` takes up when queued.
+    pub fn measure(&self, text: &str) -> [f32; 2] {
+        let mut width: f32 = 0.0;
+        for line in text.lines() {
+            let line_width = line.chars().filter_map(|character| self.atlas.glyph(character)).map(|glyph| glyph.advance).sum();
+            width = width.max(line_width);
+        }
+        [width, text.lines().count().max(1) as f32 * self.atlas.line_height]
+    }
+
+    /// Queues `text` with its top left corner at `position` in pixels, `\n` starts a new line.
+    pub fn queue(&mut self, text: &str, position: [f32; 2], color: [f32; 4]) {
+        let mut pen = [position[0], position[1] + self.atlas.ascent];
+        for character in text.chars() {
+            if character == '\n' {
+                pen = [position[0], pen[1] + self.atlas.line_height];
+                continue;
+            }
+
+            let Some(glyph) = self.atlas.glyph(character).copied() else {
+                continue;
+            };
+            let min = [(pen[0] + glyph.offset[0]).round(), (pen[1] + glyph.offset[1]).round()];
+            let max = [min[0] + glyph.size[0], min[1] + glyph.size[1]];
+            self.push_quad(min, max, glyph.uv_min, glyph.uv_max, color);
+            pen[0] += glyph.advance;
+        }
+    }
+
+    /// Queues a solid rectangle, e.g. as a background behind text.
+    pub fn queue_rect(&mut self, min: [f32; 2], max: [f32; 2], color: [f32; 4]) {
+        let uv = self.atlas.solid_uv;
+        self.push_quad(min, max, uv, uv, color);
+    }
+
+    fn push_quad(&mut self, min: [f32; 2], max: [f32; 2], uv_min: [f32; 2], uv_max: [f32; 2], color: [f32; 4]) {
+        let vertex = |x: usize, y: usize| TextVertex {
+            position: [[min[0], max[0]][x], [min[1], max[1]][y]],
+            uv: [[uv_min[0], uv_max[0]][x], [uv_min[1], uv_max[1]][y]],
+            color,
+        };
+        self.vertices
+            .extend_from_slice(&[vertex(0, 0), vertex(0, 1), vertex(1, 1), vertex(0, 0), vertex(1, 1), vertex(1, 0)]);
+    }
+
+    /// Draws everything queued this frame over `view` and clears the queue.
+    pub fn render(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        if self.vertices.is_empty() {
+            return;
+        }
+
+        if self.vertices.len() > self.capacity {
+            self.capacity = self.vertices.len().next_power_of_two();
+            self.vertex_buffer = create_vertex_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Text Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, &self.screen_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.atlas_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            render_pass.draw(0..self.vertices.len() as u32, 0..1);
+        }
+
+        self.vertices.clear();
+    }
+}
+
+fn create_vertex_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Text Vertex Buffer"),
+        size: (std::mem::size_of::<TextVertex>() * capacity) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
@@ -0,0 +1,44 @@
+struct Screen {
+	size: vec2<f32>,
+}
+@group(0) @binding(0)
+var<uniform> screen: Screen;
+
+@group(1) @binding(0)
+var t_atlas: texture_2d<f32>;
+@group(1) @binding(1)
+var s_atlas: sampler;
+
+struct VertexInput {
+	@location(0) position: vec2<f32>,
+	@location(1) uv: vec2<f32>,
+	@location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+	@builtin(position) clip_position: vec4<f32>,
+	@location(0) uv: vec2<f32>,
+	@location(1) color: vec4<f32>,
+}
+
+// Positions are in pixels with the origin in the top left corner of the screen.
+@vertex
+fn vs_main(
+	in: VertexInput,
+) -> VertexOutput {
+	let ndc = in.position / screen.size * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0);
+
+	var out: VertexOutput;
+	out.clip_position = vec4<f32>(ndc, 0.0, 1.0);
+	out.uv = in.uv;
+	out.color = in.color;
+	return out;
+}
+
+@fragment
+fn fs_main(
+	in: VertexOutput,
+) -> @location(0) vec4<f32> {
+	let coverage = textureSample(t_atlas, s_atlas, in.uv).r;
+	return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
@@ -0,0 +1,650 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, ensure, Context};
+use cgmath::Vector3;
+
+use crate::voxel_things::{
+    region::{Clipboard, Region},
+    voxel::{BlockType, Voxel},
+};
+
+const MAGIC: &[u8; 4] = b"VOX ";
+/// Version MagicaVoxel writes, scene graphs included.
+const VERSION: i32 = 150;
+/// Newest version whose layout is known.
+const MAX_VERSION: i32 = 200;
+pub const EXTENSION: &str = "vox";
+/// MagicaVoxel can't open models bigger than this along any axis, larger exports are split.
+pub const MAX_MODEL_SIZE: i32 = 256;
+/// Most voxels an import may span, so a scene with far apart models doesn't exhaust memory.
+const MAX_IMPORT_VOLUME: i64 = Clipboard::MAX_VOLUME as i64;
+/// Deepest scene graph followed, also stops cycles in damaged files.
+const MAX_SCENE_DEPTH: usize = 64;
+/// Most nodes and instances a scene graph may expand to.
+const MAX_SCENE_VISITS: usize = 1 << 20;
+
+/// A MagicaVoxel `.vox` file. Positions are in MagicaVoxel's coordinates, where z points up.
+///
+/// The file starts with `VOX ` and a little endian i32 version, followed by a `MAIN` chunk. Every
+/// chunk is a 4 byte id, the i32 sizes of its content and of its children, then both. `MAIN` has
+/// a `SIZE` and `XYZI` chunk per model, the palette as `RGBA` and the scene graph as `nTRN`,
+/// `nGRP` and `nSHP` nodes. Materials, layers and cameras don't affect blocks and are skipped.
+#[derive(Debug, Clone)]
+pub struct VoxFile {
+    pub models: Vec<VoxModel>,
+    /// RGBA of every color index, index 0 means empty.
+    pub palette: [[u8; 4]; 256],
+    /// Every placement of a model in the scene. Files without a scene graph have one per model at
+    /// the origin.
+    pub instances: Vec<VoxInstance>,
+}
+
+#[derive(Debug, Clone)]
+pub struct VoxModel {
+    pub size: Vector3<i32>,
+    /// Position and color index of every filled voxel.
+    pub voxels: Vec<(Vector3<u8>, u8)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct VoxInstance {
+    pub model: usize,
+    pub transform: VoxTransform,
+}
+
+/// Rotation followed by translation, applied to voxels relative to the center of their model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VoxTransform {
+    /// Rows of a matrix with a single 1 or -1 in every row and column.
+    pub rotation: [[i32; 3]; 3],
+    pub translation: Vector3<i32>,
+}
+
+impl Default for VoxTransform {
+    fn default() -> Self {
+        Self {
+            rotation: [[1, 0, 0], [0, 1, 0], [0, 0, 1]],
+            translation: Vector3::new(0, 0, 0),
+        }
+    }
+}
+
+impl VoxTransform {
+    /// `None` if the result doesn't fit in an `i32`, files can translate models anywhere.
+    pub fn apply(&self, position: Vector3<i32>) -> Option<Vector3<i32>> {
+        let rotated = self.rotate(position)?;
+        Some(Vector3::new(rotated.x.checked_add(self.translation.x)?, rotated.y.checked_add(self.translation.y)?, rotated.z.checked_add(self.translation.z)?))
+    }
+
+    fn rotate(&self, position: Vector3<i32>) -> Option<Vector3<i32>> {
+        let row = |row: [i32; 3]| row[0].checked_mul(position.x)?.checked_add(row[1].checked_mul(position.y)?)?.checked_add(row[2].checked_mul(position.z)?);
+        Some(Vector3::new(row(self.rotation[0])?, row(self.rotation[1])?, row(self.rotation[2])?))
+    }
+
+    /// `child` followed by this transform, for a child node in the scene graph.
+    fn then(&self, child: &VoxTransform) -> anyhow::Result<VoxTransform> {
+        let mut rotation = [[0; 3]; 3];
+        for (row, rotation_row) in rotation.iter_mut().enumerate() {
+            for (column, value) in rotation_row.iter_mut().enumerate() {
+                *value = (0..3).map(|i| self.rotation[row][i] * child.rotation[i][column]).sum();
+            }
+        }
+        Ok(VoxTransform {
+            rotation,
+            translation: self.apply(child.translation).context("the scene translates a node out of range")?,
+        })
+    }
+}
+
+impl VoxFile {
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let bytes = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        decode(&bytes).with_context(|| format!("{} is not a valid .vox file", path.display()))
+    }
+
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        fs::write(path, encode(self)).with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    /// Every instance's voxels as blocks, each color turned into the block closest to it. Where
+    /// instances overlap the later one wins, like in MagicaVoxel.
+    pub fn to_clipboard(&self) -> anyhow::Result<Clipboard> {
+        // The bounds are checked before anything is allocated for the voxels, so far apart
+        // instances can't exhaust memory.
+        let mut bounds = None;
+        for placed in self.placed_voxels() {
+            let (position, _) = placed?;
+            let (min, max) = bounds.get_or_insert((position, position));
+            *min = Vector3::new(min.x.min(position.x), min.y.min(position.y), min.z.min(position.z));
+            *max = Vector3::new(max.x.max(position.x), max.y.max(position.y), max.z.max(position.z));
+        }
+        let (min, max) = bounds.context("the file has no voxels")?;
+        let region = Region::new(min, max);
+        ensure!(region.volume() <= MAX_IMPORT_VOLUME, "the models span more than {} voxels", MAX_IMPORT_VOLUME);
+
+        let blocks = self.palette.map(|[r, g, b, _]| BlockType::nearest_to_color([r, g, b]));
+        let size = region.size();
+        let mut voxels = vec![Voxel::new_empty(); region.volume() as usize];
+        for placed in self.placed_voxels() {
+            let (position, index) = placed?;
+            let offset = position - min;
+            voxels[((offset.x * size.y + offset.y) * size.z + offset.z) as usize] = Voxel::new(blocks[index as usize]);
+        }
+        Clipboard::from_voxels(size, voxels).context("the models don't fit in a clipboard")
+    }
+
+    /// The position of every instance's voxels in block coordinates and their color index.
+    fn placed_voxels(&self) -> impl Iterator<Item = anyhow::Result<(Vector3<i32>, u8)>> + '_ {
+        self.instances.iter().flat_map(move |instance| {
+            let model = &self.models[instance.model];
+            let center = model.size / 2;
+            model.voxels.iter().map(move |(position, index)| {
+                let position = instance.transform.apply(position.map(i32::from) - center).context("an instance is translated out of range")?;
+                // z is up in MagicaVoxel, keeping the handedness turns its y into our -z.
+                let flipped_y = position.y.checked_neg().context("an instance is translated out of range")?;
+                Ok((Vector3::new(position.x, position.z, flipped_y), *index))
+            })
+        })
+    }
+
+    /// The clipboard's non-empty voxels, split into models of at most `MAX_MODEL_SIZE` and with a
+    /// palette of the colors of the blocks it uses.
+    pub fn from_clipboard(clipboard: &Clipboard) -> anyhow::Result<Self> {
+        let mut palette = [[0; 4]; 256];
+        let mut indices = HashMap::new();
+        for voxel in clipboard.voxels().iter().filter(|voxel| !voxel.is_empty()) {
+            if !indices.contains_key(&voxel.block_type) {
+                let index = indices.len() + 1;
+                let [r, g, b] = voxel.block_type.definition().color;
+                palette[index] = [r, g, b, 255];
+                indices.insert(voxel.block_type, index as u8);
+            }
+        }
+        ensure!(!indices.is_empty(), "there are no blocks to export");
+
+        // The inverse of the axes `to_clipboard` uses, shifted so every coordinate is positive.
+        let size = clipboard.size();
+        let vox_size = Vector3::new(size.x, size.z, size.y);
+        let mut tiles = HashMap::<Vector3<i32>, Vec<_>>::new();
+        for position in clipboard.positions() {
+            let voxel = clipboard.get(position);
+            if voxel.is_empty() {
+                continue;
+            }
+            let vox_position = Vector3::new(position.x, size.z - 1 - position.z, position.y);
+            let tile = vox_position / MAX_MODEL_SIZE;
+            let local = (vox_position - tile * MAX_MODEL_SIZE).map(|value| value as u8);
+            tiles.entry(tile).or_default().push((local, indices[&voxel.block_type]));
+        }
+
+        let mut tiles = tiles.into_iter().collect::<Vec<_>>();
+        tiles.sort_by_key(|(tile, _)| (tile.x, tile.y, tile.z));
+        let mut file = VoxFile {
+            models: Vec::new(),
+            palette,
+            instances: Vec::new(),
+        };
+        for (tile, voxels) in tiles {
+            let origin = tile * MAX_MODEL_SIZE;
+            let model_size = (vox_size - origin).map(|value| value.min(MAX_MODEL_SIZE));
+            file.instances.push(VoxInstance {
+                model: file.models.len(),
+                transform: VoxTransform {
+                    translation: origin + model_size / 2,
+                    ..VoxTransform::default()
+                },
+            });
+            file.models.push(VoxModel { size: model_size, voxels });
+        }
+        Ok(file)
+    }
+}
+
+enum Node {
+    Transform { child: i32, transform: VoxTransform },
+    Group { children: Vec<i32> },
+    Shape { models: Vec<i32> },
+}
+
+pub fn decode(bytes: &[u8]) -> anyhow::Result<VoxFile> {
+    let mut reader = Reader { bytes };
+    ensure!(reader.take(4)? == MAGIC, "missing VOX header");
+    let version = reader.i32()?;
+    ensure!(version <= MAX_VERSION, "unsupported .vox version {}", version);
+    let (id, _, children) = reader.chunk()?;
+    ensure!(id == *b"MAIN", "missing MAIN chunk");
+
+    let mut models = Vec::new();
+    let mut size = None;
+    let mut palette = default_palette();
+    let mut nodes = HashMap::new();
+    let mut children = Reader { bytes: children };
+    while !children.bytes.is_empty() {
+        let (id, content, _) = children.chunk()?;
+        let mut content = Reader { bytes: content };
+        match &id {
+            b"SIZE" => {
+                let model_size = Vector3::new(content.i32()?, content.i32()?, content.i32()?);
+                ensure!((0..3).all(|axis| (1..=MAX_MODEL_SIZE).contains(&model_size[axis])), "invalid model size {}x{}x{}", model_size.x, model_size.y, model_size.z);
+                size = Some(model_size);
+            }
+            b"XYZI" => {
+                let size = size.take().context("XYZI chunk without a SIZE chunk before it")?;
+                let count = content.i32()?;
+                ensure!(count >= 0 && content.bytes.len() >= count as usize * 4, "XYZI chunk ends early");
+                let mut voxels = Vec::with_capacity(count as usize);
+                for voxel in content.take(count as usize * 4)?.chunks_exact(4) {
+                    let position = Vector3::new(voxel[0], voxel[1], voxel[2]);
+                    ensure!((0..3).all(|axis| (position[axis] as i32) < size[axis]), "voxel {} {} {} is outside of its model", position.x, position.y, position.z);
+                    if voxel[3] != 0 {
+                        voxels.push((position, voxel[3]));
+                    }
+                }
+                models.push(VoxModel { size, voxels });
+            }
+            // Entry i is color index i + 1, the last entry is unused.
+            b"RGBA" => {
+                for color in &mut palette[1..] {
+                    *color = content.array()?;
+                }
+            }
+            b"nTRN" => {
+                let id = content.i32()?;
+                let attributes = content.dict()?;
+                let child = content.i32()?;
+                let _reserved = content.i32()?;
+                let _layer = content.i32()?;
+                let frame_count = content.i32()?;
+                let frame = if frame_count > 0 { content.dict()? } else { HashMap::new() };
+                // Hidden nodes are left out the way MagicaVoxel doesn't render them.
+                if attributes.get("_hidden").map(String::as_str) == Some("1") {
+                    nodes.insert(id, Node::Group { children: Vec::new() });
+                    continue;
+                }
+                nodes.insert(id, Node::Transform { child, transform: frame_transform(&frame)? });
+            }
+            b"nGRP" => {
+                let id = content.i32()?;
+                content.dict()?;
+                let count = content.i32()?;
+                let children = (0..count.max(0)).map(|_| content.i32()).collect::<anyhow::Result<_>>()?;
+                nodes.insert(id, Node::Group { children });
+            }
+            b"nSHP" => {
+                let id = content.i32()?;
+                content.dict()?;
+                let count = content.i32()?;
+                let mut shape_models = Vec::new();
+                for _ in 0..count.max(0) {
+                    shape_models.push(content.i32()?);
+                    content.dict()?;
+                }
+                nodes.insert(id, Node::Shape { models: shape_models });
+            }
+            _ => {}
+        }
+    }
+
+    let mut instances = Vec::new();
+    if nodes.is_empty() {
+        // Without a scene every model sits with its minimum corner at the origin.
+        instances.extend(models.iter().enumerate().map(|(model, VoxModel { size, .. })| VoxInstance {
+            model,
+            transform: VoxTransform {
+                translation: *size / 2,
+                ..VoxTransform::default()
+            },
+        }));
+    } else {
+        collect_instances(&nodes, 0, VoxTransform::default(), models.len(), 0, &mut instances, &mut 0)?;
+        let voxels = instances.iter().map(|instance| models[instance.model].voxels.len()).sum::<usize>();
+        ensure!(voxels <= Clipboard::MAX_VOLUME, "the scene places more than {} voxels", Clipboard::MAX_VOLUME);
+    }
+    Ok(VoxFile { models, palette, instances })
+}
+
+/// Adds the instances below node `id`. `visits` counts the nodes visited and the instances found
+/// so far, groups can list a child more than once and a few of them nested would otherwise place
+/// more instances than fit in memory.
+fn collect_instances(nodes: &HashMap<i32, Node>, id: i32, transform: VoxTransform, model_count: usize, depth: usize, instances: &mut Vec<VoxInstance>, visits: &mut usize) -> anyhow::Result<()> {
+    ensure!(depth < MAX_SCENE_DEPTH, "the scene graph is deeper than {} nodes or has a cycle", MAX_SCENE_DEPTH);
+    *visits += 1;
+    ensure!(*visits <= MAX_SCENE_VISITS, "the scene places more than {} nodes", MAX_SCENE_VISITS);
+    match nodes.get(&id).with_context(|| format!("missing scene node {}", id))? {
+        Node::Transform { child, transform: local } => collect_instances(nodes, *child, transform.then(local)?, model_count, depth + 1, instances, visits)?,
+        Node::Group { children } => {
+            for child in children {
+                collect_instances(nodes, *child, transform, model_count, depth + 1, instances, visits)?;
+            }
+        }
+        Node::Shape { models } => {
+            for model in models {
+                ensure!((0..model_count as i32).contains(model), "scene uses missing model {}", model);
+                *visits += 1;
+                ensure!(*visits <= MAX_SCENE_VISITS, "the scene places more than {} nodes", MAX_SCENE_VISITS);
+                instances.push(VoxInstance { model: *model as usize, transform });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads `_t`, the translation as "x y z", and `_r`, the rotation packed into a byte. Bits 0-1 are
+/// the column of the non-zero entry in the first row, bits 2-3 the one in the second row and bits
+/// 4-6 whether the entries of the three rows are negative.
+fn frame_transform(frame: &HashMap<String, String>) -> anyhow::Result<VoxTransform> {
+    let mut transform = VoxTransform::default();
+    if let Some(translation) = frame.get("_t") {
+        let values = translation.split_whitespace().map(|value| value.parse().with_context(|| format!("invalid translation '{}'", translation))).collect::<anyhow::Result<Vec<i32>>>()?;
+        ensure!(values.len() == 3, "invalid translation '{}'", translation);
+        transform.translation = Vector3::new(values[0], values[1], values[2]);
+    }
+    if let Some(rotation) = frame.get("_r") {
+        let byte = rotation.parse::<u8>().with_context(|| format!("invalid rotation '{}'", rotation))?;
+        let first = (byte & 3) as usize;
+        let second = ((byte >> 2) & 3) as usize;
+        if first > 2 || second > 2 || first == second {
+            bail!("invalid rotation {}", byte);
+        }
+        transform.rotation = [[0; 3]; 3];
+        for (row, column) in [first, second, 3 - first - second].into_iter().enumerate() {
+            transform.rotation[row][column] = if byte & (1 << (4 + row)) != 0 { -1 } else { 1 };
+        }
+    }
+    Ok(transform)
+}
+
+/// Writes a model per `VoxModel` and a scene graph with a transform per instance under one group.
+/// Only translations are written, rotated instances aren't needed for exports.
+pub fn encode(file: &VoxFile) -> Vec<u8> {
+    let mut children = Vec::new();
+    for model in &file.models {
+        let size = [model.size.x, model.size.y, model.size.z].iter().flat_map(|value| value.to_le_bytes()).collect::<Vec<_>>();
+        write_chunk(&mut children, b"SIZE", &size);
+        let mut voxels = (model.voxels.len() as i32).to_le_bytes().to_vec();
+        for (position, index) in &model.voxels {
+            voxels.extend_from_slice(&[position.x, position.y, position.z, *index]);
+        }
+        write_chunk(&mut children, b"XYZI", &voxels);
+    }
+
+    // Node 0 is the root transform, 1 the group under it, then a transform and a shape for every
+    // instance.
+    let mut root = Vec::new();
+    write_i32s(&mut root, &[0, 0, 1, -1, -1, 1, 0]);
+    write_chunk(&mut children, b"nTRN", &root);
+    let mut group = Vec::new();
+    write_i32s(&mut group, &[1, 0, file.instances.len() as i32]);
+    write_i32s(&mut group, &(0..file.instances.len() as i32).map(|instance| 2 + instance * 2).collect::<Vec<_>>());
+    write_chunk(&mut children, b"nGRP", &group);
+    for (instance_index, instance) in file.instances.iter().enumerate() {
+        let id = 2 + instance_index as i32 * 2;
+        let translation = instance.transform.translation;
+        let mut transform = Vec::new();
+        write_i32s(&mut transform, &[id, 0, id + 1, -1, 0, 1]);
+        write_dict(&mut transform, &[("_t", format!("{} {} {}", translation.x, translation.y, translation.z))]);
+        write_chunk(&mut children, b"nTRN", &transform);
+        let mut shape = Vec::new();
+        write_i32s(&mut shape, &[id + 1, 0, 1, instance.model as i32, 0]);
+        write_chunk(&mut children, b"nSHP", &shape);
+    }
+
+    let mut palette = file.palette[1..].iter().flatten().copied().collect::<Vec<_>>();
+    palette.extend_from_slice(&[0; 4]);
+    write_chunk(&mut children, b"RGBA", &palette);
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(b"MAIN");
+    write_i32s(&mut bytes, &[0, children.len() as i32]);
+    bytes.extend_from_slice(&children);
+    bytes
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, id: &[u8; 4], content: &[u8]) {
+    bytes.extend_from_slice(id);
+    write_i32s(bytes, &[content.len() as i32, 0]);
+    bytes.extend_from_slice(content);
+}
+
+fn write_i32s(bytes: &mut Vec<u8>, values: &[i32]) {
+    for value in values {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+fn write_dict(bytes: &mut Vec<u8>, entries: &[(&str, String)]) {
+    write_i32s(bytes, &[entries.len() as i32]);
+    for (key, value) in entries {
+        for string in [*key, value.as_str()] {
+            write_i32s(bytes, &[string.len() as i32]);
+            bytes.extend_from_slice(string.as_bytes());
+        }
+    }
+}
+
+/// The palette MagicaVoxel uses for files without an `RGBA` chunk, a 6x6x6 color cube without
+/// black followed by ramps of red, green, blue and gray.
+fn default_palette() -> [[u8; 4]; 256] {
+    let mut palette = [[0; 4]; 256];
+    let steps = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+    let cube = steps.into_iter().flat_map(|r| steps.into_iter().flat_map(move |g| steps.into_iter().map(move |b| [r, g, b, 0xff]))).take(215);
+    let ramp = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+    let ramps = [[1, 0, 0], [0, 1, 0], [0, 0, 1], [1, 1, 1]].into_iter().flat_map(|channels: [u8; 3]| ramp.map(|value| [channels[0] * value, channels[1] * value, channels[2] * value, 0xff]));
+    for (color, entry) in cube.chain(ramps).zip(&mut palette[1..]) {
+        *entry = color;
+    }
+    palette
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> anyhow::Result<&'a [u8]> {
+        ensure!(self.bytes.len() >= count, "file ends early");
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+        Ok(self.take(N)?.try_into()?)
+    }
+
+    fn i32(&mut self) -> anyhow::Result<i32> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+
+    fn len(&mut self) -> anyhow::Result<usize> {
+        usize::try_from(self.i32()?).context("negative length")
+    }
+
+    /// The id, content and children of the next chunk.
+    fn chunk(&mut self) -> anyhow::Result<([u8; 4], &'a [u8], &'a [u8])> {
+        let id = self.array()?;
+        let content_len = self.len()?;
+        let children_len = self.len()?;
+        Ok((id, self.take(content_len)?, self.take(children_len)?))
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let len = self.len()?;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+
+    fn dict(&mut self) -> anyhow::Result<HashMap<String, String>> {
+        let count = self.len()?;
+        (0..count).map(|_| Ok((self.string()?, self.string()?))).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One 2x2x2 model with two voxels, followed by the scene graph chunks in `nodes`.
+    fn file_with_scene(nodes: &[u8]) -> Vec<u8> {
+        let mut children = Vec::new();
+        write_chunk(&mut children, b"SIZE", &[2, 0, 0, 0, 2, 0, 0, 0, 2, 0, 0, 0]);
+        write_chunk(&mut children, b"XYZI", &[2, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2]);
+        children.extend_from_slice(nodes);
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(b"MAIN");
+        write_i32s(&mut bytes, &[0, children.len() as i32]);
+        bytes.extend_from_slice(&children);
+        bytes
+    }
+
+    fn transform_node(nodes: &mut Vec<u8>, id: i32, child: i32) {
+        let mut content = Vec::new();
+        write_i32s(&mut content, &[id, 0, child, -1, 0, 1]);
+        write_dict(&mut content, &[("_t", "0 0 0".to_string())]);
+        write_chunk(nodes, b"nTRN", &content);
+    }
+
+    fn group_node(nodes: &mut Vec<u8>, id: i32, children: &[i32]) {
+        let mut content = Vec::new();
+        write_i32s(&mut content, &[id, 0, children.len() as i32]);
+        write_i32s(&mut content, children);
+        write_chunk(nodes, b"nGRP", &content);
+    }
+
+    fn shape_node(nodes: &mut Vec<u8>, id: i32, model: i32) {
+        let mut content = Vec::new();
+        write_i32s(&mut content, &[id, 0, 1, model, 0]);
+        write_chunk(nodes, b"nSHP", &content);
+    }
+
+    /// The error `decode` fails with, without printing the whole file if it doesn't.
+    fn decode_error(bytes: &[u8]) -> String {
+        match decode(bytes) {
+            Ok(_) => panic!("the file decoded"),
+            Err(error) => format!("{:#}", error),
+        }
+    }
+
+    #[test]
+    fn decodes_a_scene() {
+        let mut nodes = Vec::new();
+        transform_node(&mut nodes, 0, 1);
+        group_node(&mut nodes, 1, &[2, 2]);
+        shape_node(&mut nodes, 2, 0);
+        let file = decode(&file_with_scene(&nodes)).unwrap();
+        assert_eq!(file.models.len(), 1);
+        assert_eq!(file.instances.len(), 2);
+
+        let clipboard = file.to_clipboard().unwrap();
+        assert_eq!(clipboard.size(), Vector3::new(2, 2, 2));
+        assert_eq!(clipboard.voxels().iter().filter(|voxel| voxel.block_type != BlockType::Empty).count(), 2);
+    }
+
+    #[test]
+    fn rejects_truncated_chunks() {
+        let mut nodes = Vec::new();
+        shape_node(&mut nodes, 0, 0);
+        let bytes = file_with_scene(&nodes);
+        for len in [3, 8, 20, 40, bytes.len() - 1] {
+            assert!(decode(&bytes[..len]).is_err(), "decoded the first {} bytes", len);
+        }
+
+        // An XYZI chunk claiming more voxels than it holds.
+        let mut children = Vec::new();
+        write_chunk(&mut children, b"SIZE", &[2, 0, 0, 0, 2, 0, 0, 0, 2, 0, 0, 0]);
+        write_chunk(&mut children, b"XYZI", &[9, 0, 0, 0, 0, 0, 0, 1]);
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(b"MAIN");
+        write_i32s(&mut bytes, &[0, children.len() as i32]);
+        bytes.extend_from_slice(&children);
+        assert!(decode(&bytes).is_err());
+    }
+
+    #[test]
+    fn rejects_cyclic_scenes() {
+        let mut nodes = Vec::new();
+        transform_node(&mut nodes, 0, 1);
+        group_node(&mut nodes, 1, &[0]);
+        let error = decode_error(&file_with_scene(&nodes));
+        assert!(error.contains("cycle"), "{}", error);
+    }
+
+    #[test]
+    fn stops_repeated_children() {
+        // Every group lists the next one twice, 2^60 instances if nothing stopped it.
+        let mut nodes = Vec::new();
+        for id in 0..60 {
+            group_node(&mut nodes, id, &[id + 1, id + 1]);
+        }
+        shape_node(&mut nodes, 60, 0);
+        let error = decode_error(&file_with_scene(&nodes));
+        assert!(error.contains("places more than"), "{}", error);
+
+        // Fewer instances, but more voxels than a clipboard holds.
+        let mut children = Vec::new();
+        write_chunk(&mut children, b"SIZE", &[255, 0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0]);
+        let mut voxels = (255 * 255 * 4i32).to_le_bytes().to_vec();
+        for x in 0..255 {
+            for y in 0..255 {
+                for z in 0..4 {
+                    voxels.extend_from_slice(&[x, y, z, 1]);
+                }
+            }
+        }
+        write_chunk(&mut children, b"XYZI", &voxels);
+        group_node(&mut children, 0, &[1; 65]);
+        shape_node(&mut children, 1, 0);
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(b"MAIN");
+        write_i32s(&mut bytes, &[0, children.len() as i32]);
+        bytes.extend_from_slice(&children);
+        let error = decode_error(&bytes);
+        assert!(error.contains("more than 16777216 voxels"), "{}", error);
+
+        // Groups without shapes at the bottom still count.
+        let mut nodes = Vec::new();
+        for id in 0..60 {
+            group_node(&mut nodes, id, &[id + 1, id + 1]);
+        }
+        group_node(&mut nodes, 60, &[]);
+        assert!(decode(&file_with_scene(&nodes)).is_err());
+    }
+
+    #[test]
+    fn rejects_missing_models() {
+        for model in [1, -1, i32::MAX] {
+            let mut nodes = Vec::new();
+            shape_node(&mut nodes, 0, model);
+            let error = decode_error(&file_with_scene(&nodes));
+            assert!(error.contains("missing model"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn rejects_instances_too_far_apart() {
+        let model = VoxModel {
+            size: Vector3::new(1, 1, 1),
+            voxels: vec![(Vector3::new(0, 0, 0), 1)],
+        };
+        let instance = |x| VoxInstance {
+            model: 0,
+            transform: VoxTransform {
+                translation: Vector3::new(x, 0, 0),
+                ..VoxTransform::default()
+            },
+        };
+        let file = VoxFile {
+            models: vec![model],
+            palette: default_palette(),
+            instances: vec![instance(-1_000_000_000), instance(1_000_000_000)],
+        };
+        let error = file.to_clipboard().unwrap_err();
+        assert!(format!("{:#}", error).contains("span more than"), "{:#}", error);
+    }
+}
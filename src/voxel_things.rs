@@ -1,6 +1,13 @@
+pub mod block;
 pub mod chunk;
 pub mod quad;
+pub mod raycast;
+pub mod region;
+pub mod selection;
 pub mod voxel;
 pub mod vertex;
 pub mod direction;
+pub mod hash;
+pub mod history;
 pub mod vertex_desc;
+pub mod world;
@@ -0,0 +1,117 @@
+use super::direction::Direction;
+
+/// Texture file names in `res/textures`, the position in this list is the texture array layer.
+pub const BLOCK_TEXTURES: &[&str] = &["grass_top", "grass_side", "dirt", "stone", "water", "glass", "leaves"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transparency {
+    Opaque,
+    /// Texels are either fully opaque or discarded by the alpha test.
+    Cutout,
+    /// Alpha blended in the translucent pass.
+    Translucent,
+}
+
+pub struct FaceTextures {
+    pub top: &'static str,
+    pub side: &'static str,
+    pub bottom: &'static str,
+}
+
+impl FaceTextures {
+    pub const fn all(texture: &'static str) -> Self {
+        Self {
+            top: texture,
+            side: texture,
+            bottom: texture,
+        }
+    }
+}
+
+pub struct BlockDefinition {
+    pub name: &'static str,
+    pub textures: FaceTextures,
+    pub transparency: Transparency,
+    /// How much darker than its texture a voxel of this block can get, 0.0 keeps every voxel identical.
+    pub color_variation: f32,
+    /// Average color of its textures, for matching blocks to colors from other tools.
+    pub color: [u8; 3],
+}
+
+impl BlockDefinition {
+    pub fn texture(&self, direction: Direction) -> &'static str {
+        match direction {
+            Direction::Up => self.textures.top,
+            Direction::Down => self.textures.bottom,
+            _ => self.textures.side,
+        }
+    }
+
+    pub fn texture_layer(&self, direction: Direction) -> u32 {
+        let texture = self.texture(direction);
+        BLOCK_TEXTURES
+            .iter()
+            .position(|name| *name == texture)
+            .unwrap_or_else(|| panic!("block {} uses unknown texture {}", self.name, texture)) as u32
+    }
+}
+
+pub const EMPTY: BlockDefinition = BlockDefinition {
+    name: "empty",
+    textures: FaceTextures::all("stone"),
+    transparency: Transparency::Translucent,
+    color_variation: 0.0,
+    color: [0, 0, 0],
+};
+
+pub const GRASS: BlockDefinition = BlockDefinition {
+    name: "grass",
+    textures: FaceTextures {
+        top: "grass_top",
+        side: "grass_side",
+        bottom: "dirt",
+    },
+    transparency: Transparency::Opaque,
+    color_variation: 0.1,
+    color: [90, 156, 55],
+};
+
+pub const DIRT: BlockDefinition = BlockDefinition {
+    name: "dirt",
+    textures: FaceTextures::all("dirt"),
+    transparency: Transparency::Opaque,
+    color_variation: 0.08,
+    color: [132, 91, 55],
+};
+
+pub const STONE: BlockDefinition = BlockDefinition {
+    name: "stone",
+    textures: FaceTextures::all("stone"),
+    transparency: Transparency::Opaque,
+    color_variation: 0.06,
+    color: [125, 125, 130],
+};
+
+pub const WATER: BlockDefinition = BlockDefinition {
+    name: "water",
+    textures: FaceTextures::all("water"),
+    transparency: Transparency::Translucent,
+    color_variation: 0.03,
+    color: [50, 101, 203],
+};
+
+pub const GLASS: BlockDefinition = BlockDefinition {
+    name: "glass",
+    textures: FaceTextures::all("glass"),
+    transparency: Transparency::Translucent,
+    color_variation: 0.0,
+    color: [204, 230, 238],
+};
+
+pub const LEAVES: BlockDefinition = BlockDefinition {
+    name: "leaves",
+    textures: FaceTextures::all("leaves"),
+    transparency: Transparency::Cutout,
+    color_variation: 0.15,
+    color: [50, 126, 37],
+};
@@ -1,30 +1,194 @@
+use std::time::{Duration, Instant};
+
+use cgmath::{MetricSpace, Point3, Vector3};
 use noise::NoiseFn;
+use wgpu::util::DeviceExt;
 
-use super::voxel;
+use super::{block::Transparency, direction::Direction, quad::Quad, vertex::Vertex, voxel, world::World};
 
 pub struct ChunkMesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
     pub vertex_buffer: Option<wgpu::Buffer>,
     pub index_buffer: Option<wgpu::Buffer>,
-    pub num_indices: u16,
+    pub instance_buffer: Option<wgpu::Buffer>,
+    pub num_indices: u32,
+    pub offset: Vector3<f32>,
+}
+
+impl Default for ChunkMesh {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ChunkMesh {
     pub fn new() -> Self {
         Self {
+            vertices: Vec::new(),
+            indices: Vec::new(),
             vertex_buffer: None,
             index_buffer: None,
+            instance_buffer: None,
             num_indices: 0,
+            offset: Vector3::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    fn push_quad(&mut self, quad: &Quad) {
+        let normal = quad.direction.get_normal().into();
+        let start = self.vertices.len() as u32;
+
+        for (corner, uv) in quad.corners.iter().zip(quad.uvs) {
+            self.vertices.push(Vertex {
+                position: (*corner).into(),
+                normal,
+                color: quad.color,
+                uv,
+                texture_layer: quad.texture_layer,
+            });
+        }
+
+        self.indices
+            .extend_from_slice(&[start, start + 1, start + 2, start, start + 2, start + 3]);
+    }
+
+    fn upload(&mut self, device: &wgpu::Device, position: Vector3<f32>) {
+        self.vertex_buffer = Some(device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Chunk Vertex Buffer"),
+            contents: bytemuck::cast_slice(&self.vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        }));
+
+        self.index_buffer = Some(device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Chunk Index Buffer"),
+            contents: bytemuck::cast_slice(&self.indices),
+            usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+        }));
+
+        let instance = crate::Instance { position };
+        self.instance_buffer = Some(device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Chunk Instance Buffer"),
+            contents: bytemuck::cast_slice(&[instance.to_raw()]),
+            usage: wgpu::BufferUsages::VERTEX,
+        }));
+
+        self.num_indices = self.indices.len() as u32;
+        self.offset = position;
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        Point3::new(0.5, 0.5, 0.5) * CHUNK_SIZE as f32 + self.offset
+    }
+
+    /// Reorders the quads so the ones furthest from `camera_position` are drawn first, which alpha
+    /// blending needs to composite translucent faces correctly.
+    pub fn sort_back_to_front(&mut self, queue: &wgpu::Queue, camera_position: Point3<f32>) {
+        let camera_position = camera_position - self.offset;
+        let mut quads = self
+            .vertices
+            .chunks_exact(4)
+            .enumerate()
+            .map(|(index, corners)| {
+                let center = corners
+                    .iter()
+                    .fold(Point3::new(0.0, 0.0, 0.0), |sum, corner| sum + Vector3::from(corner.position) / 4.0);
+                (index as u32, center.distance2(camera_position))
+            })
+            .collect::<Vec<_>>();
+        quads.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        self.indices.clear();
+        for (quad, _) in quads {
+            let start = quad * 4;
+            self.indices
+                .extend_from_slice(&[start, start + 1, start + 2, start, start + 2, start + 3]);
+        }
+
+        if let Some(index_buffer) = &self.index_buffer {
+            queue.write_buffer(index_buffer, 0, bytemuck::cast_slice(&self.indices));
+        }
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if let (Some(vertex_buffer), Some(index_buffer), Some(instance_buffer)) =
+            (&self.vertex_buffer, &self.index_buffer, &self.instance_buffer)
+        {
+            if self.num_indices == 0 {
+                return;
+            }
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
         }
     }
 }
 
+/// How a chunk's voxels are laid out, uniform chunks hold a single block type throughout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageKind {
+    Uniform,
+    Dense,
+}
+
+/// Numbers collected while meshing a chunk, for tuning the mesher.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkStats {
+    pub vertex_count: usize,
+    pub index_count: usize,
+    pub translucent_vertex_count: usize,
+    pub translucent_index_count: usize,
+    /// Time spent generating faces, not including the upload to the GPU.
+    pub build_time: Duration,
+    pub storage: StorageKind,
+}
+
 pub const CHUNK_SIZE: usize = 16;
 
+/// Parameters of the density noise terrain generator.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeneratorConfig {
+    /// Frequency of the noise, smaller values give larger hills.
+    pub noise_scale: f64,
+    /// Height the terrain surface is centered around.
+    pub surface_level: f64,
+    /// How many blocks above or below the surface level the noise can push the terrain.
+    pub surface_falloff: f64,
+    /// Density above which a voxel is solid.
+    pub solid_threshold: f64,
+    /// Layers of dirt between the grass and the stone.
+    pub dirt_depth: usize,
+    /// Empty voxels below this height are filled with water.
+    pub water_level: f64,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        Self {
+            noise_scale: 0.06,
+            surface_level: 8.0,
+            surface_falloff: 12.0,
+            solid_threshold: 0.3,
+            dirt_depth: 3,
+            water_level: 6.0,
+        }
+    }
+}
+
+impl GeneratorConfig {
+    fn density(&self, perlin: &noise::Perlin, x: f64, y: f64, z: f64) -> f64 {
+        let scale = self.noise_scale;
+        perlin.get([x * scale, y * scale, z * scale]) + (self.surface_level - y) / self.surface_falloff
+    }
+}
+
 pub struct Chunk {
+    pub position: Vector3<i32>,
     pub voxels: [[[voxel::Voxel; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
 }
 
-struct ChunkIterator<'a> {
+pub struct ChunkIterator<'a> {
     chunk: &'a Chunk,
     x: usize,
     y: usize,
@@ -35,7 +199,11 @@ impl<'a> Iterator for ChunkIterator<'a> {
     type Item = (voxel::Voxel, usize, usize, usize);
 
     fn next(&mut self) -> Option<Self::Item> {
-        let voxel = self.chunk.voxels[self.x][self.y][self.z];
+        if self.z >= CHUNK_SIZE {
+            return None;
+        }
+
+        let item = (self.chunk.voxels[self.x][self.y][self.z], self.x, self.y, self.z);
         self.x += 1;
         if self.x >= CHUNK_SIZE {
             self.x = 0;
@@ -45,14 +213,14 @@ impl<'a> Iterator for ChunkIterator<'a> {
                 self.z += 1;
             }
         }
-        Some((voxel, self.x, self.y, self.z))
+        Some(item)
     }
 }
 
 impl Chunk {
-    fn iter_mut(&mut self) -> ChunkIterator {
+    pub fn iter(&self) -> ChunkIterator<'_> {
         ChunkIterator {
-            chunk: &mut self,
+            chunk: self,
             x: 0,
             y: 0,
             z: 0,
@@ -61,27 +229,213 @@ impl Chunk {
 }
 
 impl Chunk {
-    pub fn new() -> Self {
+    pub fn new(position: Vector3<i32>) -> Self {
         Self {
+            position,
             voxels: [[[voxel::Voxel::new_empty(); CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
         }
     }
 
-    pub fn build_voxels(&mut self) {
-        let perlin = noise::Perlin::new(484);
+    pub fn storage_kind(&self) -> StorageKind {
+        let first = self.voxels[0][0][0].block_type;
+        if self.iter().all(|(voxel, _, _, _)| voxel.block_type == first) {
+            StorageKind::Uniform
+        } else {
+            StorageKind::Dense
+        }
+    }
+
+    /// World space position of the chunk's minimum corner.
+    pub fn world_offset(&self) -> Vector3<f32> {
+        self.position.cast::<f32>().unwrap() * CHUNK_SIZE as f32
+    }
+
+    pub fn build_voxels(&mut self, seed: u32, config: &GeneratorConfig) {
+        let perlin = noise::Perlin::new(seed);
+        let offset = self.world_offset().cast::<f64>().unwrap();
 
-        for (voxel, x, y, z) in self.iter_mut() {
-            let density = perlin.get([x as f64, y as f64, z as f64]);
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    let world_x = offset.x + x as f64;
+                    let world_y = offset.y + y as f64;
+                    let world_z = offset.z + z as f64;
+
+                    let is_solid = |y: f64| config.density(&perlin, world_x, y, world_z) > config.solid_threshold;
+
+                    self.voxels[x][y][z].block_type = if !is_solid(world_y) {
+                        if world_y < config.water_level {
+                            voxel::BlockType::Water
+                        } else {
+                            voxel::BlockType::Empty
+                        }
+                    } else if !is_solid(world_y + 1.0) {
+                        voxel::BlockType::Grass
+                    } else if (2..=config.dirt_depth).any(|depth| !is_solid(world_y + depth as f64)) {
+                        voxel::BlockType::Dirt
+                    } else {
+                        voxel::BlockType::Stone
+                    };
+                }
+            }
+        }
+    }
 
-            voxel.block_type = if density > 0.3 {
-                voxel::BlockType::Grass
-            } else {
-                voxel::BlockType::Empty
-            };
+    /// Voxel at a position local to the chunk, positions outside of it are looked up in the neighboring chunks.
+    fn voxel_at(&self, world: &World, position: Vector3<i32>) -> voxel::Voxel {
+        let size = CHUNK_SIZE as i32;
+        if position.x < 0 || position.y < 0 || position.z < 0 || position.x >= size || position.y >= size || position.z >= size {
+            return world.get_voxel(self.position * size + position);
         }
+
+        self.voxels[position.x as usize][position.y as usize][position.z as usize]
     }
 }
 
-pub fn build_chunk_mesh(chunk: &mut ChunkMesh, device: &wgpu::Device) {
+/// Meshes opaque and cutout faces into `mesh` and translucent faces into `translucent_mesh`.
+pub fn build_chunk_mesh(world: &World, chunk: &Chunk, mesh: &mut ChunkMesh, translucent_mesh: &mut ChunkMesh, device: &wgpu::Device) -> ChunkStats {
+    let stats = mesh_chunk(world, chunk, mesh, translucent_mesh);
+
+    mesh.upload(device, chunk.world_offset());
+    translucent_mesh.upload(device, chunk.world_offset());
+
+    stats
+}
 
+/// The CPU half of `build_chunk_mesh`, fills the vertex and index lists without touching the GPU.
+pub fn mesh_chunk(world: &World, chunk: &Chunk, mesh: &mut ChunkMesh, translucent_mesh: &mut ChunkMesh) -> ChunkStats {
+    let start = Instant::now();
+
+    mesh.vertices.clear();
+    mesh.indices.clear();
+    translucent_mesh.vertices.clear();
+    translucent_mesh.indices.clear();
+
+    for (voxel, x, y, z) in chunk.iter() {
+        if voxel.is_empty() {
+            continue;
+        }
+
+        let position = Vector3::new(x as i32, y as i32, z as i32);
+        let color = voxel.color(chunk.position * CHUNK_SIZE as i32 + position, world.seed);
+        for direction in Direction::ALL {
+            if !voxel.is_face_visible(&chunk.voxel_at(world, position + direction.get_offset())) {
+                continue;
+            }
+
+            let center = (position.cast::<f32>().unwrap() + Vector3::new(0.5, 0.5, 0.5)) * voxel::SIZE;
+            let texture_layer = voxel.block_type.definition().texture_layer(direction);
+            let quad = Quad::new(direction, center, texture_layer, color);
+            match voxel.transparency() {
+                Transparency::Translucent => translucent_mesh.push_quad(&quad),
+                Transparency::Opaque | Transparency::Cutout => mesh.push_quad(&quad),
+            }
+        }
+    }
+
+    chunk_stats(chunk, mesh, translucent_mesh, start)
+}
+
+/// Like `mesh_chunk`, but merges neighboring faces of the same block and direction into larger
+/// quads, for exports where fewer faces matter more than per voxel tints. A merged face takes the
+/// tint of its first voxel.
+pub fn mesh_chunk_greedy(world: &World, chunk: &Chunk, mesh: &mut ChunkMesh, translucent_mesh: &mut ChunkMesh) -> ChunkStats {
+    let start = Instant::now();
+
+    mesh.vertices.clear();
+    mesh.indices.clear();
+    translucent_mesh.vertices.clear();
+    translucent_mesh.indices.clear();
+
+    for direction in Direction::ALL {
+        let offset = direction.get_offset();
+        let normal_axis = (0..3).find(|axis| offset[*axis] != 0).unwrap_or_default();
+        let (u, v) = ((normal_axis + 1) % 3, (normal_axis + 2) % 3);
+        let local = |layer: usize, a: usize, b: usize| {
+            let mut position = Vector3::new(0, 0, 0);
+            position[normal_axis] = layer as i32;
+            position[u] = a as i32;
+            position[v] = b as i32;
+            position
+        };
+
+        for layer in 0..CHUNK_SIZE {
+            // Blocks whose face in this direction is visible, cleared as they are merged.
+            let mut faces = [[None; CHUNK_SIZE]; CHUNK_SIZE];
+            for (a, row) in faces.iter_mut().enumerate() {
+                for (b, face) in row.iter_mut().enumerate() {
+                    let position = local(layer, a, b);
+                    let voxel = chunk.voxel_at(world, position);
+                    if !voxel.is_empty() && voxel.is_face_visible(&chunk.voxel_at(world, position + offset)) {
+                        *face = Some(voxel.block_type);
+                    }
+                }
+            }
+
+            for a in 0..CHUNK_SIZE {
+                for b in 0..CHUNK_SIZE {
+                    let Some(block_type) = faces[a][b] else {
+                        continue;
+                    };
+
+                    let width = (b..CHUNK_SIZE).take_while(|b| faces[a][*b] == Some(block_type)).count();
+                    let height = (a..CHUNK_SIZE).take_while(|a| faces[*a][b..b + width].iter().all(|face| *face == Some(block_type))).count();
+                    for row in &mut faces[a..a + height] {
+                        row[b..b + width].fill(None);
+                    }
+
+                    let position = local(layer, a, b);
+                    let voxel = voxel::Voxel::new(block_type);
+                    let color = voxel.color(chunk.position * CHUNK_SIZE as i32 + position, world.seed);
+                    let center = (position.cast::<f32>().unwrap() + Vector3::new(0.5, 0.5, 0.5)) * voxel::SIZE;
+                    let mut extents = Vector3::new(1, 1, 1);
+                    extents[u] = height as i32;
+                    extents[v] = width as i32;
+                    let quad = Quad::new(direction, center, block_type.definition().texture_layer(direction), color).stretched(extents);
+                    match voxel.transparency() {
+                        Transparency::Translucent => translucent_mesh.push_quad(&quad),
+                        Transparency::Opaque | Transparency::Cutout => mesh.push_quad(&quad),
+                    }
+                }
+            }
+        }
+    }
+
+    chunk_stats(chunk, mesh, translucent_mesh, start)
+}
+
+fn chunk_stats(chunk: &Chunk, mesh: &ChunkMesh, translucent_mesh: &ChunkMesh, start: Instant) -> ChunkStats {
+    ChunkStats {
+        vertex_count: mesh.vertices.len(),
+        index_count: mesh.indices.len(),
+        translucent_vertex_count: translucent_mesh.vertices.len(),
+        translucent_index_count: translucent_mesh.indices.len(),
+        build_time: start.elapsed(),
+        storage: chunk.storage_kind(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel_things::voxel::{BlockType, Voxel};
+
+    #[test]
+    fn indexes_chunks_with_more_than_u16_vertices() {
+        let position = Vector3::new(0, 0, 0);
+        let mut chunk = Chunk::new(position);
+        for voxel in chunk.voxels.iter_mut().flatten().flatten() {
+            *voxel = Voxel::new(BlockType::Leaves);
+        }
+        let mut world = World::new(0);
+        world.chunks.insert(position, chunk);
+
+        let (mut mesh, mut translucent_mesh) = (ChunkMesh::new(), ChunkMesh::new());
+        mesh_chunk(&world, &world.chunks[&position], &mut mesh, &mut translucent_mesh);
+        // Leaves show the faces between each other, six per voxel.
+        assert_eq!(mesh.vertices.len() + translucent_mesh.vertices.len(), CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE * 6 * 4);
+        for mesh in [&mesh, &translucent_mesh] {
+            assert!(mesh.indices.iter().all(|index| (*index as usize) < mesh.vertices.len()));
+        }
+    }
 }
@@ -1,3 +1,4 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Direction {
     Left,
     Right,
@@ -8,6 +9,15 @@ pub enum Direction {
 }
 
 impl Direction {
+    pub const ALL: [Direction; 6] = [
+        Direction::Left,
+        Direction::Right,
+        Direction::Down,
+        Direction::Up,
+        Direction::Back,
+        Direction::Forward,
+    ];
+
     pub fn get_normal(&self) -> cgmath::Vector3<f32> {
         match self {
             Direction::Left => -cgmath::Vector3::<f32>::unit_x(),
@@ -18,4 +28,15 @@ impl Direction {
             Direction::Forward => cgmath::Vector3::<f32>::unit_z(),
         }
     }
+
+    pub fn get_offset(&self) -> cgmath::Vector3<i32> {
+        match self {
+            Direction::Left => cgmath::Vector3::new(-1, 0, 0),
+            Direction::Right => cgmath::Vector3::new(1, 0, 0),
+            Direction::Down => cgmath::Vector3::new(0, -1, 0),
+            Direction::Up => cgmath::Vector3::new(0, 1, 0),
+            Direction::Back => cgmath::Vector3::new(0, 0, -1),
+            Direction::Forward => cgmath::Vector3::new(0, 0, 1),
+        }
+    }
 }
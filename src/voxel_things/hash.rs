@@ -0,0 +1,23 @@
+use cgmath::Vector3;
+
+/// Stable 32 bit hash of a voxel position, the same inputs give the same output on every run and
+/// platform.
+pub fn hash_position(position: Vector3<i32>, salt: u32, seed: u32) -> u32 {
+    let mut hash = seed ^ 0x9e37_79b9;
+    for value in [position.x as u32, position.y as u32, position.z as u32, salt] {
+        hash ^= value.wrapping_mul(0x85eb_ca6b);
+        hash = hash.rotate_left(13).wrapping_mul(5).wrapping_add(0xe654_6b64);
+    }
+
+    // murmur3 finalizer
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^ (hash >> 16)
+}
+
+/// `hash_position` mapped to `[0, 1)`.
+pub fn hash_position_unit(position: Vector3<i32>, salt: u32, seed: u32) -> f32 {
+    (hash_position(position, salt, seed) >> 8) as f32 / (1 << 24) as f32
+}
@@ -0,0 +1,241 @@
+use std::collections::VecDeque;
+
+use cgmath::Vector3;
+
+use super::voxel::Voxel;
+
+/// About 16 MiB worth of changes.
+pub const DEFAULT_MAX_CHANGES: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy)]
+pub struct VoxelChange {
+    pub position: Vector3<i32>,
+    pub old: Voxel,
+    pub new: Voxel,
+}
+
+/// The changes made by one user action, in the order they were made.
+#[derive(Debug, Clone, Default)]
+pub struct Transaction {
+    pub changes: Vec<VoxelChange>,
+}
+
+/// Undo and redo stacks of world edits. Edits made between `begin` and `end` form a single
+/// transaction, any other edit is a transaction of its own.
+#[derive(Debug)]
+pub struct History {
+    undo: VecDeque<Transaction>,
+    redo: Vec<Transaction>,
+    open: Option<Transaction>,
+    /// How many `begin` calls are waiting for their `end`, so grouped actions can nest.
+    depth: usize,
+    /// Changes kept across both stacks before the oldest transactions are dropped.
+    pub max_changes: usize,
+    stored_changes: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_CHANGES)
+    }
+}
+
+impl History {
+    pub fn new(max_changes: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            open: None,
+            depth: 0,
+            max_changes,
+            stored_changes: 0,
+        }
+    }
+
+    /// Starts grouping edits into one transaction.
+    pub fn begin(&mut self) {
+        self.depth += 1;
+        if self.open.is_none() {
+            self.open = Some(Transaction::default());
+        }
+    }
+
+    /// Ends the group started by the matching `begin`.
+    pub fn end(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+        if self.depth == 0 {
+            if let Some(transaction) = self.open.take() {
+                self.commit(transaction);
+            }
+        }
+    }
+
+    /// Records changes the world just made.
+    pub fn record(&mut self, changes: Vec<VoxelChange>) {
+        if changes.is_empty() {
+            return;
+        }
+
+        match &mut self.open {
+            Some(transaction) => transaction.changes.extend(changes),
+            None => self.commit(Transaction { changes }),
+        }
+    }
+
+    fn commit(&mut self, transaction: Transaction) {
+        if transaction.changes.is_empty() {
+            return;
+        }
+
+        self.stored_changes -= self.redo.drain(..).map(|transaction| transaction.changes.len()).sum::<usize>();
+
+        // Older transactions can't be undone past one that wasn't kept, so a transaction too big to
+        // keep empties the history.
+        if transaction.changes.len() > self.max_changes {
+            log::warn!("edit of {} voxels is too big to undo", transaction.changes.len());
+            self.clear();
+            return;
+        }
+
+        self.stored_changes += transaction.changes.len();
+        self.undo.push_back(transaction);
+        while self.stored_changes > self.max_changes {
+            let Some(oldest) = self.undo.pop_front() else {
+                break;
+            };
+            self.stored_changes -= oldest.changes.len();
+        }
+    }
+
+    /// Forgets everything, e.g. when the world is generated again and the recorded changes no
+    /// longer apply.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        if let Some(transaction) = &mut self.open {
+            transaction.changes.clear();
+        }
+        self.stored_changes = 0;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Number of transactions that can be undone.
+    pub fn undo_count(&self) -> usize {
+        self.undo.len()
+    }
+
+    pub fn redo_count(&self) -> usize {
+        self.redo.len()
+    }
+
+    pub fn stored_changes(&self) -> usize {
+        self.stored_changes
+    }
+
+    /// Takes the newest transaction to undo, the caller reverts it and hands it to `push_redo`.
+    pub(super) fn pop_undo(&mut self) -> Option<Transaction> {
+        self.undo.pop_back()
+    }
+
+    pub(super) fn push_redo(&mut self, transaction: Transaction) {
+        self.redo.push(transaction);
+    }
+
+    /// Takes the newest undone transaction, the caller applies it again and hands it to
+    /// `push_undo`.
+    pub(super) fn pop_redo(&mut self) -> Option<Transaction> {
+        self.redo.pop()
+    }
+
+    pub(super) fn push_undo(&mut self, transaction: Transaction) {
+        self.undo.push_back(transaction);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel_things::{chunk::Chunk, voxel::BlockType, world::World};
+
+    fn changes(count: usize) -> Vec<VoxelChange> {
+        (0..count as i32)
+            .map(|x| VoxelChange {
+                position: Vector3::new(x, 0, 0),
+                old: Voxel::new_empty(),
+                new: Voxel::new(BlockType::Stone),
+            })
+            .collect()
+    }
+
+    fn world() -> World {
+        let mut world = World::new(0);
+        world.chunks.insert(Vector3::new(0, 0, 0), Chunk::new(Vector3::new(0, 0, 0)));
+        world
+    }
+
+    #[test]
+    fn drops_the_oldest_transactions_at_the_cap() {
+        let mut history = History::new(10);
+        for count in [4, 4, 4] {
+            history.record(changes(count));
+        }
+        assert_eq!(history.undo_count(), 2);
+        assert_eq!(history.stored_changes(), 8);
+
+        // Too big to keep at all, nothing older can be undone past it.
+        history.record(changes(11));
+        assert_eq!(history.undo_count(), 0);
+        assert_eq!(history.stored_changes(), 0);
+    }
+
+    #[test]
+    fn empty_groups_record_nothing() {
+        let mut history = History::new(10);
+        history.begin();
+        history.begin();
+        history.end();
+        history.end();
+        history.record(Vec::new());
+        assert!(!history.can_undo());
+
+        history.begin();
+        history.record(changes(2));
+        history.record(changes(3));
+        history.end();
+        assert_eq!(history.undo_count(), 1);
+        assert_eq!(history.stored_changes(), 5);
+    }
+
+    #[test]
+    fn undoes_and_redoes_in_order() {
+        let mut world = world();
+        let (first, second) = (Vector3::new(1, 1, 1), Vector3::new(2, 2, 2));
+        world.set_voxel(first, Voxel::new(BlockType::Stone));
+        world.set_voxel(second, Voxel::new(BlockType::Dirt));
+
+        world.undo().unwrap();
+        assert!(world.get_voxel(second).is_empty());
+        assert_eq!(world.get_voxel(first).block_type, BlockType::Stone);
+        world.undo().unwrap();
+        assert!(world.get_voxel(first).is_empty());
+        assert!(world.undo().is_none());
+
+        world.redo().unwrap();
+        assert_eq!(world.get_voxel(first).block_type, BlockType::Stone);
+        assert!(world.get_voxel(second).is_empty());
+        assert_eq!(world.history.redo_count(), 1);
+
+        // A new edit throws away what was undone.
+        world.set_voxel(Vector3::new(3, 3, 3), Voxel::new(BlockType::Glass));
+        assert!(!world.history.can_redo());
+        assert!(world.redo().is_none());
+        assert_eq!(world.history.undo_count(), 2);
+    }
+}
@@ -1,17 +1,18 @@
 use super::{voxel, direction::Direction};
 use cgmath::Vector3;
-use rand::Rng;
 
 pub struct Quad {
     pub corners: [Vector3<f32>; 4],
+    pub uvs: [[f32; 2]; 4],
     pub color: [f32; 3],
+    pub texture_layer: u32,
     pub direction: Direction,
 }
 
 const HALF_SIZE: f32 = voxel::SIZE / 2.0;
 
 impl Quad {
-    pub fn new(direction: Direction, pos: Vector3<f32>) -> Self {
+    pub fn new(direction: Direction, pos: Vector3<f32>, texture_layer: u32, color: [f32; 3]) -> Self {
         let corners = match direction {
             Direction::Left => [
                 Vector3::new(pos.x - HALF_SIZE, pos.y - HALF_SIZE, pos.z - HALF_SIZE),
@@ -20,10 +21,10 @@ impl Quad {
                 Vector3::new(pos.x - HALF_SIZE, pos.y + HALF_SIZE, pos.z - HALF_SIZE),
             ],
             Direction::Right => [
-                Vector3::new(pos.x - HALF_SIZE, pos.y + HALF_SIZE, pos.z - HALF_SIZE),
-                Vector3::new(pos.x - HALF_SIZE, pos.y + HALF_SIZE, pos.z + HALF_SIZE),
-                Vector3::new(pos.x - HALF_SIZE, pos.y - HALF_SIZE, pos.z + HALF_SIZE),
-                Vector3::new(pos.x - HALF_SIZE, pos.y - HALF_SIZE, pos.z - HALF_SIZE),
+                Vector3::new(pos.x + HALF_SIZE, pos.y + HALF_SIZE, pos.z - HALF_SIZE),
+                Vector3::new(pos.x + HALF_SIZE, pos.y + HALF_SIZE, pos.z + HALF_SIZE),
+                Vector3::new(pos.x + HALF_SIZE, pos.y - HALF_SIZE, pos.z + HALF_SIZE),
+                Vector3::new(pos.x + HALF_SIZE, pos.y - HALF_SIZE, pos.z - HALF_SIZE),
             ],
             Direction::Down => [
                 Vector3::new(pos.x - HALF_SIZE, pos.y - HALF_SIZE, pos.z - HALF_SIZE),
@@ -32,10 +33,10 @@ impl Quad {
                 Vector3::new(pos.x - HALF_SIZE, pos.y - HALF_SIZE, pos.z + HALF_SIZE),
             ],
             Direction::Up => [
-                Vector3::new(pos.x - HALF_SIZE, pos.y - HALF_SIZE, pos.z + HALF_SIZE),
-                Vector3::new(pos.x + HALF_SIZE, pos.y - HALF_SIZE, pos.z + HALF_SIZE),
-                Vector3::new(pos.x + HALF_SIZE, pos.y - HALF_SIZE, pos.z - HALF_SIZE),
-                Vector3::new(pos.x - HALF_SIZE, pos.y - HALF_SIZE, pos.z - HALF_SIZE),
+                Vector3::new(pos.x - HALF_SIZE, pos.y + HALF_SIZE, pos.z + HALF_SIZE),
+                Vector3::new(pos.x + HALF_SIZE, pos.y + HALF_SIZE, pos.z + HALF_SIZE),
+                Vector3::new(pos.x + HALF_SIZE, pos.y + HALF_SIZE, pos.z - HALF_SIZE),
+                Vector3::new(pos.x - HALF_SIZE, pos.y + HALF_SIZE, pos.z - HALF_SIZE),
             ],
             Direction::Back => [
                 Vector3::new(pos.x - HALF_SIZE, pos.y - HALF_SIZE, pos.z - HALF_SIZE),
@@ -44,20 +45,46 @@ impl Quad {
                 Vector3::new(pos.x + HALF_SIZE, pos.y - HALF_SIZE, pos.z - HALF_SIZE),
             ],
             Direction::Forward => [
-                Vector3::new(pos.x + HALF_SIZE, pos.y - HALF_SIZE, pos.z - HALF_SIZE),
-                Vector3::new(pos.x + HALF_SIZE, pos.y + HALF_SIZE, pos.z - HALF_SIZE),
-                Vector3::new(pos.x - HALF_SIZE, pos.y + HALF_SIZE, pos.z - HALF_SIZE),
-                Vector3::new(pos.x - HALF_SIZE, pos.y - HALF_SIZE, pos.z - HALF_SIZE),
+                Vector3::new(pos.x + HALF_SIZE, pos.y - HALF_SIZE, pos.z + HALF_SIZE),
+                Vector3::new(pos.x + HALF_SIZE, pos.y + HALF_SIZE, pos.z + HALF_SIZE),
+                Vector3::new(pos.x - HALF_SIZE, pos.y + HALF_SIZE, pos.z + HALF_SIZE),
+                Vector3::new(pos.x - HALF_SIZE, pos.y - HALF_SIZE, pos.z + HALF_SIZE),
             ],
         };
 
-        let green = 0.4;
-        let color = [
-            rand::thread_rng().gen_range(0f32..0.1f32),
-            (1.0 - green + rand::thread_rng().gen_range(0f32..1.0 - green)),
-            rand::thread_rng().gen_range(0f32..0.1f32),
-        ];
+        // Keeps the texture upright on the side faces, v grows downwards.
+        let uvs = match direction {
+            Direction::Left | Direction::Down | Direction::Up => [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]],
+            Direction::Right => [[1.0, 0.0], [0.0, 0.0], [0.0, 1.0], [1.0, 1.0]],
+            Direction::Back | Direction::Forward => [[1.0, 1.0], [1.0, 0.0], [0.0, 0.0], [0.0, 1.0]],
+        };
+
+        Self { corners, uvs, color, texture_layer, direction }
+    }
+
+    /// Grows the face of one voxel over `extents` voxels along each axis, 1 along its normal. The
+    /// texture repeats once per voxel.
+    pub fn stretched(mut self, extents: Vector3<i32>) -> Self {
+        let center = self.corners.iter().sum::<Vector3<f32>>() / 4.0;
+        // Consecutive corners share an edge, along which only one axis and one UV component change.
+        let mut uv_axes = [0; 2];
+        for (a, b) in [(0, 1), (1, 2)] {
+            let component = if self.uvs[a][0] != self.uvs[b][0] { 0 } else { 1 };
+            uv_axes[component] = (0..3).find(|axis| self.corners[a][*axis] != self.corners[b][*axis]).unwrap_or_default();
+        }
 
-        Self { corners, color, direction }
+        for corner in &mut self.corners {
+            for axis in 0..3 {
+                if corner[axis] > center[axis] {
+                    corner[axis] += (extents[axis] - 1) as f32 * voxel::SIZE;
+                }
+            }
+        }
+        for uv in &mut self.uvs {
+            for (component, axis) in uv_axes.iter().enumerate() {
+                uv[component] *= extents[*axis] as f32;
+            }
+        }
+        self
     }
 }
@@ -0,0 +1,152 @@
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3};
+
+use super::{direction::Direction, voxel::Voxel, world::World};
+
+#[derive(Debug, Clone, Copy)]
+pub struct RaycastHit {
+    /// World position of the voxel that was hit.
+    pub position: Vector3<i32>,
+    pub voxel: Voxel,
+    /// Face of the hit voxel the ray entered through.
+    pub face: Direction,
+    /// Distance along the ray from the origin to the hit face.
+    pub distance: f32,
+    /// The cell the ray passed through right before the hit, where a new block would be placed.
+    pub previous: Vector3<i32>,
+}
+
+/// Walks the voxel grid cell by cell (Amanatides & Woo DDA) from `origin` along `direction` and
+/// returns the first voxel for which `is_hit` is true within `max_distance`. The cell containing
+/// `origin` is never reported. Non-finite inputs miss, the walk would never end otherwise.
+pub fn raycast(world: &World, origin: Point3<f32>, direction: Vector3<f32>, max_distance: f32, is_hit: impl Fn(&Voxel) -> bool) -> Option<RaycastHit> {
+    let finite = |vector: Vector3<f32>| vector.x.is_finite() && vector.y.is_finite() && vector.z.is_finite();
+    if !max_distance.is_finite() || !finite(origin.to_vec()) || !finite(direction) || direction.magnitude2() == 0.0 {
+        return None;
+    }
+    let direction = direction.normalize();
+
+    let mut cell = Vector3::new(origin.x.floor() as i32, origin.y.floor() as i32, origin.z.floor() as i32);
+    let step = direction.map(|value| if value > 0.0 { 1 } else if value < 0.0 { -1 } else { 0 });
+    let t_delta = direction.map(|value| if value == 0.0 { f32::INFINITY } else { 1.0 / value.abs() });
+
+    let mut t_max = Vector3::new(0.0, 0.0, 0.0);
+    for axis in 0..3 {
+        let boundary = if step[axis] > 0 { cell[axis] + 1 } else { cell[axis] } as f32;
+        t_max[axis] = if step[axis] == 0 {
+            f32::INFINITY
+        } else {
+            (boundary - origin[axis]) / direction[axis]
+        };
+    }
+
+    loop {
+        let axis = if t_max.x < t_max.y {
+            if t_max.x < t_max.z {
+                0
+            } else {
+                2
+            }
+        } else if t_max.y < t_max.z {
+            1
+        } else {
+            2
+        };
+
+        let distance = t_max[axis];
+        if distance > max_distance {
+            return None;
+        }
+
+        let previous = cell;
+        cell[axis] = cell[axis].checked_add(step[axis])?;
+        t_max[axis] += t_delta[axis];
+
+        let voxel = world.get_voxel(cell);
+        if is_hit(&voxel) {
+            let face = match (axis, step[axis] > 0) {
+                (0, true) => Direction::Left,
+                (0, false) => Direction::Right,
+                (1, true) => Direction::Down,
+                (1, false) => Direction::Up,
+                (_, true) => Direction::Back,
+                (_, false) => Direction::Forward,
+            };
+
+            return Some(RaycastHit {
+                position: cell,
+                voxel,
+                face,
+                distance,
+                previous,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel_things::{chunk::Chunk, voxel::BlockType};
+
+    const CENTER: Point3<f32> = Point3::new(8.5, 8.5, 8.5);
+
+    /// An empty chunk at the origin with stone at `solid`.
+    fn world_with(solid: &[Vector3<i32>]) -> World {
+        let mut world = World::new(0);
+        world.chunks.insert(Vector3::new(0, 0, 0), Chunk::new(Vector3::new(0, 0, 0)));
+        for position in solid {
+            assert!(world.set_voxel(*position, Voxel::new(BlockType::Stone)));
+        }
+        world
+    }
+
+    fn solid(voxel: &Voxel) -> bool {
+        !voxel.is_empty()
+    }
+
+    #[test]
+    fn hits_along_every_axis() {
+        let faces = [(0, Direction::Left, Direction::Right), (1, Direction::Down, Direction::Up), (2, Direction::Back, Direction::Forward)];
+        for (axis, positive_face, negative_face) in faces {
+            for (sign, face) in [(1, positive_face), (-1, negative_face)] {
+                let mut step = Vector3::new(0, 0, 0);
+                step[axis] = sign;
+                let target = Vector3::new(8, 8, 8) + step * 3;
+                let world = world_with(&[target]);
+
+                let hit = raycast(&world, CENTER, step.map(|value| value as f32), 10.0, solid).unwrap();
+                assert_eq!(hit.position, target);
+                assert_eq!(hit.face, face);
+                assert_eq!(hit.previous, target - step);
+                assert!((hit.distance - 2.5).abs() < 1e-5, "{}", hit.distance);
+            }
+        }
+    }
+
+    #[test]
+    fn stops_at_max_distance() {
+        let world = world_with(&[Vector3::new(11, 8, 8)]);
+        let direction = Vector3::unit_x();
+        assert!(raycast(&world, CENTER, direction, 2.0, solid).is_none());
+        assert!(raycast(&world, CENTER, direction, 3.0, solid).is_some());
+    }
+
+    #[test]
+    fn skips_the_starting_voxel() {
+        let world = world_with(&[Vector3::new(8, 8, 8), Vector3::new(10, 8, 8)]);
+        let hit = raycast(&world, CENTER, Vector3::unit_x(), 10.0, solid).unwrap();
+        assert_eq!(hit.position, Vector3::new(10, 8, 8));
+        assert_eq!(hit.previous, Vector3::new(9, 8, 8));
+    }
+
+    #[test]
+    fn misses_with_degenerate_input() {
+        let world = world_with(&[Vector3::new(9, 8, 8)]);
+        assert!(raycast(&world, CENTER, Vector3::new(0.0, 0.0, 0.0), 10.0, solid).is_none());
+        assert!(raycast(&world, CENTER, Vector3::new(f32::NAN, 0.0, 0.0), 10.0, solid).is_none());
+        assert!(raycast(&world, CENTER, Vector3::new(f32::INFINITY, 0.0, 0.0), 10.0, solid).is_none());
+        assert!(raycast(&world, Point3::new(f32::NAN, 8.5, 8.5), Vector3::unit_x(), 10.0, solid).is_none());
+        assert!(raycast(&world, CENTER, Vector3::unit_x(), f32::INFINITY, |_| false).is_none());
+        assert!(raycast(&world, CENTER, Vector3::unit_x(), f32::NAN, solid).is_none());
+    }
+}
@@ -0,0 +1,249 @@
+use cgmath::Vector3;
+
+use super::{
+    voxel::{BlockType, Voxel},
+    world::{EditSummary, World},
+};
+
+/// An axis-aligned box of voxels, both corners are inside it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub min: Vector3<i32>,
+    pub max: Vector3<i32>,
+}
+
+impl Region {
+    /// The box between two opposite corners, in any order.
+    pub fn new(a: Vector3<i32>, b: Vector3<i32>) -> Self {
+        Self {
+            min: Vector3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            max: Vector3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+        }
+    }
+
+    /// Number of voxels along each axis. Check `volume` first for regions that come from the
+    /// player, an axis spanning more than `i32::MAX` voxels overflows.
+    pub fn size(&self) -> Vector3<i32> {
+        self.max - self.min + Vector3::new(1, 1, 1)
+    }
+
+    /// Number of voxels in the region, computed in `i64` so it works for any corners and
+    /// saturating at `i64::MAX`.
+    pub fn volume(&self) -> i64 {
+        (0..3).map(|axis| self.max[axis] as i64 - self.min[axis] as i64 + 1).fold(1, i64::saturating_mul)
+    }
+
+    pub fn contains(&self, position: Vector3<i32>) -> bool {
+        (0..3).all(|axis| self.min[axis] <= position[axis] && position[axis] <= self.max[axis])
+    }
+
+    /// Whether `position` is one of the outermost voxels of the region.
+    pub fn is_on_shell(&self, position: Vector3<i32>) -> bool {
+        self.contains(position) && (0..3).any(|axis| position[axis] == self.min[axis] || position[axis] == self.max[axis])
+    }
+
+    /// Like `is_on_shell`, but the floor and ceiling don't count.
+    pub fn is_on_walls(&self, position: Vector3<i32>) -> bool {
+        self.contains(position) && [0, 2].into_iter().any(|axis| position[axis] == self.min[axis] || position[axis] == self.max[axis])
+    }
+
+    /// Every position in the region, x changing slowest and z fastest.
+    pub fn positions(&self) -> impl Iterator<Item = Vector3<i32>> {
+        let (min, max) = (self.min, self.max);
+        (min.x..=max.x).flat_map(move |x| (min.y..=max.y).flat_map(move |y| (min.z..=max.z).map(move |z| Vector3::new(x, y, z))))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    fn index(&self) -> usize {
+        match self {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2,
+        }
+    }
+}
+
+/// Sets every voxel in `region` to `voxel`.
+pub fn fill(world: &mut World, region: Region, voxel: Voxel) -> EditSummary {
+    world.set_voxels(region.positions().map(|position| (position, voxel)))
+}
+
+/// Puts `voxel` on the outside of `region` and empties the inside.
+pub fn hollow(world: &mut World, region: Region, voxel: Voxel) -> EditSummary {
+    world.set_voxels(region.positions().map(|position| {
+        if region.is_on_shell(position) {
+            (position, voxel)
+        } else {
+            (position, Voxel::new_empty())
+        }
+    }))
+}
+
+/// Puts `voxel` on the four vertical sides of `region`, leaving the rest alone.
+pub fn walls(world: &mut World, region: Region, voxel: Voxel) -> EditSummary {
+    world.set_voxels(region.positions().filter(|position| region.is_on_walls(*position)).map(|position| (position, voxel)))
+}
+
+/// Turns every `from` block in `region` into `to`.
+pub fn replace(world: &mut World, region: Region, from: BlockType, to: Voxel) -> EditSummary {
+    let edits = region.positions().filter(|position| world.get_voxel(*position).block_type == from).map(|position| (position, to)).collect::<Vec<_>>();
+    world.set_voxels(edits)
+}
+
+/// Voxels copied out of the world, positioned relative to the copied region's minimum corner.
+#[derive(Debug, Clone)]
+pub struct Clipboard {
+    size: Vector3<i32>,
+    voxels: Vec<Voxel>,
+}
+
+impl Clipboard {
+    /// Most voxels a clipboard built from a file can hold.
+    pub const MAX_VOLUME: usize = 1 << 24;
+
+    pub fn copy(world: &World, region: Region) -> Self {
+        Self::copy_masked(world, region, |_| true)
+    }
+
+    /// Copies `region`, leaving positions `include` rejects empty.
+    pub fn copy_masked(world: &World, region: Region, include: impl Fn(Vector3<i32>) -> bool) -> Self {
+        Self {
+            size: region.size(),
+            voxels: region.positions().map(|position| if include(position) { world.get_voxel(position) } else { Voxel::new_empty() }).collect(),
+        }
+    }
+
+    /// A clipboard of `size` from voxels in the order `Region::positions` visits them, `None` if
+    /// the counts don't match or it would hold more than `MAX_VOLUME` voxels.
+    pub fn from_voxels(size: Vector3<i32>, voxels: Vec<Voxel>) -> Option<Self> {
+        let expected = (0..3).try_fold(1usize, |volume, axis| volume.checked_mul(usize::try_from(size[axis]).ok()?))?;
+        (expected == voxels.len() && expected > 0 && expected <= Self::MAX_VOLUME).then_some(Self { size, voxels })
+    }
+
+    pub fn size(&self) -> Vector3<i32> {
+        self.size
+    }
+
+    /// Every voxel, in the order `Region::positions` visits them.
+    pub fn voxels(&self) -> &[Voxel] {
+        &self.voxels
+    }
+
+    fn index(&self, position: Vector3<i32>) -> usize {
+        ((position.x * self.size.y + position.y) * self.size.z + position.z) as usize
+    }
+
+    /// Voxel at a position relative to the minimum corner.
+    pub fn get(&self, position: Vector3<i32>) -> Voxel {
+        self.voxels[self.index(position)]
+    }
+
+    /// Every position relative to the minimum corner, in the order of `voxels`.
+    pub fn positions(&self) -> impl Iterator<Item = Vector3<i32>> {
+        Region::new(Vector3::new(0, 0, 0), self.size - Vector3::new(1, 1, 1)).positions()
+    }
+
+    /// Builds a clipboard of `size` where every position takes the voxel `source` maps it back to.
+    fn remap(&self, size: Vector3<i32>, source: impl Fn(Vector3<i32>) -> Vector3<i32>) -> Self {
+        let mut clipboard = Self {
+            size,
+            voxels: Vec::with_capacity(self.voxels.len()),
+        };
+        for position in clipboard.positions().collect::<Vec<_>>() {
+            clipboard.voxels.push(self.get(source(position)));
+        }
+        clipboard
+    }
+
+    /// Rotated around the vertical axis by `quarter_turns` times 90 degrees, clockwise when seen
+    /// from above. Negative turns rotate counterclockwise.
+    pub fn rotated(&self, quarter_turns: i32) -> Self {
+        let size = self.size;
+        match quarter_turns.rem_euclid(4) {
+            0 => self.clone(),
+            1 => self.remap(Vector3::new(size.z, size.y, size.x), |p| Vector3::new(p.z, p.y, size.z - 1 - p.x)),
+            2 => self.remap(size, |p| Vector3::new(size.x - 1 - p.x, p.y, size.z - 1 - p.z)),
+            _ => self.remap(Vector3::new(size.z, size.y, size.x), |p| Vector3::new(size.x - 1 - p.z, p.y, p.x)),
+        }
+    }
+
+    /// Flipped along `axis`.
+    pub fn mirrored(&self, axis: Axis) -> Self {
+        let axis = axis.index();
+        let size = self.size;
+        self.remap(size, |mut p| {
+            p[axis] = size[axis] - 1 - p[axis];
+            p
+        })
+    }
+
+    /// Writes the clipboard with its minimum corner at `origin`. Empty voxels only overwrite the
+    /// world when `include_empty` is set, so pasted shapes can be merged into existing terrain.
+    pub fn paste(&self, world: &mut World, origin: Vector3<i32>, include_empty: bool) -> EditSummary {
+        world.set_voxels(
+            self.positions()
+                .map(|position| (origin + position, self.get(position)))
+                .filter(|(_, voxel)| include_empty || !voxel.is_empty()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 3x2x5 clipboard where every voxel's block depends on its position.
+    fn clipboard() -> Clipboard {
+        let size = Vector3::new(3, 2, 5);
+        let voxels = Region::new(Vector3::new(0, 0, 0), size - Vector3::new(1, 1, 1)).positions().map(|p| Voxel::new(BlockType::ALL[((p.x + 2 * p.y + 3 * p.z) % 7) as usize])).collect();
+        Clipboard::from_voxels(size, voxels).unwrap()
+    }
+
+    fn block_types(clipboard: &Clipboard) -> Vec<BlockType> {
+        clipboard.voxels().iter().map(|voxel| voxel.block_type).collect()
+    }
+
+    #[test]
+    fn four_rotations_are_the_identity() {
+        let original = clipboard();
+        let mut rotated = original.clone();
+        for _ in 0..4 {
+            rotated = rotated.rotated(1);
+        }
+        assert_eq!(rotated.size(), original.size());
+        assert_eq!(block_types(&rotated), block_types(&original));
+        assert_eq!(block_types(&original.rotated(-1)), block_types(&original.rotated(3)));
+        assert_eq!(block_types(&original.rotated(2).rotated(2)), block_types(&original));
+    }
+
+    #[test]
+    fn mirroring_twice_is_the_identity() {
+        let original = clipboard();
+        for axis in [Axis::X, Axis::Y, Axis::Z] {
+            let mirrored = original.mirrored(axis);
+            assert_ne!(block_types(&mirrored), block_types(&original));
+            assert_eq!(block_types(&mirrored.mirrored(axis)), block_types(&original));
+        }
+    }
+
+    #[test]
+    fn rotation_swaps_the_horizontal_sides() {
+        let original = clipboard();
+        let rotated = original.rotated(1);
+        assert_eq!(rotated.size(), Vector3::new(5, 2, 3));
+        assert_eq!(original.rotated(2).size(), original.size());
+        // Clockwise from above, x becomes z and z becomes x running the other way.
+        for p in original.positions() {
+            let turned = Vector3::new(original.size().z - 1 - p.z, p.y, p.x);
+            assert_eq!(rotated.get(turned).block_type, original.get(p).block_type);
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use cgmath::{InnerSpace, Vector2, Vector3};
+
+use super::{
+    region::{Clipboard, Region},
+    world::World,
+};
+
+/// A volume of voxels picked in the editor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selection {
+    Box(Region),
+    /// Upright prism over a polygon on the horizontal plane. `points` are the x and z of the
+    /// picked voxels, in order.
+    Polygon { points: Vec<Vector2<i32>>, min_y: i32, max_y: i32 },
+}
+
+impl Selection {
+    /// Prism through picked voxels, spanning the heights between the lowest and highest of them.
+    /// Needs at least three points.
+    pub fn polygon(picked: &[Vector3<i32>]) -> Option<Self> {
+        if picked.len() < 3 {
+            return None;
+        }
+
+        Some(Selection::Polygon {
+            points: picked.iter().map(|point| Vector2::new(point.x, point.z)).collect(),
+            min_y: picked.iter().map(|point| point.y).min()?,
+            max_y: picked.iter().map(|point| point.y).max()?,
+        })
+    }
+
+    /// The smallest box around the selection.
+    pub fn bounds(&self) -> Region {
+        match self {
+            Selection::Box(region) => *region,
+            Selection::Polygon { points, min_y, max_y } => {
+                let min_x = points.iter().map(|point| point.x).min().unwrap_or_default();
+                let max_x = points.iter().map(|point| point.x).max().unwrap_or_default();
+                let min_z = points.iter().map(|point| point.y).min().unwrap_or_default();
+                let max_z = points.iter().map(|point| point.y).max().unwrap_or_default();
+                Region::new(Vector3::new(min_x, *min_y, min_z), Vector3::new(max_x, *max_y, max_z))
+            }
+        }
+    }
+
+    /// Polygons include voxels whose center is inside the outline and every voxel the outline
+    /// passes through, so the picked voxels themselves are always selected.
+    pub fn contains(&self, position: Vector3<i32>) -> bool {
+        match self {
+            Selection::Box(region) => region.contains(position),
+            Selection::Polygon { points, min_y, max_y } => {
+                if position.y < *min_y || position.y > *max_y {
+                    return false;
+                }
+
+                let point = Vector2::new(position.x as f32, position.z as f32);
+                let edges = points.iter().zip(points.iter().cycle().skip(1)).map(|(a, b)| (a.cast::<f32>().unwrap(), b.cast::<f32>().unwrap()));
+                let mut inside = false;
+                for (a, b) in edges {
+                    if distance_to_segment(point, a, b) <= 0.5 {
+                        return true;
+                    }
+                    // Even-odd rule, counting edges crossed by a ray towards +x.
+                    if (a.y > point.y) != (b.y > point.y) && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x) {
+                        inside = !inside;
+                    }
+                }
+                inside
+            }
+        }
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = Vector3<i32>> + '_ {
+        self.bounds().positions().filter(|position| self.contains(*position))
+    }
+
+    /// Copies the selection, voxels inside its bounds but outside of it stay empty.
+    pub fn copy(&self, world: &World) -> Clipboard {
+        Clipboard::copy_masked(world, self.bounds(), |position| self.contains(position))
+    }
+}
+
+fn distance_to_segment(point: Vector2<f32>, a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+    let segment = b - a;
+    let length2 = segment.magnitude2();
+    let t = if length2 == 0.0 { 0.0 } else { ((point - a).dot(segment) / length2).clamp(0.0, 1.0) };
+    (point - (a + segment * t)).magnitude()
+}
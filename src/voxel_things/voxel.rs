@@ -1,11 +1,53 @@
-use crate::{render_utilities, texture};
+use crate::{render_utilities, texture, InstanceRaw};
 
-use super::{vertex::Vertex, vertex_desc::VertexDesc};
+use cgmath::Vector3;
 
-#[derive(Debug, Clone, Copy)]
+use super::{block, hash, vertex::Vertex, vertex_desc::VertexDesc};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BlockType {
     Empty,
     Grass,
+    Dirt,
+    Stone,
+    Water,
+    Glass,
+    Leaves,
+}
+
+impl BlockType {
+    pub const ALL: [BlockType; 7] = [
+        BlockType::Empty,
+        BlockType::Grass,
+        BlockType::Dirt,
+        BlockType::Stone,
+        BlockType::Water,
+        BlockType::Glass,
+        BlockType::Leaves,
+    ];
+
+    /// Looks a block type up by its definition's name, e.g. "stone".
+    pub fn from_name(name: &str) -> Option<BlockType> {
+        Self::ALL.into_iter().find(|block_type| block_type.definition().name == name)
+    }
+
+    /// The non-empty block whose color is closest to `color`.
+    pub fn nearest_to_color(color: [u8; 3]) -> BlockType {
+        let distance = |block_type: &BlockType| block_type.definition().color.iter().zip(color).map(|(a, b)| (*a as i32 - b as i32).pow(2)).sum::<i32>();
+        Self::ALL.into_iter().filter(|block_type| *block_type != BlockType::Empty).min_by_key(distance).expect("there are non-empty blocks")
+    }
+
+    pub fn definition(&self) -> &'static block::BlockDefinition {
+        match self {
+            BlockType::Empty => &block::EMPTY,
+            BlockType::Grass => &block::GRASS,
+            BlockType::Dirt => &block::DIRT,
+            BlockType::Stone => &block::STONE,
+            BlockType::Water => &block::WATER,
+            BlockType::Glass => &block::GLASS,
+            BlockType::Leaves => &block::LEAVES,
+        }
+    }
 }
 
 pub const SIZE: f32 = 1.0;
@@ -16,19 +58,62 @@ pub struct Voxel {
 }
 
 impl Voxel {
+    pub fn new(block_type: BlockType) -> Self {
+        Self { block_type }
+    }
+
     pub fn new_empty() -> Self {
         Self {
             block_type: BlockType::Empty,
         }
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.block_type == BlockType::Empty
+    }
+
+    pub fn transparency(&self) -> block::Transparency {
+        self.block_type.definition().transparency
+    }
+
+    /// Tint of this voxel at `world_position`, derived from a hash so meshes are identical on every run.
+    pub fn color(&self, world_position: Vector3<i32>, seed: u32) -> [f32; 3] {
+        let variation = self.block_type.definition().color_variation;
+        let brightness = 1.0 - variation * hash::hash_position_unit(world_position, self.block_type as u32, seed);
+        [brightness, brightness, brightness]
+    }
+
+    /// Whether the face of this voxel that touches `neighbor` has to be meshed.
+    pub fn is_face_visible(&self, neighbor: &Voxel) -> bool {
+        if neighbor.is_empty() {
+            return true;
+        }
+
+        match neighbor.transparency() {
+            block::Transparency::Opaque => false,
+            block::Transparency::Cutout => true,
+            block::Transparency::Translucent => neighbor.block_type != self.block_type,
+        }
+    }
 }
 
-pub fn create_voxel_pipeline(device: wgpu::Device, texture_format: wgpu::TextureFormat, light_bind_group_layout: &wgpu::BindGroupLayout, shader_module: wgpu::ShaderModule) -> wgpu::RenderPipeline {
-    let visibility = wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT;
-    let camera_bind_group_layout = render_utilities::create_bind_group_layout(&device, "camera_bind_group_layout", 0, visibility);
+pub fn create_voxel_pipeline(device: &wgpu::Device, texture_format: wgpu::TextureFormat, camera_bind_group_layout: &wgpu::BindGroupLayout, light_bind_group_layout: &wgpu::BindGroupLayout, sky_bind_group_layout: &wgpu::BindGroupLayout, block_texture_bind_group_layout: &wgpu::BindGroupLayout, shader_module: wgpu::ShaderModule) -> wgpu::RenderPipeline {
+    let bind_group_layouts = &[camera_bind_group_layout, light_bind_group_layout, sky_bind_group_layout, block_texture_bind_group_layout];
+    let pipeline_layout = render_utilities::create_pipeline_layout(device, "voxel_pipeline", bind_group_layouts);
+
+    render_utilities::create_render_pipeline(device, &pipeline_layout, texture_format, Some(texture::Texture::DEPTH_FORMAT), &[Vertex::desc(), InstanceRaw::desc()], shader_module, "voxel pipeline")
+}
 
-    let bind_group_layouts = &[&camera_bind_group_layout, &light_bind_group_layout];
-    let pipeline_layout = render_utilities::create_pipeline_layout(&device, "voxel_pipeline", bind_group_layouts);
+/// Alpha blended pipeline for translucent faces, drawn back to front after the opaque pass without writing depth.
+pub fn create_translucent_voxel_pipeline(device: &wgpu::Device, texture_format: wgpu::TextureFormat, camera_bind_group_layout: &wgpu::BindGroupLayout, light_bind_group_layout: &wgpu::BindGroupLayout, sky_bind_group_layout: &wgpu::BindGroupLayout, block_texture_bind_group_layout: &wgpu::BindGroupLayout, shader_module: wgpu::ShaderModule) -> wgpu::RenderPipeline {
+    let bind_group_layouts = &[camera_bind_group_layout, light_bind_group_layout, sky_bind_group_layout, block_texture_bind_group_layout];
+    let pipeline_layout = render_utilities::create_pipeline_layout(device, "translucent_voxel_pipeline", bind_group_layouts);
 
-    render_utilities::create_render_pipeline(&device, &pipeline_layout, texture_format, Some(texture::Texture::DEPTH_FORMAT), &[Vertex::desc()], shader_module, "voxel pipeline")
+    let options = render_utilities::PipelineOptions {
+        blend: wgpu::BlendState::ALPHA_BLENDING,
+        depth_write_enabled: false,
+        fragment_entry_point: "fs_translucent",
+        ..render_utilities::PipelineOptions::new("translucent voxel pipeline")
+    };
+    render_utilities::create_render_pipeline_with_options(device, &pipeline_layout, texture_format, Some(texture::Texture::DEPTH_FORMAT), &[Vertex::desc(), InstanceRaw::desc()], shader_module, options)
 }
@@ -0,0 +1,179 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use cgmath::{Point3, Vector3};
+
+use super::{
+    chunk::{Chunk, GeneratorConfig, CHUNK_SIZE},
+    history::{History, VoxelChange},
+    raycast, voxel,
+};
+
+/// What a batch of voxel writes changed, see `World::set_voxels`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EditSummary {
+    /// Voxels whose block changed, writing the block a voxel already has doesn't count.
+    pub changed: usize,
+    /// Writes dropped because their chunk isn't loaded.
+    pub skipped: usize,
+    /// Chunks queued for remeshing.
+    pub chunks: usize,
+}
+
+impl fmt::Display for EditSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "changed {} blocks in {} chunks", self.changed, self.chunks)?;
+        if self.skipped > 0 {
+            write!(f, ", {} outside the loaded world", self.skipped)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct World {
+    pub seed: u32,
+    pub generator: GeneratorConfig,
+    pub chunks: HashMap<Vector3<i32>, Chunk>,
+    /// Chunks whose voxels changed since their meshes were last built.
+    pub dirty_chunks: HashSet<Vector3<i32>>,
+    /// Chunks whose voxels changed since the world was last saved, see `save::WorldSave::save`.
+    pub modified_chunks: HashSet<Vector3<i32>>,
+    /// Every voxel write is recorded here so it can be undone.
+    pub history: History,
+}
+
+impl World {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            seed,
+            generator: GeneratorConfig::default(),
+            chunks: HashMap::new(),
+            dirty_chunks: HashSet::new(),
+            modified_chunks: HashSet::new(),
+            history: History::default(),
+        }
+    }
+
+    pub fn generate_chunk(&mut self, position: Vector3<i32>) -> &Chunk {
+        let mut chunk = Chunk::new(position);
+        chunk.build_voxels(self.seed, &self.generator);
+        self.chunks.insert(position, chunk);
+        &self.chunks[&position]
+    }
+
+    /// Generates every loaded chunk again with the current seed and generator settings, throwing
+    /// away any edits and the undo history. The chunks count as modified, so the next save replaces
+    /// the saved ones.
+    pub fn regenerate(&mut self) {
+        self.history.clear();
+        for (position, chunk) in self.chunks.iter_mut() {
+            *chunk = Chunk::new(*position);
+            chunk.build_voxels(self.seed, &self.generator);
+            self.dirty_chunks.insert(*position);
+            self.modified_chunks.insert(*position);
+        }
+    }
+
+    /// Splits a world voxel position into the position of its chunk and its position inside it.
+    pub fn split_position(position: Vector3<i32>) -> (Vector3<i32>, Vector3<usize>) {
+        let size = CHUNK_SIZE as i32;
+        let chunk = position.map(|value| value.div_euclid(size));
+        let local = position.map(|value| value.rem_euclid(size) as usize);
+        (chunk, local)
+    }
+
+    /// Voxel at a world position, voxels in chunks that aren't loaded are empty.
+    pub fn get_voxel(&self, position: Vector3<i32>) -> voxel::Voxel {
+        let (chunk, local) = Self::split_position(position);
+        match self.chunks.get(&chunk) {
+            Some(chunk) => chunk.voxels[local.x][local.y][local.z],
+            None => voxel::Voxel::new_empty(),
+        }
+    }
+
+    /// Replaces the voxel at a world position and marks its chunk dirty, along with the neighboring
+    /// chunks whose border faces touch it. Returns false if the chunk isn't loaded.
+    pub fn set_voxel(&mut self, position: Vector3<i32>, voxel: voxel::Voxel) -> bool {
+        self.set_voxels([(position, voxel)]).skipped == 0
+    }
+
+    /// Writes many voxels at once and records them in the history. Unlike calling `set_voxel` in a
+    /// loop, unchanged voxels don't mark anything dirty and every affected chunk is queued for
+    /// remeshing once, after all writes.
+    pub fn set_voxels(&mut self, edits: impl IntoIterator<Item = (Vector3<i32>, voxel::Voxel)>) -> EditSummary {
+        let (summary, changes) = self.write_voxels(edits);
+        self.history.record(changes);
+        summary
+    }
+
+    /// Reverts the newest transaction in the history, returns `None` if there is nothing to undo.
+    pub fn undo(&mut self) -> Option<EditSummary> {
+        let transaction = self.history.pop_undo()?;
+        let (summary, _) = self.write_voxels(transaction.changes.iter().rev().map(|change| (change.position, change.old)));
+        self.history.push_redo(transaction);
+        Some(summary)
+    }
+
+    /// Applies the newest undone transaction again, returns `None` if there is nothing to redo.
+    pub fn redo(&mut self) -> Option<EditSummary> {
+        let transaction = self.history.pop_redo()?;
+        let (summary, _) = self.write_voxels(transaction.changes.iter().map(|change| (change.position, change.new)));
+        self.history.push_undo(transaction);
+        Some(summary)
+    }
+
+    fn write_voxels(&mut self, edits: impl IntoIterator<Item = (Vector3<i32>, voxel::Voxel)>) -> (EditSummary, Vec<VoxelChange>) {
+        let mut summary = EditSummary::default();
+        let mut changes = Vec::new();
+        let mut dirty = HashSet::new();
+
+        for (position, voxel) in edits {
+            let (chunk_position, local) = Self::split_position(position);
+            let Some(chunk) = self.chunks.get_mut(&chunk_position) else {
+                summary.skipped += 1;
+                continue;
+            };
+
+            let current = &mut chunk.voxels[local.x][local.y][local.z];
+            if current.block_type == voxel.block_type {
+                continue;
+            }
+            changes.push(VoxelChange {
+                position,
+                old: *current,
+                new: voxel,
+            });
+            *current = voxel;
+
+            self.modified_chunks.insert(chunk_position);
+            dirty.insert(chunk_position);
+            dirty.extend(border_neighbors(chunk_position, local));
+        }
+
+        dirty.retain(|position| self.chunks.contains_key(position));
+        summary.changed = changes.len();
+        summary.chunks = dirty.len();
+        self.dirty_chunks.extend(dirty);
+        (summary, changes)
+    }
+
+    /// First non-empty voxel along a ray, see `raycast::raycast`.
+    pub fn raycast(&self, origin: Point3<f32>, direction: Vector3<f32>, max_distance: f32) -> Option<raycast::RaycastHit> {
+        raycast::raycast(self, origin, direction, max_distance, |voxel| !voxel.is_empty())
+    }
+}
+
+/// Chunks next to `chunk_position` whose border faces touch the voxel at `local`.
+fn border_neighbors(chunk_position: Vector3<i32>, local: Vector3<usize>) -> impl Iterator<Item = Vector3<i32>> {
+    (0..3).filter_map(move |axis| {
+        let mut offset = Vector3::new(0, 0, 0);
+        if local[axis] == 0 {
+            offset[axis] = -1;
+        } else if local[axis] == CHUNK_SIZE - 1 {
+            offset[axis] = 1;
+        } else {
+            return None;
+        }
+        Some(chunk_position + offset)
+    })
+}
@@ -0,0 +1,277 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+use anyhow::{ensure, Context};
+use cgmath::{InnerSpace, Vector3};
+
+use crate::voxel_things::{
+    region::Clipboard,
+    voxel::{BlockType, Voxel},
+};
+
+pub const EXTENSION: &str = "obj";
+pub const MAX_RESOLUTION: i32 = 256;
+/// Used for meshes without a material or vertex colors.
+const DEFAULT_BLOCK: BlockType = BlockType::Stone;
+
+/// How `voxelize` turns a mesh into voxels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VoxelizeOptions {
+    /// Voxels along the longest side of the mesh, the other sides keep its proportions.
+    pub resolution: i32,
+    /// Also fills the voxels enclosed by the surface, otherwise only the surface is kept.
+    pub fill_interior: bool,
+}
+
+/// `res/models`, where the models `voxelize` can load are.
+pub fn models_directory() -> PathBuf {
+    Path::new(env!("OUT_DIR")).join("res").join("models")
+}
+
+/// A triangle in voxel space and the block it becomes.
+struct Triangle {
+    vertices: [Vector3<f32>; 3],
+    block_type: BlockType,
+}
+
+/// Voxelizes the OBJ at `path`. Every voxel a triangle touches becomes a block, the block whose
+/// color is closest to the diffuse color of the triangle's material, or to its vertex colors.
+pub fn voxelize(path: &Path, options: VoxelizeOptions) -> anyhow::Result<Clipboard> {
+    ensure!((1..=MAX_RESOLUTION).contains(&options.resolution), "the resolution has to be between 1 and {}", MAX_RESOLUTION);
+
+    let load_options = tobj::LoadOptions {
+        triangulate: true,
+        single_index: true,
+        ..Default::default()
+    };
+    let (models, materials) = tobj::load_obj(path, &load_options).with_context(|| format!("failed to load {}", path.display()))?;
+    let materials = materials.unwrap_or_else(|error| {
+        log::warn!("{} has no usable materials, using {}: {}", path.display(), DEFAULT_BLOCK.definition().name, error);
+        Vec::new()
+    });
+
+    let mut triangles = Vec::new();
+    for model in &models {
+        let mesh = &model.mesh;
+        let material_block = mesh.material_id.and_then(|id| materials.get(id)).map(|material| BlockType::nearest_to_color(material.diffuse.map(to_byte)));
+        let position = |index: u32| Vector3::new(mesh.positions[index as usize * 3], mesh.positions[index as usize * 3 + 1], mesh.positions[index as usize * 3 + 2]);
+        for face in mesh.indices.chunks_exact(3) {
+            let block_type = match material_block {
+                Some(block_type) => block_type,
+                None if !mesh.vertex_color.is_empty() => {
+                    let color = face.iter().fold([0.0; 3], |sum, index| [0, 1, 2].map(|channel| sum[channel] + mesh.vertex_color[*index as usize * 3 + channel] / 3.0));
+                    BlockType::nearest_to_color(color.map(to_byte))
+                }
+                None => DEFAULT_BLOCK,
+            };
+            triangles.push(Triangle {
+                vertices: [position(face[0]), position(face[1]), position(face[2])],
+                block_type,
+            });
+        }
+    }
+    ensure!(!triangles.is_empty(), "{} has no faces", path.display());
+    voxelize_triangles(triangles, options)
+}
+
+/// Scales the triangles to the resolution and fills the voxels they touch.
+fn voxelize_triangles(mut triangles: Vec<Triangle>, options: VoxelizeOptions) -> anyhow::Result<Clipboard> {
+    // Scale so the longest side spans `resolution` voxels, with the minimum corner at the origin.
+    let vertices = || triangles.iter().flat_map(|triangle| triangle.vertices);
+    let min = vertices().fold(Vector3::new(f32::MAX, f32::MAX, f32::MAX), |min, vertex| Vector3::new(min.x.min(vertex.x), min.y.min(vertex.y), min.z.min(vertex.z)));
+    let max = vertices().fold(Vector3::new(f32::MIN, f32::MIN, f32::MIN), |max, vertex| Vector3::new(max.x.max(vertex.x), max.y.max(vertex.y), max.z.max(vertex.z)));
+    let extent = max - min;
+    let longest = extent.x.max(extent.y).max(extent.z);
+    let scale = if longest > 0.0 { options.resolution as f32 / longest } else { 1.0 };
+    let size = extent.map(|value| ((value * scale).ceil() as i32).clamp(1, options.resolution));
+    for triangle in &mut triangles {
+        triangle.vertices = triangle.vertices.map(|vertex| (vertex - min) * scale);
+    }
+
+    let mut grid = Grid::new(size);
+    for triangle in &triangles {
+        grid.rasterize(triangle);
+    }
+    if options.fill_interior {
+        grid.fill_interior();
+    }
+
+    let voxels = grid.cells.iter().map(|cell| cell.map_or(Voxel::new_empty(), Voxel::new)).collect();
+    Clipboard::from_voxels(size, voxels).context("the mesh produced no voxels")
+}
+
+fn to_byte(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// The voxels of the mesh being voxelized, in the order `Clipboard::voxels` uses.
+struct Grid {
+    size: Vector3<i32>,
+    cells: Vec<Option<BlockType>>,
+}
+
+impl Grid {
+    fn new(size: Vector3<i32>) -> Self {
+        Self {
+            size,
+            cells: vec![None; (size.x * size.y * size.z) as usize],
+        }
+    }
+
+    fn index(&self, position: Vector3<i32>) -> usize {
+        ((position.x * self.size.y + position.y) * self.size.z + position.z) as usize
+    }
+
+    fn contains(&self, position: Vector3<i32>) -> bool {
+        (0..3).all(|axis| (0..self.size[axis]).contains(&position[axis]))
+    }
+
+    /// Sets every voxel the triangle overlaps that isn't set yet.
+    fn rasterize(&mut self, triangle: &Triangle) {
+        let [a, b, c] = triangle.vertices;
+        let low = Vector3::new(a.x.min(b.x).min(c.x), a.y.min(b.y).min(c.y), a.z.min(b.z).min(c.z));
+        let high = Vector3::new(a.x.max(b.x).max(c.x), a.y.max(b.y).max(c.y), a.z.max(b.z).max(c.z));
+        let (low, high) = (0..3).fold((Vector3::new(0, 0, 0), Vector3::new(0, 0, 0)), |(mut start, mut end), axis| {
+            start[axis] = (low[axis].floor() as i32).clamp(0, self.size[axis] - 1);
+            end[axis] = (high[axis].floor() as i32).clamp(0, self.size[axis] - 1);
+            (start, end)
+        });
+
+        for x in low.x..=high.x {
+            for y in low.y..=high.y {
+                for z in low.z..=high.z {
+                    let position = Vector3::new(x, y, z);
+                    let index = self.index(position);
+                    if self.cells[index].is_none() && overlaps_voxel(&triangle.vertices, position) {
+                        self.cells[index] = Some(triangle.block_type);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Fills every empty voxel that can't be reached from outside the grid. Each takes the block
+    /// of the closest surface voxel before it along x.
+    fn fill_interior(&mut self) {
+        let mut outside = vec![false; self.cells.len()];
+        let mut queue = VecDeque::new();
+        for x in 0..self.size.x {
+            for y in 0..self.size.y {
+                for z in 0..self.size.z {
+                    let position = Vector3::new(x, y, z);
+                    let on_border = (0..3).any(|axis| position[axis] == 0 || position[axis] == self.size[axis] - 1);
+                    if on_border && self.cells[self.index(position)].is_none() {
+                        outside[self.index(position)] = true;
+                        queue.push_back(position);
+                    }
+                }
+            }
+        }
+
+        let steps = [Vector3::unit_x(), -Vector3::unit_x(), Vector3::unit_y(), -Vector3::unit_y(), Vector3::unit_z(), -Vector3::unit_z()];
+        while let Some(position) = queue.pop_front() {
+            for step in steps {
+                let neighbor = position + step;
+                if !self.contains(neighbor) {
+                    continue;
+                }
+                let index = self.index(neighbor);
+                if !outside[index] && self.cells[index].is_none() {
+                    outside[index] = true;
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        for y in 0..self.size.y {
+            for z in 0..self.size.z {
+                let mut block_type = DEFAULT_BLOCK;
+                for x in 0..self.size.x {
+                    let index = self.index(Vector3::new(x, y, z));
+                    match self.cells[index] {
+                        Some(surface) => block_type = surface,
+                        None if !outside[index] => self.cells[index] = Some(block_type),
+                        None => {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Whether a triangle touches the unit cube at `position`, by looking for a separating axis
+/// among the cube's faces, the triangle's plane and the cross products of their edges.
+fn overlaps_voxel(vertices: &[Vector3<f32>; 3], position: Vector3<i32>) -> bool {
+    const HALF: f32 = 0.5;
+    let center = position.map(|value| value as f32 + HALF);
+    let vertices = vertices.map(|vertex| vertex - center);
+    let separated = |axis: Vector3<f32>| {
+        let projections = vertices.map(|vertex| vertex.dot(axis));
+        let radius = HALF * (axis.x.abs() + axis.y.abs() + axis.z.abs());
+        projections.iter().copied().fold(f32::MAX, f32::min) > radius || projections.iter().copied().fold(f32::MIN, f32::max) < -radius
+    };
+
+    let edges = [vertices[1] - vertices[0], vertices[2] - vertices[1], vertices[0] - vertices[2]];
+    let box_axes = [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()];
+    if box_axes.iter().any(|axis| separated(*axis)) || separated(edges[0].cross(edges[1])) {
+        return false;
+    }
+    !edges.iter().any(|edge| box_axes.iter().any(|axis| separated(axis.cross(*edge))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The twelve triangles of the cube from the origin to (1, 1, 1).
+    fn unit_cube() -> Vec<Triangle> {
+        let corner = |index: usize| Vector3::new((index & 1) as f32, (index >> 1 & 1) as f32, (index >> 2 & 1) as f32);
+        let faces = [[0, 2, 6, 4], [1, 5, 7, 3], [0, 4, 5, 1], [2, 3, 7, 6], [0, 1, 3, 2], [4, 6, 7, 5]];
+        faces
+            .iter()
+            .flat_map(|[a, b, c, d]| [[*a, *b, *c], [*a, *c, *d]])
+            .map(|face| Triangle {
+                vertices: face.map(corner),
+                block_type: BlockType::Dirt,
+            })
+            .collect()
+    }
+
+    fn filled(clipboard: &Clipboard) -> usize {
+        clipboard.voxels().iter().filter(|voxel| voxel.block_type != BlockType::Empty).count()
+    }
+
+    #[test]
+    fn voxelizes_a_cube() {
+        let hollow = voxelize_triangles(unit_cube(), VoxelizeOptions { resolution: 4, fill_interior: false }).unwrap();
+        assert_eq!(hollow.size(), Vector3::new(4, 4, 4));
+        // Only the 2x2x2 voxels in the middle don't touch a face.
+        assert_eq!(filled(&hollow), 64 - 8);
+        assert!(hollow.voxels().iter().all(|voxel| matches!(voxel.block_type, BlockType::Empty | BlockType::Dirt)));
+
+        let solid = voxelize_triangles(unit_cube(), VoxelizeOptions { resolution: 4, fill_interior: true }).unwrap();
+        assert_eq!(filled(&solid), 64);
+        assert!(solid.voxels().iter().all(|voxel| voxel.block_type == BlockType::Dirt));
+    }
+
+    #[test]
+    fn voxelizes_a_flat_triangle() {
+        let triangle = Triangle {
+            vertices: [Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0)],
+            block_type: BlockType::Stone,
+        };
+        let clipboard = voxelize_triangles(vec![triangle], VoxelizeOptions { resolution: 4, fill_interior: true }).unwrap();
+        assert_eq!(clipboard.size(), Vector3::new(4, 1, 4));
+        // Every voxel whose lowest corner is on or below the hypotenuse, x + z = 4.
+        assert_eq!(filled(&clipboard), 4 + 4 + 3 + 2);
+    }
+
+    #[test]
+    fn rejects_resolutions_past_the_cap() {
+        for resolution in [0, MAX_RESOLUTION + 1] {
+            // The resolution is checked before the file is loaded.
+            let error = voxelize(Path::new("missing.obj"), VoxelizeOptions { resolution, fill_interior: false }).unwrap_err();
+            assert!(error.to_string().contains("resolution"), "{}", error);
+        }
+    }
+}